@@ -1,4 +1,8 @@
-#[derive(Debug, PartialEq)]
+use source_map::Span;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Ty {
     BoolTy,
     IntTy,
@@ -6,13 +10,67 @@ pub enum Ty {
     StrTy,
 }
 
+impl Ty {
+    pub fn is_numeric(self) -> bool {
+        self == Ty::IntTy || self == Ty::FloatTy
+    }
+
+    /// The keyword naming the type in source code.
+    pub fn name(self) -> &'static str {
+        match self {
+            Ty::BoolTy => "bool",
+            Ty::IntTy => "int",
+            Ty::FloatTy => "float",
+            Ty::StrTy => "str",
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}`", self.name())
+    }
+}
+
 /// A Name references an identifier in the identifier table.
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Name(pub u64);
 
+/// The identifier table, which interns every identifier of a program as a
+/// `Name`, so identifiers can be compared and hashed cheaply.
+#[derive(Default)]
+pub struct IdentTable {
+    names: HashMap<String, Name>,
+    idents: Vec<String>,
+}
+
+impl IdentTable {
+    /// Returns the name of an identifier, interning it if it's new.
+    pub fn intern(&mut self, ident: &str) -> Name {
+        if let Some(&name) = self.names.get(ident) {
+            return name;
+        }
+        let name = Name(self.idents.len() as u64);
+        self.names.insert(ident.to_owned(), name);
+        self.idents.push(ident.to_owned());
+        name
+    }
+
+    /// Returns the identifier a name was interned from.
+    pub fn get(&self, name: Name) -> &str {
+        &self.idents[name.0 as usize]
+    }
+
+    /// Returns the name of an identifier, if it has been interned.
+    pub fn lookup(&self, ident: &str) -> Option<Name> {
+        self.names.get(ident).cloned()
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Ident {
     pub name: Name,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq)]
@@ -20,14 +78,25 @@ pub enum Lit {
     IntLit(u64),
     FloatLit(f64),
     StrLit(String),
+    BoolLit(bool),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BinOp {
     Add,
     Sub,
     Mult,
+    /// Division yielding a float, e.g. `7 / 2 == 3.5`.
     Div,
+    /// Integer division, e.g. `7 div 2 == 3`.
+    IntDiv,
+    Mod,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     Eq,
     Ne,
     Lt,
@@ -36,30 +105,158 @@ pub enum BinOp {
     Ge,
 }
 
-#[derive(Debug, PartialEq)]
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match *self {
+                BinOp::Add => "+",
+                BinOp::Sub => "-",
+                BinOp::Mult => "*",
+                BinOp::Div => "/",
+                BinOp::IntDiv => "div",
+                BinOp::Mod => "mod",
+                BinOp::Pow => "**",
+                BinOp::BitAnd => "&",
+                BinOp::BitOr => "|",
+                BinOp::BitXor => "^",
+                BinOp::Shl => "<<",
+                BinOp::Shr => ">>",
+                BinOp::Eq => "==",
+                BinOp::Ne => "!=",
+                BinOp::Lt => "<",
+                BinOp::Le => "<=",
+                BinOp::Gt => ">",
+                BinOp::Ge => ">=",
+            }
+        )
+    }
+}
+
+impl BinOp {
+    /// How tightly the operator binds its operands. Operators with greater
+    /// precedence are grouped first, e.g. `a + b * c` is `a + (b * c)`.
+    ///
+    /// Bitwise operators bind looser than comparisons, so that they can
+    /// combine conditions, e.g. `a < b & b < c`.
+    pub fn precedence(self) -> u32 {
+        match self {
+            BinOp::BitOr => 1,
+            BinOp::BitXor => 2,
+            BinOp::BitAnd => 3,
+            BinOp::Eq | BinOp::Ne => 4,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 5,
+            BinOp::Shl | BinOp::Shr => 6,
+            BinOp::Add | BinOp::Sub => 7,
+            BinOp::Mult | BinOp::Div | BinOp::IntDiv | BinOp::Mod => 8,
+            BinOp::Pow => 10,
+        }
+    }
+
+    /// Whether a chain of the operator groups from the right, e.g.
+    /// `a ** b ** c` is `a ** (b ** c)`.
+    pub fn is_right_assoc(self) -> bool {
+        self == BinOp::Pow
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UnOp {
     Neg,
     Not,
 }
 
+impl fmt::Display for UnOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnOp::Neg => write!(f, "-"),
+            UnOp::Not => write!(f, "!"),
+        }
+    }
+}
+
+impl UnOp {
+    /// Unary operators bind tighter than every binary operator but `**`.
+    pub fn precedence(self) -> u32 {
+        9
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
 #[derive(Debug, PartialEq)]
-pub enum Expr {
+pub enum ExprKind {
     BinaryOp(BinOp, Box<Expr>, Box<Expr>),
     UnaryOp(UnOp, Box<Expr>),
     Lit(Lit),
     Ident(Ident),
     Paren(Box<Expr>),
+    /// An explicit type conversion, e.g. `int(x)`.
+    Cast(Ty, Box<Expr>),
+    /// A function call, e.g. `len(s)`.
+    Call(Ident, Vec<Expr>),
+    /// An argument of `write` with a field width and an optional precision,
+    /// e.g. `x:8:2`.
+    Formatted(Box<Expr>, Box<Expr>, Option<Box<Expr>>),
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Block {
     pub stmts: Vec<Stmt>,
+    pub span: Span,
 }
 
+/// The direction in which a `for` loop steps its control variable.
 #[derive(Debug, PartialEq)]
-pub enum Stmt {
+pub enum ForDir {
+    To,
+    Downto,
+}
+
+/// A pattern to be matched against the value of a `case` statement.
+#[derive(Debug, PartialEq)]
+pub struct Pat {
+    pub kind: PatKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PatKind {
+    /// An integer, which unlike an integer literal may be negative, e.g.
+    /// `-1`.
+    Int(i64),
+    /// An inclusive range of integers, e.g. `-3..5`.
+    Range(i64, i64),
+    /// A string or boolean literal.
+    Lit(Lit),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CaseArm {
+    pub pats: Vec<Pat>,
+    pub block: Box<Block>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StmtKind {
     While(Expr, Box<Block>),
+    For(Ident, Expr, ForDir, Expr, Box<Block>),
+    Repeat(Box<Block>, Expr),
+    Break,
+    Continue,
     If(Expr, Box<Block>, Option<Box<Block>>),
+    Case(Expr, Vec<CaseArm>, Option<Box<Block>>),
     Assign(Ident, Expr),
     BlockStmt(Box<Block>),
     Call(Ident, Vec<Expr>),
@@ -71,9 +268,40 @@ pub struct Decl {
     pub ty: Ty,
 }
 
+/// A constant declaration, e.g. `const pi: float = 3.14159;`.
+#[derive(Debug, PartialEq)]
+pub struct ConstDecl {
+    pub ident: Ident,
+    pub ty: Ty,
+    pub expr: Expr,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Program {
     pub name: Ident,
+    /// The units named by the `uses` clause.
+    pub uses: Vec<Ident>,
+    pub consts: Vec<ConstDecl>,
+    pub decls: Vec<Decl>,
+    pub stmts: Vec<Stmt>,
+}
+
+/// A unit, whose constants and variables are available to the programs and
+/// units using it.
+#[derive(Debug, PartialEq)]
+pub struct Unit {
+    pub name: Ident,
+    /// The units named by the `uses` clause.
+    pub uses: Vec<Ident>,
+    pub consts: Vec<ConstDecl>,
+    pub decls: Vec<Decl>,
+}
+
+/// Declarations followed by statements, as typed into the REPL, e.g.
+/// `let x: int; x = 1;`.
+#[derive(Debug, PartialEq)]
+pub struct Items {
+    pub consts: Vec<ConstDecl>,
     pub decls: Vec<Decl>,
     pub stmts: Vec<Stmt>,
 }
@@ -0,0 +1,17 @@
+extern crate blaise;
+
+use blaise::lsp;
+use std::io;
+use std::process;
+
+fn main() {
+    let stdin = io::stdin();
+    match lsp::run(stdin.lock(), io::stdout()) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("blaise-lsp: {}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use ast::{IdentTable, Name, Ty};
+use interp::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A function or procedure predefined by the language. Every execution
+/// engine has to implement all of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Builtin {
+    /// `write(...)`, writes its arguments to the output. Each argument may
+    /// be followed by a field width and, for floats, a precision, e.g.
+    /// `write(x:8:2)`.
+    Write,
+    /// `writeln(...)`, like `write`, followed by a line break.
+    Writeln,
+    /// `read(...)`, reads a whitespace separated word from the input into
+    /// each of its arguments, which have to be variables.
+    Read,
+    /// `readln(...)`, like `read`, then skips the rest of the line. A
+    /// string as the last argument takes the whole rest of the line.
+    Readln,
+    /// `len(s: str): int`, the number of characters of `s`.
+    Len,
+    /// `substr(s: str, start: int, count: int): str`, up to `count`
+    /// characters of `s` starting from the character at index `start`.
+    Substr,
+    /// `char_at(s: str, index: int): str`, the character of `s` at `index`.
+    CharAt,
+    /// `find(s: str, pat: str): int`, the index of the first occurrence of
+    /// `pat` in `s`, or -1 if there is none.
+    Find,
+    /// `upper(s: str): str`, `s` with every character in uppercase.
+    Upper,
+    /// `lower(s: str): str`, `s` with every character in lowercase.
+    Lower,
+}
+
+/// The parameters a builtin takes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Params {
+    /// Exactly one argument of each type.
+    Fixed(&'static [Ty]),
+    /// Any number of values of any type, optionally formatted.
+    Values,
+    /// Any number of variables.
+    Vars,
+}
+
+/// Every builtin of the language.
+pub const BUILTINS: &[Builtin] = &[
+    Builtin::Write,
+    Builtin::Writeln,
+    Builtin::Read,
+    Builtin::Readln,
+    Builtin::Len,
+    Builtin::Substr,
+    Builtin::CharAt,
+    Builtin::Find,
+    Builtin::Upper,
+    Builtin::Lower,
+];
+
+impl Builtin {
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Write => "write",
+            Builtin::Writeln => "writeln",
+            Builtin::Read => "read",
+            Builtin::Readln => "readln",
+            Builtin::Len => "len",
+            Builtin::Substr => "substr",
+            Builtin::CharAt => "char_at",
+            Builtin::Find => "find",
+            Builtin::Upper => "upper",
+            Builtin::Lower => "lower",
+        }
+    }
+
+    pub fn params(self) -> Params {
+        match self {
+            Builtin::Write | Builtin::Writeln => Params::Values,
+            Builtin::Read | Builtin::Readln => Params::Vars,
+            Builtin::Len | Builtin::Upper | Builtin::Lower => Params::Fixed(&[Ty::StrTy]),
+            Builtin::Substr => Params::Fixed(&[Ty::StrTy, Ty::IntTy, Ty::IntTy]),
+            Builtin::CharAt => Params::Fixed(&[Ty::StrTy, Ty::IntTy]),
+            Builtin::Find => Params::Fixed(&[Ty::StrTy, Ty::StrTy]),
+        }
+    }
+
+    /// The type of the value returned by the builtin, or `None` if it's a
+    /// procedure, which returns nothing.
+    pub fn ret_ty(self) -> Option<Ty> {
+        match self {
+            Builtin::Write | Builtin::Writeln | Builtin::Read | Builtin::Readln => None,
+            Builtin::Len | Builtin::Find => Some(Ty::IntTy),
+            Builtin::Substr | Builtin::CharAt | Builtin::Upper | Builtin::Lower => Some(Ty::StrTy),
+        }
+    }
+}
+
+/// Maps the names of the builtins used by a program to their builtins.
+/// Builtins whose names don't appear in the program are left out.
+pub fn resolve(ident_table: &IdentTable) -> HashMap<Name, Builtin> {
+    BUILTINS
+        .iter()
+        .filter_map(|&builtin| {
+            ident_table
+                .lookup(builtin.name())
+                .map(|name| (name, builtin))
+        })
+        .collect()
+}
+
+/// What a native returns: its value, which a procedure doesn't have, or
+/// why it failed.
+pub type NativeResult = Result<Option<Value>, String>;
+
+type NativeFn = dyn Fn(&[Value]) -> NativeResult;
+
+/// A function or procedure defined by the program embedding blaise. It's
+/// called with one value of each parameter type, and returns a value of its
+/// return type, if it has one, or a message telling why it failed.
+pub struct Native {
+    pub name: String,
+    pub params: Vec<Ty>,
+    /// The type of the value returned, or `None` for a procedure.
+    pub ret_ty: Option<Ty>,
+    func: Box<NativeFn>,
+}
+
+impl Native {
+    pub fn call(&self, args: &[Value]) -> NativeResult {
+        (self.func)(args)
+    }
+}
+
+/// The functions and procedures a host defines for the programs it runs,
+/// which are type checked and called just like builtins.
+#[derive(Clone, Default)]
+pub struct Natives {
+    natives: Vec<Rc<Native>>,
+}
+
+impl Natives {
+    pub fn new() -> Natives {
+        Natives::default()
+    }
+
+    /// Defines a function taking arguments of the types `params`, which
+    /// returns a value of type `ret_ty`, or nothing if it's `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a builtin or another native already has the name.
+    pub fn register<F>(&mut self, name: &str, params: &[Ty], ret_ty: Option<Ty>, func: F)
+    where
+        F: Fn(&[Value]) -> NativeResult + 'static,
+    {
+        assert!(
+            BUILTINS.iter().all(|builtin| builtin.name() != name),
+            "`{}` is a builtin",
+            name
+        );
+        assert!(
+            self.natives.iter().all(|native| native.name != name),
+            "`{}` is already defined",
+            name
+        );
+        self.natives.push(Rc::new(Native {
+            name: name.to_owned(),
+            params: params.to_vec(),
+            ret_ty,
+            func: Box::new(func),
+        }));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Native> {
+        self.natives.iter().map(|native| &**native)
+    }
+
+    /// Maps the names of the natives used by a program to their natives,
+    /// like `resolve` does for builtins.
+    pub fn resolve(&self, ident_table: &IdentTable) -> HashMap<Name, Rc<Native>> {
+        self.natives
+            .iter()
+            .filter_map(|native| {
+                ident_table
+                    .lookup(&native.name)
+                    .map(|name| (name, native.clone()))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for Natives {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.natives.iter().map(|native| &native.name))
+            .finish()
+    }
+}
@@ -0,0 +1,659 @@
+use ast;
+use errors::{self, Diag};
+use parser::Parser;
+use scanner::{Category, Scanner, WordStream};
+use source_map::{BytePos, Pos, SourceFile, Span};
+use std::fmt::{self, Write};
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::result;
+use std::vec;
+
+type Result<T> = result::Result<T, Diag>;
+
+/// Text between words, which carries no meaning to the parser.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+    /// Text rejected by the scanner, whose errors are reported by the
+    /// parser.
+    Skipped,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+/// A word along with the trivia around it. Trivia following a word on its
+/// line trails it, and any other trivia leads the next word, so that the
+/// end of the file is a token leading with the trivia after the last word.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub category: Category,
+    pub text: String,
+    pub span: Span,
+    pub leading: Vec<Trivia>,
+    pub trailing: Vec<Trivia>,
+}
+
+/// The kind of a syntax tree node, which mostly mirrors the AST.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeKind {
+    Program,
+    Unit,
+    Uses,
+    ConstDecl,
+    Decl,
+    Block,
+    WhileStmt,
+    ForStmt,
+    RepeatStmt,
+    BreakStmt,
+    ContinueStmt,
+    IfStmt,
+    CaseStmt,
+    CaseArm,
+    AssignStmt,
+    CallStmt,
+    BinaryExpr,
+    UnaryExpr,
+    LitExpr,
+    IdentExpr,
+    ParenExpr,
+    CastExpr,
+    CallExpr,
+    FormattedExpr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Element {
+    Node(Node),
+    Token(Token),
+}
+
+/// A node of a concrete syntax tree, which holds every token of its source
+/// text, trivia included. The root node of a file reprints it byte for byte
+/// through `Display`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub children: Vec<Element>,
+}
+
+impl Node {
+    /// Returns the tokens of the node, in order.
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut tokens = vec![];
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a Token>) {
+        for child in &self.children {
+            match *child {
+                Element::Node(ref node) => node.collect_tokens(tokens),
+                Element::Token(ref token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// Returns the span from the node's first word to its last one,
+    /// excluding trivia.
+    pub fn span(&self) -> Span {
+        let tokens = self.tokens();
+        Span {
+            start: tokens[0].span.start,
+            end: tokens[tokens.len() - 1].span.end,
+        }
+    }
+
+    /// Shows the tree, one node, token or trivia per line, indented by
+    /// depth. Positions are byte offsets into the file.
+    pub fn dump(&self, file: &SourceFile) -> String {
+        let mut text = String::new();
+        self.write_dump(&mut text, file, 0);
+        text
+    }
+
+    fn write_dump(&self, text: &mut String, file: &SourceFile, depth: usize) {
+        let offset = |pos: BytePos| (pos - file.start_pos()).to_usize();
+        let span = self.span();
+        let _ = writeln!(
+            text,
+            "{:indent$}{:?} {}..{}",
+            "",
+            self.kind,
+            offset(span.start),
+            offset(span.end),
+            indent = depth * 2
+        );
+        let indent = (depth + 1) * 2;
+        for child in &self.children {
+            let token = match *child {
+                Element::Node(ref node) => {
+                    node.write_dump(text, file, depth + 1);
+                    continue;
+                }
+                Element::Token(ref token) => token,
+            };
+            for trivia in &token.leading {
+                let _ = writeln!(text, "{:indent$}{:?} {:?}", "", trivia.kind, trivia.text);
+            }
+            let _ = writeln!(
+                text,
+                "{:indent$}{:?} {}..{} {:?}",
+                "",
+                token.category,
+                offset(token.span.start),
+                offset(token.span.end),
+                token.text
+            );
+            for trivia in &token.trailing {
+                let _ = writeln!(text, "{:indent$}{:?} {:?}", "", trivia.kind, trivia.text);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in self.tokens() {
+            for trivia in &token.leading {
+                write!(f, "{}", trivia.text)?;
+            }
+            write!(f, "{}", token.text)?;
+            for trivia in &token.trailing {
+                write!(f, "{}", trivia.text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a file into tokens, keeping all of its text. Text the scanner
+/// rejects is kept as skipped trivia, without reporting errors.
+pub fn tokenize(file: &Rc<SourceFile>) -> Vec<Token> {
+    let mut scanner = Scanner::new(file.clone());
+    let mut tokens: Vec<Token> = vec![];
+    let mut pos = file.start_pos();
+    loop {
+        let word = match scanner.next_word() {
+            Ok(word) => word,
+            // The scanner has moved past the rejected text.
+            Err(_) => continue,
+        };
+        let span = word.lexeme;
+
+        let gap = file.span_to_snippet(Span {
+            start: pos,
+            end: span.start,
+        });
+        let mut trivia = split_trivia(gap).into_iter().peekable();
+        if let Some(prev_token) = tokens.last_mut() {
+            while let Some(piece) = trivia.next_if(|piece| !piece.text.starts_with(is_line_break)) {
+                prev_token.trailing.push(piece);
+            }
+        }
+        tokens.push(Token {
+            category: word.category,
+            text: file.span_to_snippet(span).to_owned(),
+            span,
+            leading: trivia.collect(),
+            trailing: vec![],
+        });
+        if word.category == Category::Eof {
+            return tokens;
+        }
+        pos = span.end;
+    }
+}
+
+fn is_line_break(c: char) -> bool {
+    c == '\n' || c == '\r'
+}
+
+/// Splits the text between two words into comments, skipped text, and runs
+/// of whitespace, where line breaks start new runs.
+fn split_trivia(text: &str) -> Vec<Trivia> {
+    let mut trivia = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let (kind, len) = if rest.starts_with("//") {
+            let line_len = rest.find('\n').unwrap_or(rest.len());
+            let len = rest[..line_len].trim_end_matches('\r').len();
+            (TriviaKind::Comment, len)
+        } else if is_line_break(c) {
+            let len = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            (TriviaKind::Whitespace, len)
+        } else if c.is_whitespace() {
+            let len = rest
+                .find(|c: char| !c.is_whitespace() || is_line_break(c))
+                .unwrap_or(rest.len());
+            (TriviaKind::Whitespace, len)
+        } else {
+            let len = rest
+                .char_indices()
+                .find(|&(i, c)| c.is_whitespace() || rest[i..].starts_with("//"))
+                .map_or(rest.len(), |(i, _)| i);
+            (TriviaKind::Skipped, len)
+        };
+        trivia.push(Trivia {
+            kind,
+            text: rest[..len].to_owned(),
+        });
+        rest = &rest[len..];
+    }
+    trivia
+}
+
+/// Parses the program or unit of a file into its concrete syntax tree.
+pub fn parse(file: Rc<SourceFile>, handler: &errors::Handler) -> Result<Node> {
+    let mut parser = Parser::new(WordStream::new(Scanner::new(file.clone()), handler));
+    let mut builder = Builder::new(tokenize(&file));
+    if parser.is_unit() {
+        builder.unit(&parser.parse_unit()?);
+    } else {
+        builder.program(&parser.parse_program()?);
+    }
+    Ok(builder.finish())
+}
+
+/// Builds a tree out of the tokens of a file by walking its AST, where
+/// each node takes the tokens within its span.
+struct Builder {
+    tokens: Peekable<vec::IntoIter<Token>>,
+    /// The nodes being built, each one a child of the previous one.
+    stack: Vec<Node>,
+}
+
+impl Builder {
+    fn new(tokens: Vec<Token>) -> Builder {
+        Builder {
+            tokens: tokens.into_iter().peekable(),
+            stack: vec![],
+        }
+    }
+
+    /// Returns the root node, which takes the tokens left.
+    fn finish(mut self) -> Node {
+        while self.tokens.peek().is_some() {
+            self.bump();
+        }
+        let root = self.stack.pop().unwrap();
+        assert!(self.stack.is_empty(), "has to have closed every node!");
+        root
+    }
+
+    fn enter(&mut self, kind: NodeKind) {
+        self.stack.push(Node {
+            kind,
+            children: vec![],
+        });
+    }
+
+    fn exit(&mut self) {
+        let node = self.stack.pop().unwrap();
+        self.stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(Element::Node(node));
+    }
+
+    fn bump(&mut self) {
+        let token = self.tokens.next().unwrap();
+        self.stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(Element::Token(token));
+    }
+
+    fn peek_category(&mut self) -> Category {
+        self.tokens
+            .peek()
+            .map_or(Category::Eof, |token| token.category)
+    }
+
+    /// Takes the tokens up to and including the next one of a category.
+    fn bump_through(&mut self, category: Category) {
+        loop {
+            let next_category = self.peek_category();
+            if next_category == Category::Eof {
+                return;
+            }
+            self.bump();
+            if next_category == category {
+                return;
+            }
+        }
+    }
+
+    /// Takes the tokens starting before a position.
+    fn advance_to(&mut self, pos: BytePos) {
+        while self.peek_category() != Category::Eof && self.tokens.peek().unwrap().span.start < pos
+        {
+            self.bump();
+        }
+    }
+
+    /// Takes the tokens ending at or before a position.
+    fn advance_through(&mut self, pos: BytePos) {
+        while self.peek_category() != Category::Eof && self.tokens.peek().unwrap().span.end <= pos {
+            self.bump();
+        }
+    }
+
+    fn program(&mut self, program: &ast::Program) {
+        self.enter(NodeKind::Program);
+        self.bump_through(Category::Semi);
+        self.uses(&program.uses);
+        self.decls(&program.consts, &program.decls);
+        for stmt in &program.stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn unit(&mut self, unit: &ast::Unit) {
+        self.enter(NodeKind::Unit);
+        self.bump_through(Category::Semi);
+        self.uses(&unit.uses);
+        self.decls(&unit.consts, &unit.decls);
+    }
+
+    fn uses(&mut self, uses: &[ast::Ident]) {
+        if !uses.is_empty() {
+            self.enter(NodeKind::Uses);
+            self.bump_through(Category::Semi);
+            self.exit();
+        }
+    }
+
+    /// Adds the declarations in the order they were given.
+    fn decls(&mut self, consts: &[ast::ConstDecl], decls: &[ast::Decl]) {
+        let mut consts = consts.iter().peekable();
+        let mut decls = decls.iter().peekable();
+        loop {
+            let next_const_pos = consts.peek().map(|decl| decl.ident.span.start);
+            let next_decl_pos = decls.peek().map(|decl| decl.ident.span.start);
+            match (next_const_pos, next_decl_pos) {
+                (Some(const_pos), Some(decl_pos)) if const_pos < decl_pos => {
+                    self.const_decl(consts.next().unwrap())
+                }
+                (Some(_), None) => self.const_decl(consts.next().unwrap()),
+                (_, Some(_)) => {
+                    decls.next();
+                    self.enter(NodeKind::Decl);
+                    self.bump_through(Category::Semi);
+                    self.exit();
+                }
+                (None, None) => return,
+            }
+        }
+    }
+
+    fn const_decl(&mut self, decl: &ast::ConstDecl) {
+        self.enter(NodeKind::ConstDecl);
+        self.expr(&decl.expr);
+        self.bump_through(Category::Semi);
+        self.exit();
+    }
+
+    fn block(&mut self, block: &ast::Block) {
+        self.advance_to(block.span.start);
+        self.enter(NodeKind::Block);
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+        self.advance_through(block.span.end);
+        self.exit();
+    }
+
+    fn stmt(&mut self, stmt: &ast::Stmt) {
+        use ast::StmtKind;
+        let kind = match stmt.kind {
+            StmtKind::While(..) => NodeKind::WhileStmt,
+            StmtKind::For(..) => NodeKind::ForStmt,
+            StmtKind::Repeat(..) => NodeKind::RepeatStmt,
+            StmtKind::Break => NodeKind::BreakStmt,
+            StmtKind::Continue => NodeKind::ContinueStmt,
+            StmtKind::If(..) => NodeKind::IfStmt,
+            StmtKind::Case(..) => NodeKind::CaseStmt,
+            StmtKind::Assign(..) => NodeKind::AssignStmt,
+            StmtKind::BlockStmt(ref block) => return self.block(block),
+            StmtKind::Call(..) => NodeKind::CallStmt,
+        };
+        self.advance_to(stmt.span.start);
+        self.enter(kind);
+        match stmt.kind {
+            StmtKind::While(ref cond_expr, ref block) => {
+                self.expr(cond_expr);
+                self.block(block);
+            }
+            StmtKind::For(_, ref start_expr, _, ref end_expr, ref block) => {
+                self.expr(start_expr);
+                self.expr(end_expr);
+                self.block(block);
+            }
+            StmtKind::Repeat(ref block, ref cond_expr) => {
+                self.block(block);
+                self.expr(cond_expr);
+            }
+            StmtKind::If(ref cond_expr, ref then_block, ref else_block) => {
+                self.expr(cond_expr);
+                self.block(then_block);
+                if let Some(ref else_block) = *else_block {
+                    self.else_block(else_block);
+                }
+            }
+            StmtKind::Case(ref expr, ref arms, ref else_block) => {
+                self.expr(expr);
+                self.bump_through(Category::Of);
+                for arm in arms {
+                    self.enter(NodeKind::CaseArm);
+                    self.block(&arm.block);
+                    self.exit();
+                }
+                if let Some(ref else_block) = *else_block {
+                    self.block(else_block);
+                }
+            }
+            StmtKind::Assign(_, ref expr) => self.expr(expr),
+            StmtKind::Call(_, ref args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            StmtKind::Break | StmtKind::Continue | StmtKind::BlockStmt(_) => {}
+        }
+        self.advance_through(stmt.span.end);
+        self.exit();
+    }
+
+    /// Adds the `else` block of an `if`, which for an `else if` is just the
+    /// chained `if`.
+    fn else_block(&mut self, else_block: &ast::Block) {
+        if let [ref stmt] = else_block.stmts[..] {
+            if stmt.span == else_block.span {
+                return self.stmt(stmt);
+            }
+        }
+        self.block(else_block);
+    }
+
+    fn expr(&mut self, expr: &ast::Expr) {
+        use ast::ExprKind;
+        self.advance_to(expr.span.start);
+        self.enter(match expr.kind {
+            ExprKind::BinaryOp(..) => NodeKind::BinaryExpr,
+            ExprKind::UnaryOp(..) => NodeKind::UnaryExpr,
+            ExprKind::Lit(_) => NodeKind::LitExpr,
+            ExprKind::Ident(_) => NodeKind::IdentExpr,
+            ExprKind::Paren(_) => NodeKind::ParenExpr,
+            ExprKind::Cast(..) => NodeKind::CastExpr,
+            ExprKind::Call(..) => NodeKind::CallExpr,
+            ExprKind::Formatted(..) => NodeKind::FormattedExpr,
+        });
+        match expr.kind {
+            ExprKind::BinaryOp(_, ref lhs, ref rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::UnaryOp(_, ref operand)
+            | ExprKind::Paren(ref operand)
+            | ExprKind::Cast(_, ref operand) => self.expr(operand),
+            ExprKind::Lit(_) | ExprKind::Ident(_) => {}
+            ExprKind::Call(_, ref args) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            ExprKind::Formatted(ref value, ref width, ref prec) => {
+                self.expr(value);
+                self.expr(width);
+                if let Some(ref prec) = *prec {
+                    self.expr(prec);
+                }
+            }
+        }
+        self.advance_through(expr.span.end);
+        self.exit();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse, tokenize, Element, Node, NodeKind, TriviaKind};
+    use errors;
+    use scanner::Category;
+    use source_map::SourceFile;
+    use std::rc::Rc;
+
+    fn parse_src(src: &str) -> (Node, Rc<SourceFile>) {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let file = Rc::new(SourceFile::new("test".into(), src.into()));
+        (parse(file.clone(), &handler).unwrap(), file)
+    }
+
+    fn child_kinds(node: &Node) -> Vec<NodeKind> {
+        node.children
+            .iter()
+            .filter_map(|child| match *child {
+                Element::Node(ref node) => Some(node.kind),
+                Element::Token(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reprint_is_lossless() {
+        let srcs = [
+            include_str!("../examples/fibonacci.bl"),
+            include_str!("../examples/circle.bl"),
+            include_str!("../examples/mathutils.bl"),
+            "  // leading\r\nprogram p ;let x:int;\n\n  x=1 ;// one\n\n// last\n",
+            "program p; let i: int;\nfor i = 1 to 3 { if i == 2 { break; } else if i > 2 { i = 3; } else { continue; } }",
+            "program p; let x: int; case 2 of 1, 3..5: { {writeln(1);} } 2: { writeln(\"two\":4); } else { writeln(-1); }\nrepeat { x = x + 1; } until x > 3;",
+        ];
+        for src in &srcs {
+            let (root, _) = parse_src(src);
+            assert_eq!(*src, root.to_string());
+        }
+    }
+
+    #[test]
+    fn test_trivia_attachment() {
+        let file = Rc::new(SourceFile::new(
+            "test".into(),
+            "x  // trailing\n  // leading\ny $ z".into(),
+        ));
+        let tokens = tokenize(&file);
+
+        let trivia = |trivia: &[super::Trivia]| {
+            trivia
+                .iter()
+                .map(|trivia| (trivia.kind, trivia.text.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![
+                (TriviaKind::Whitespace, "  ".to_owned()),
+                (TriviaKind::Comment, "// trailing".to_owned()),
+            ],
+            trivia(&tokens[0].trailing)
+        );
+        assert_eq!(
+            vec![
+                (TriviaKind::Whitespace, "\n  ".to_owned()),
+                (TriviaKind::Comment, "// leading".to_owned()),
+                (TriviaKind::Whitespace, "\n".to_owned()),
+            ],
+            trivia(&tokens[1].leading)
+        );
+        assert_eq!(
+            vec![
+                (TriviaKind::Whitespace, " ".to_owned()),
+                (TriviaKind::Skipped, "$".to_owned()),
+                (TriviaKind::Whitespace, " ".to_owned()),
+            ],
+            trivia(&tokens[1].trailing)
+        );
+        assert_eq!(Category::Eof, tokens[3].category);
+        assert_eq!("", tokens[3].text);
+    }
+
+    #[test]
+    fn test_tree_structure() {
+        let (root, _) = parse_src("program p; let x: int; x = 1 + 2; while x < 3 { x = x + 1; }");
+        assert_eq!(NodeKind::Program, root.kind);
+        assert_eq!(
+            vec![NodeKind::Decl, NodeKind::AssignStmt, NodeKind::WhileStmt],
+            child_kinds(&root)
+        );
+
+        let assign = match root.children[4] {
+            Element::Node(ref node) => node,
+            Element::Token(_) => panic!("has to be a node!"),
+        };
+        assert_eq!(vec![NodeKind::BinaryExpr], child_kinds(assign));
+        assert_eq!(
+            vec!["x", "=", "1", "+", "2", ";"],
+            assign
+                .tokens()
+                .iter()
+                .map(|token| token.text.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let (root, file) = parse_src("program p;\nx = 1; // one\n");
+        assert_eq!(
+            "Program 0..25\n  \
+               Program 0..7 \"program\"\n  \
+               Whitespace \" \"\n  \
+               Ident 8..9 \"p\"\n  \
+               Semi 9..10 \";\"\n  \
+               AssignStmt 11..17\n    \
+                 Whitespace \"\\n\"\n    \
+                 Ident 11..12 \"x\"\n    \
+                 Whitespace \" \"\n    \
+                 Eq 13..14 \"=\"\n    \
+                 Whitespace \" \"\n    \
+                 LitExpr 15..16\n      \
+                   NumConst { is_float: false } 15..16 \"1\"\n    \
+                 Semi 16..17 \";\"\n    \
+                 Whitespace \" \"\n    \
+                 Comment \"// one\"\n  \
+               Whitespace \"\\n\"\n  \
+               Eof 25..25 \"\"\n",
+            root.dump(&file)
+        );
+    }
+}
@@ -0,0 +1,833 @@
+use ast;
+use builtins::Natives;
+use cst;
+use errors::{self, Diag, Level, LevelConfig, RuntimeError};
+use fix;
+use formatter;
+use interp::{Interpreter, Limits};
+use ir;
+use json::Json;
+use loader::Loader;
+use parser::Parser;
+use scanner::{Category, Scanner, Word, WordStream};
+use source_map::{BytePos, Pos, SourceFile, SourceMap};
+use std::cell::RefCell;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use typeck::TypeChecker;
+
+/// How code is compiled.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Changes to the levels diagnostics are reported at.
+    pub levels: LevelConfig,
+    /// How many errors are reported before the rest are dropped, if there's
+    /// a limit.
+    pub max_errors: Option<usize>,
+    /// The directories units are looked for in, after the directory of the
+    /// file compiled.
+    pub search_path: Vec<PathBuf>,
+    /// The functions the host defines for the programs compiled.
+    pub natives: Natives,
+    /// Whether the program or unit of a file is kept even if errors are
+    /// found, as long as it could be loaded, e.g. so that an editor can
+    /// still look up the names in it.
+    pub keep_ill_typed: bool,
+}
+
+/// A file, or a string standing for one.
+#[derive(Clone, Copy, Debug)]
+pub enum Input<'a> {
+    File(&'a Path),
+    /// The code of a string, which diagnostics refer to by `name`.
+    Str {
+        name: &'a str,
+        src: &'a str,
+    },
+}
+
+/// What `dump` and `emit` write out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Emit {
+    /// The words of the file, one per line along with its position and the
+    /// byte offsets of its span.
+    Tokens,
+    /// The syntax tree.
+    Ast,
+    /// The concrete syntax tree, comments and whitespace included.
+    Cst,
+    /// The program or unit lowered into basic blocks, which only a checked
+    /// file can be.
+    Ir,
+}
+
+/// The program or unit of a file.
+#[derive(Debug)]
+pub enum Root {
+    Program(ast::Program),
+    Unit(ast::Unit),
+}
+
+/// A well-typed program or unit, along with the units it uses.
+pub struct Module {
+    pub root: Root,
+    /// The units used, each one placed after the units it uses.
+    pub units: Vec<ast::Unit>,
+    pub ident_table: ast::IdentTable,
+    /// The functions defined by the host, which the module was checked
+    /// against.
+    pub natives: Natives,
+}
+
+impl Root {
+    pub fn name(&self) -> &ast::Ident {
+        match *self {
+            Root::Program(ref program) => &program.name,
+            Root::Unit(ref unit) => &unit.name,
+        }
+    }
+
+    pub fn uses(&self) -> &[ast::Ident] {
+        match *self {
+            Root::Program(ref program) => &program.uses,
+            Root::Unit(ref unit) => &unit.uses,
+        }
+    }
+
+    pub fn consts(&self) -> &[ast::ConstDecl] {
+        match *self {
+            Root::Program(ref program) => &program.consts,
+            Root::Unit(ref unit) => &unit.consts,
+        }
+    }
+
+    pub fn decls(&self) -> &[ast::Decl] {
+        match *self {
+            Root::Program(ref program) => &program.decls,
+            Root::Unit(ref unit) => &unit.decls,
+        }
+    }
+
+    /// The statements of a program, none for a unit.
+    pub fn stmts(&self) -> &[ast::Stmt] {
+        match *self {
+            Root::Program(ref program) => &program.stmts,
+            Root::Unit(_) => &[],
+        }
+    }
+}
+
+impl Module {
+    /// The program of the module, unless it holds a unit.
+    pub fn program(&self) -> Option<&ast::Program> {
+        match self.root {
+            Root::Program(ref program) => Some(program),
+            Root::Unit(_) => None,
+        }
+    }
+}
+
+/// A diagnostic, along with the level it's reported at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub diag: Diag,
+    pub level: Level,
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic as text, as `blaise` writes it.
+    pub fn render(&self, source_map: &SourceMap) -> String {
+        self.diag.render(self.level, source_map)
+    }
+
+    /// Renders the diagnostic as JSON, as `blaise --error-format=json`
+    /// writes it.
+    pub fn to_json(&self, source_map: &SourceMap) -> Json {
+        self.diag.to_json(self.level, source_map)
+    }
+}
+
+/// The result of compiling a file.
+pub struct Compilation {
+    /// The source map holding the file and the units it uses, which the
+    /// spans of the module and diagnostics point into.
+    pub source_map: Rc<SourceMap>,
+    /// The file compiled.
+    pub file: Rc<SourceFile>,
+    /// The program or unit of the file, unless any error was found, or
+    /// unless it couldn't be loaded if `Options::keep_ill_typed` is set.
+    pub module: Option<Module>,
+    /// The diagnostics reported, in source order.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Compilation {
+    pub fn has_errors(&self) -> bool {
+        has_errors(&self.diagnostics)
+    }
+
+    /// Renders every diagnostic as text, one after another.
+    pub fn render_diagnostics(&self) -> String {
+        render_diagnostics(&self.diagnostics, &self.source_map)
+    }
+}
+
+/// The text written for a file by `dump`, `format` or `fix`.
+pub struct Output {
+    /// The source map holding the file, which the spans of the diagnostics
+    /// point into.
+    pub source_map: Rc<SourceMap>,
+    /// The file read.
+    pub file: Rc<SourceFile>,
+    /// The text written, unless an error kept it from being written.
+    pub text: Option<String>,
+    /// The diagnostics reported, in source order.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Output {
+    pub fn has_errors(&self) -> bool {
+        has_errors(&self.diagnostics)
+    }
+
+    /// Renders every diagnostic as text, one after another.
+    pub fn render_diagnostics(&self) -> String {
+        render_diagnostics(&self.diagnostics, &self.source_map)
+    }
+}
+
+fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.level == Level::Error)
+}
+
+fn render_diagnostics(diagnostics: &[Diagnostic], source_map: &SourceMap) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(source_map) + "\n")
+        .collect()
+}
+
+/// Compiles the code of a string, which diagnostics refer to by `name`.
+/// Units are looked for in the search path of the options.
+pub fn compile_str(name: &str, src: &str, options: &Options) -> Compilation {
+    let source_map = Rc::new(SourceMap::new());
+    let file = source_map.add_file(name.into(), src.to_owned());
+    compile(source_map, file, options.search_path.clone(), options)
+}
+
+/// Reads and compiles a file. Units are looked for next to it, then in the
+/// search path of the options.
+pub fn check_file(path: &Path, options: &Options) -> io::Result<Compilation> {
+    let source_map = Rc::new(SourceMap::new());
+    let file = source_map.load_file(path)?;
+    let dir = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+    let mut search_path = vec![dir];
+    search_path.extend(options.search_path.iter().cloned());
+    Ok(compile(source_map, file, search_path, options))
+}
+
+/// Runs the program of a module, which reads from `input` and writes to
+/// `output`, stopping at the first runtime error.
+///
+/// # Panics
+///
+/// Panics if the module holds a unit, which can't be run.
+pub fn run_program<R, W>(module: &Module, input: R, output: W) -> Result<(), RuntimeError>
+where
+    R: BufRead,
+    W: Write,
+{
+    run_program_with_limits(module, Limits::default(), input, output)
+}
+
+/// Runs the program of a module like `run_program`, stopping it once it
+/// goes over the limits given.
+///
+/// # Panics
+///
+/// Panics if the module holds a unit, which can't be run.
+pub fn run_program_with_limits<R, W>(
+    module: &Module,
+    limits: Limits,
+    input: R,
+    output: W,
+) -> Result<(), RuntimeError>
+where
+    R: BufRead,
+    W: Write,
+{
+    let program = module.program().expect("only programs can be run");
+    let mut interp = Interpreter::with_natives(&module.ident_table, &module.natives, input, output)
+        .with_limits(limits);
+    module
+        .units
+        .iter()
+        .try_for_each(|unit| interp.init_unit(unit))
+        .and_then(|_| interp.run_program(program))
+}
+
+/// Parses a file without checking it, then writes what `emit` asks for.
+///
+/// # Panics
+///
+/// Panics if `emit` is `Emit::Ir`, as only a checked file can be lowered.
+pub fn dump(input: Input, emit: Emit, options: &Options) -> io::Result<Output> {
+    write_output(input, options, |_, file, handler| {
+        write_emit(file, emit, None, handler)
+    })
+}
+
+/// Writes what `emit` asks for of a compiled file, unless it has no module.
+pub fn emit(compilation: &Compilation, emit: Emit) -> Option<String> {
+    let module = compilation.module.as_ref()?;
+    // The diagnostics were reported while compiling the file.
+    let handler = errors::Handler::with_ignoring_emitter();
+    write_emit(&compilation.file, emit, Some(module), &handler)
+}
+
+/// Formats a file in the canonical style.
+pub fn format(input: Input, options: &Options) -> io::Result<Output> {
+    write_output(input, options, |_, file, handler| {
+        formatter::format_source(file.clone(), handler)
+            .map_err(|diag| handler.report(diag))
+            .ok()
+    })
+}
+
+/// Applies the fixes suggested for a file, then parses the fixed code,
+/// reporting the errors left.
+pub fn fix(input: Input, options: &Options) -> io::Result<Output> {
+    write_output(input, options, |source_map, file, handler| {
+        let fixed = fix::fix_source(file);
+        parse(
+            source_map.add_file(file.name().clone(), fixed.clone()),
+            handler,
+        );
+        Some(fixed)
+    })
+}
+
+impl<'a> Input<'a> {
+    /// Reads the file into a new source map.
+    fn load(self) -> io::Result<(Rc<SourceMap>, Rc<SourceFile>)> {
+        let source_map = Rc::new(SourceMap::new());
+        let file = match self {
+            Input::File(path) => source_map.load_file(path)?,
+            Input::Str { name, src } => source_map.add_file(name.into(), src.to_owned()),
+        };
+        Ok((source_map, file))
+    }
+}
+
+/// Reads a file, then writes text for it, collecting the diagnostics
+/// reported.
+fn write_output<F>(input: Input, options: &Options, write: F) -> io::Result<Output>
+where
+    F: FnOnce(&Rc<SourceMap>, &Rc<SourceFile>, &errors::Handler) -> Option<String>,
+{
+    let (source_map, file) = input.load()?;
+    let (handler, diagnostics) = collecting_handler(options);
+    let text = write(&source_map, &file, &handler);
+    handler.flush();
+    Ok(Output {
+        source_map,
+        file,
+        text,
+        diagnostics: diagnostics.take(),
+    })
+}
+
+/// Writes what `emit` asks for of a file, whose syntax tree is parsed unless
+/// a module holds it. Returns `None` if the file couldn't be parsed.
+fn write_emit(
+    file: &Rc<SourceFile>,
+    emit: Emit,
+    module: Option<&Module>,
+    handler: &errors::Handler,
+) -> Option<String> {
+    match emit {
+        Emit::Tokens => Some(dump_tokens(file, handler)),
+        Emit::Ast => match module {
+            Some(module) => Some(dump_root(&module.root)),
+            None => parse(file.clone(), handler).map(|root| dump_root(&root)),
+        },
+        Emit::Cst => cst::parse(file.clone(), handler)
+            .map(|root| root.dump(file))
+            .map_err(|diag| handler.report(diag))
+            .ok(),
+        Emit::Ir => {
+            let module = module.expect("only a checked file can be lowered");
+            Some(ir::lower(module).dump(&module.ident_table))
+        }
+    }
+}
+
+/// Lists the words of a file, one per line along with its position and the
+/// byte offsets of its span.
+fn dump_tokens(file: &Rc<SourceFile>, handler: &errors::Handler) -> String {
+    let mut text = String::new();
+    for word in WordStream::new(Scanner::new(file.clone()), handler) {
+        let loc = file.lookup_source_location(word.lexeme.start).unwrap();
+        let offset = |pos: BytePos| (pos - file.start_pos()).to_usize();
+        let _ = writeln!(
+            text,
+            "{}:{}: {:?} {}..{} {:?}",
+            loc.line,
+            loc.col_char + 1,
+            word.category,
+            offset(word.lexeme.start),
+            offset(word.lexeme.end),
+            file.span_to_snippet(word.lexeme)
+        );
+    }
+    text
+}
+
+/// Writes the syntax tree of a program or unit.
+fn dump_root(root: &Root) -> String {
+    match *root {
+        Root::Program(ref program) => format!("{:#?}\n", program),
+        Root::Unit(ref unit) => format!("{:#?}\n", unit),
+    }
+}
+
+/// Parses a program or unit without loading the units it uses, reporting
+/// the error which stopped it.
+fn parse(file: Rc<SourceFile>, handler: &errors::Handler) -> Option<Root> {
+    let is_unit = is_unit(&file);
+    let mut parser = Parser::new(WordStream::new(Scanner::new(file), handler));
+    let root = if is_unit {
+        parser.parse_unit().map(Root::Unit)
+    } else {
+        parser.parse_program().map(Root::Program)
+    };
+    root.map_err(|diag| handler.report(diag)).ok()
+}
+
+/// Creates a handler collecting the diagnostics reported, at the levels the
+/// options give them.
+fn collecting_handler(options: &Options) -> (errors::Handler, Rc<RefCell<Vec<Diagnostic>>>) {
+    let diagnostics = Rc::new(RefCell::new(vec![]));
+    let handler = {
+        let diagnostics = diagnostics.clone();
+        let handler = errors::Handler::with_emitter(move |diag, level| {
+            diagnostics.borrow_mut().push(Diagnostic { diag, level })
+        })
+        .with_levels(options.levels.clone());
+        match options.max_errors {
+            Some(max_errors) => handler.with_max_errors(max_errors),
+            None => handler,
+        }
+    };
+    (handler, diagnostics)
+}
+
+/// Loads the units used by a file and type checks everything.
+fn compile(
+    source_map: Rc<SourceMap>,
+    file: Rc<SourceFile>,
+    search_path: Vec<PathBuf>,
+    options: &Options,
+) -> Compilation {
+    let (handler, diagnostics) = collecting_handler(options);
+
+    let loader = Loader::new(&handler, &source_map, search_path);
+    let loaded = if is_unit(&file) {
+        loader.load_unit_file(file.clone()).map(|loaded| Module {
+            root: Root::Unit(loaded.unit),
+            units: loaded.units,
+            ident_table: loaded.ident_table,
+            natives: options.natives.clone(),
+        })
+    } else {
+        loader.load_program(file.clone()).map(|loaded| Module {
+            root: Root::Program(loaded.program),
+            units: loaded.units,
+            ident_table: loaded.ident_table,
+            natives: options.natives.clone(),
+        })
+    };
+    let module = match loaded {
+        Ok(module) => {
+            let mut type_checker =
+                TypeChecker::with_natives(&handler, &module.ident_table, &options.natives);
+            for unit in &module.units {
+                type_checker.check_unit(unit);
+            }
+            match module.root {
+                Root::Program(ref program) => {
+                    type_checker.check_program(program);
+                }
+                Root::Unit(ref unit) => type_checker.check_unit(unit),
+            }
+            Some(module)
+        }
+        Err(diag) => {
+            handler.report(diag);
+            None
+        }
+    };
+
+    handler.flush();
+    let module = module.filter(|_| options.keep_ill_typed || !handler.has_errors());
+    Compilation {
+        source_map,
+        file,
+        module,
+        diagnostics: diagnostics.take(),
+    }
+}
+
+/// Whether a file holds a unit rather than a program.
+fn is_unit(file: &Rc<SourceFile>) -> bool {
+    matches!(
+        Scanner::new(file.clone()).next_word(),
+        Ok(Word {
+            category: Category::Unit,
+            ..
+        })
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_file, compile_str, dump, emit, fix, format, run_program, run_program_with_limits,
+        Emit, Input, Options,
+    };
+    use ast::Ty;
+    use builtins::Natives;
+    use errors::{Diag, Level, Limit, RuntimeError, RuntimeErrorKind};
+    use interp::{Limits, Value};
+    use std::cell::RefCell;
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Natives logging strings into a vector and squaring floats, which
+    /// fail on negative numbers.
+    fn natives() -> (Natives, Rc<RefCell<Vec<String>>>) {
+        let log = Rc::new(RefCell::new(vec![]));
+        let mut natives = Natives::new();
+        {
+            let log = log.clone();
+            natives.register("log", &[Ty::StrTy], None, move |args| {
+                log.borrow_mut().push(args[0].to_string());
+                Ok(None)
+            });
+        }
+        natives.register(
+            "square",
+            &[Ty::FloatTy],
+            Some(Ty::FloatTy),
+            |args| match args[0] {
+                Value::Float(x) if x < 0.0 => Err(format!("{} is negative", x)),
+                Value::Float(x) => Ok(Some(Value::Float(x * x))),
+                _ => unreachable!(),
+            },
+        );
+        natives.register("broken", &[], Some(Ty::IntTy), |_| {
+            Ok(Some(Value::Str("x".to_owned())))
+        });
+        (natives, log)
+    }
+
+    #[test]
+    fn test_compile_str() {
+        let compilation = compile_str(
+            "test",
+            "program p; let x: int; x = true;",
+            &Options::default(),
+        );
+        assert!(compilation.has_errors());
+        assert!(compilation.module.is_none());
+        assert_eq!(1, compilation.diagnostics.len());
+        assert_eq!(Level::Error, compilation.diagnostics[0].level);
+        assert!(matches!(
+            compilation.diagnostics[0].diag,
+            Diag::MismatchedTypes { .. }
+        ));
+        assert_eq!(
+            "<test>:1:28: error[B0017]: mismatched types: expected `int`, but found `bool`\n",
+            compilation.render_diagnostics()
+        );
+
+        let options = Options {
+            keep_ill_typed: true,
+            ..Options::default()
+        };
+        let compilation = compile_str("test", "program p; let x: int; x = true;", &options);
+        assert!(compilation.has_errors());
+        let module = compilation.module.unwrap();
+        assert_eq!("p", module.ident_table.get(module.root.name().name));
+        let compilation = compile_str("test", "program p; x", &options);
+        assert!(compilation.module.is_none());
+    }
+
+    #[test]
+    fn test_run_program() {
+        let compilation = compile_str(
+            "test",
+            "program p; let s: str; readln(s); writeln(s, \"!\");",
+            &Options::default(),
+        );
+        let module = compilation.module.unwrap();
+        let mut output = vec![];
+        assert_eq!(Ok(()), run_program(&module, &b"hi\n"[..], &mut output));
+        assert_eq!("hi!\n", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn test_check_file_with_units() {
+        let dir = env::temp_dir().join(format!("blaise-driver-units-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("u.bl"), "unit u; const k: int = 7;").unwrap();
+        fs::write(dir.join("p.bl"), "program p; uses u; writeln(k * 6);").unwrap();
+
+        let compilation = check_file(&dir.join("p.bl"), &Options::default()).unwrap();
+        assert!(!compilation.has_errors());
+        let module = compilation.module.unwrap();
+        assert_eq!(1, module.units.len());
+        let mut output = vec![];
+        assert_eq!(Ok(()), run_program(&module, &b""[..], &mut output));
+        assert_eq!("42\n", String::from_utf8(output).unwrap());
+
+        assert!(check_file(&dir.join("q.bl"), &Options::default()).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_dump_and_emit() {
+        let input = Input::Str {
+            name: "test",
+            src: "program p; x = 1;",
+        };
+        let output = dump(input, Emit::Ast, &Options::default()).unwrap();
+        assert!(!output.has_errors());
+        assert!(output.text.unwrap().starts_with("Program {\n"));
+        let output = dump(input, Emit::Tokens, &Options::default()).unwrap();
+        assert!(output
+            .text
+            .unwrap()
+            .starts_with("1:1: Program 0..7 \"program\"\n"));
+
+        // Unlike `dump`, `emit` needs the file to be checked.
+        let compilation = compile_str("test", "program p; x = 1;", &Options::default());
+        assert_eq!(None, emit(&compilation, Emit::Ast));
+        let compilation = compile_str("test", "program p; writeln(1);", &Options::default());
+        assert_eq!(
+            Some("program p\n\nbb0:\n    call writeln(1)\n    return\n".to_owned()),
+            emit(&compilation, Emit::Ir)
+        );
+
+        let input = Input::Str {
+            name: "test",
+            src: "program p; x = ;",
+        };
+        let output = dump(input, Emit::Cst, &Options::default()).unwrap();
+        assert_eq!(None, output.text);
+        assert_eq!(
+            "<test>:1:16: error[B0009]: expected expression, but got `;`\n",
+            output.render_diagnostics()
+        );
+    }
+
+    #[test]
+    fn test_format_and_fix() {
+        let input = Input::Str {
+            name: "test",
+            src: "program p;let x:int;x=1;",
+        };
+        let output = format(input, &Options::default()).unwrap();
+        assert_eq!(
+            Some("program p;\n\nlet x: int;\n\nx = 1;\n".to_owned()),
+            output.text
+        );
+
+        let input = Input::Str {
+            name: "test",
+            src: "program p\nx = 1\ny = ;\n",
+        };
+        let output = fix(input, &Options::default()).unwrap();
+        assert_eq!(Some("program p;\nx = 1;\ny = ;\n".to_owned()), output.text);
+        assert!(output.has_errors());
+    }
+
+    #[test]
+    fn test_natives() {
+        let (natives, log) = natives();
+        let options = Options {
+            natives,
+            ..Options::default()
+        };
+        let src = "program p; let f: float; f = square(3); log(\"f\"); writeln(f);";
+        let compilation = compile_str("test", src, &options);
+        assert_eq!("", compilation.render_diagnostics());
+        let module = compilation.module.unwrap();
+        let mut output = vec![];
+        assert_eq!(Ok(()), run_program(&module, &b""[..], &mut output));
+        assert_eq!("9.0\n", String::from_utf8(output).unwrap());
+        assert_eq!(vec!["f".to_owned()], *log.borrow());
+    }
+
+    #[test]
+    fn test_natives_are_type_checked() {
+        let (natives, _) = natives();
+        let options = Options {
+            natives,
+            ..Options::default()
+        };
+        let src = "program p; let f: float; log(1); f = square(1, 2); f = log(\"a\");";
+        let diags = compile_str("test", src, &options)
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.diag.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "mismatched types: expected `str`, but found `int`",
+                "function takes 1 argument(s), but 2 were supplied",
+                "procedure call doesn't have a value",
+            ],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_natives_failing() {
+        let (natives, _) = natives();
+        let options = Options {
+            natives,
+            ..Options::default()
+        };
+        let run = |src| {
+            let module = compile_str("test", src, &options).module.unwrap();
+            run_program(&module, &b""[..], vec![]).map_err(|err| err.kind)
+        };
+        assert_eq!(
+            Err(RuntimeErrorKind::NativeFunctionFailed {
+                name: "square".to_owned(),
+                message: "-2 is negative".to_owned(),
+            }),
+            run("program p; writeln(square(-2));")
+        );
+        assert_eq!(
+            Err(RuntimeErrorKind::NativeFunctionFailed {
+                name: "broken".to_owned(),
+                message: "returned a value of type `str` rather than `int`".to_owned(),
+            }),
+            run("program p; writeln(broken());")
+        );
+    }
+
+    #[test]
+    fn test_run_program_with_limits() {
+        let run = |src, limits| {
+            let compilation = compile_str("test", src, &Options::default());
+            let module = compilation.module.as_ref().unwrap();
+            run_program_with_limits(module, limits, &b""[..], vec![])
+                .map_err(|err| Diag::from(err).render(Level::Error, &compilation.source_map))
+        };
+        let forever = "program p;\nlet i: int;\nwhile true {\n  i = i + 1;\n}\n";
+        assert_eq!(
+            Err("<test>:4:3: error[B0037]: execution stopped after 1000 steps".to_owned()),
+            run(
+                forever,
+                Limits {
+                    max_steps: Some(1000),
+                    ..Limits::default()
+                }
+            )
+        );
+        // The time runs out either at the loop or at its body.
+        let stopped = run(
+            forever,
+            Limits {
+                timeout: Some(Duration::from_millis(10)),
+                ..Limits::default()
+            },
+        )
+        .unwrap_err();
+        assert!(
+            stopped == "<test>:3:1: error[B0037]: execution stopped after 10ms"
+                || stopped == "<test>:4:3: error[B0037]: execution stopped after 10ms",
+            "{}",
+            stopped
+        );
+        // Each time around a loop is a step, besides its statements.
+        let src = "program p; let i: int; while i < 10 { i = i + 1; }";
+        let limits = |max_steps| Limits {
+            max_steps: Some(max_steps),
+            ..Limits::default()
+        };
+        assert_eq!(Ok(()), run(src, limits(21)));
+        assert!(run(src, limits(20)).is_err());
+        assert_eq!(
+            Err("<test>:3:1: error[B0037]: execution stopped after 1000 steps".to_owned()),
+            run("program p;\nlet b: bool;\nwhile true { }\n", limits(1000))
+        );
+    }
+
+    #[test]
+    fn test_timeout_within_expression() {
+        let mut natives = Natives::new();
+        natives.register("nap", &[], Some(Ty::IntTy), |_| {
+            thread::sleep(Duration::from_millis(20));
+            Ok(Some(Value::Int(1)))
+        });
+        let options = Options {
+            natives,
+            ..Options::default()
+        };
+        // The statement never finishes, so the deadline is checked before each operand.
+        let src = "program p;\nwriteln(nap() + nap() + nap());\n";
+        let compilation = compile_str("test", src, &options);
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        };
+        match run_program_with_limits(
+            compilation.module.as_ref().unwrap(),
+            limits,
+            &b""[..],
+            vec![],
+        ) {
+            Err(RuntimeError {
+                kind: RuntimeErrorKind::LimitExceeded(Limit::Time(_)),
+                ..
+            }) => {}
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_limit_exceeded_at_statement() {
+        let src = "program p;\nlet s: str;\ns = \"ab\";\nwhile true {\n  s = s + s;\n}\n";
+        let module = compile_str("test", src, &Options::default())
+            .module
+            .unwrap();
+        let limits = Limits {
+            max_memory: Some(1000),
+            ..Limits::default()
+        };
+        match run_program_with_limits(&module, limits, &b""[..], vec![]) {
+            Err(RuntimeError {
+                kind: RuntimeErrorKind::LimitExceeded(limit),
+                span,
+                ..
+            }) => {
+                assert_eq!(Limit::Memory(1000), limit);
+                assert_eq!("s = s + s;", &src[span.start.0..span.end.0]);
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
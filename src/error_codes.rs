@@ -0,0 +1,754 @@
+use errors::Level;
+
+/// The stable identifier of a kind of diagnostic, along with a longer
+/// explanation of it, as shown by `blaise --explain`.
+#[derive(Debug, PartialEq)]
+pub struct ErrorCode {
+    /// The code, e.g. `B0017`.
+    pub code: &'static str,
+    /// A name for the code, e.g. `mismatched-types`, which may be given in
+    /// its place on the command line.
+    pub name: &'static str,
+    /// The level diagnostics with this code are reported at, unless the
+    /// command line says otherwise.
+    pub level: Level,
+    pub explanation: &'static str,
+}
+
+/// Finds a code by itself or by its name.
+pub fn lookup(code_or_name: &str) -> Option<&'static ErrorCode> {
+    ERROR_CODES
+        .iter()
+        .find(|error_code| error_code.code == code_or_name || error_code.name == code_or_name)
+}
+
+/// Every code, in order. Codes are never reused, even if the diagnostic
+/// they identify goes away.
+pub const ERROR_CODES: &[ErrorCode] = &[
+    ErrorCode {
+        code: "B0001",
+        name: "too-many-errors",
+        level: Level::Error,
+        explanation: "\
+Too many errors were found, so the compiler stopped looking for more.
+
+Fix the errors reported so far and compile again to see the rest.
+",
+    },
+    ErrorCode {
+        code: "B0002",
+        name: "invalid-digit",
+        level: Level::Error,
+        explanation: "\
+A numeric literal is followed by letters, which aren't digits.
+
+Erroneous code example:
+
+    x = 12abc;
+
+Separate the number from the identifier, or remove the letters:
+
+    x = 12;
+",
+    },
+    ErrorCode {
+        code: "B0003",
+        name: "missing-exponent-digits",
+        level: Level::Error,
+        explanation: "\
+A floating point literal has an exponent without any digits.
+
+Erroneous code example:
+
+    x = 1.5e;
+
+Write the digits of the exponent after the `e`, optionally signed:
+
+    x = 1.5e3;
+    y = 1.5e-3;
+",
+    },
+    ErrorCode {
+        code: "B0004",
+        name: "missing-terminating-string-mark",
+        level: Level::Error,
+        explanation: "\
+A string literal isn't closed by a `\"` before the end of its line.
+
+Erroneous code example:
+
+    writeln(\"hello);
+
+Close the string on the same line:
+
+    writeln(\"hello\");
+",
+    },
+    ErrorCode {
+        code: "B0005",
+        name: "unknown-character",
+        level: Level::Error,
+        explanation: "\
+A character which isn't part of any word of the language was found. A run
+of such characters is reported once.
+
+Erroneous code example:
+
+    x = 1 $ 2;
+
+Remove the character, or put it inside a string literal.
+",
+    },
+    ErrorCode {
+        code: "B0006",
+        name: "unexpected-end-of-file",
+        level: Level::Error,
+        explanation: "\
+The file ended in the middle of a declaration or statement.
+
+Complete the code at the end of the file.
+",
+    },
+    ErrorCode {
+        code: "B0007",
+        name: "expected-word",
+        level: Level::Error,
+        explanation: "\
+A word other than the one the grammar requires was found.
+
+Erroneous code example:
+
+    x = 1
+    y = 2;
+
+Here, the assignment to `x` misses its `;`:
+
+    x = 1;
+    y = 2;
+",
+    },
+    ErrorCode {
+        code: "B0008",
+        name: "expected-one-of",
+        level: Level::Error,
+        explanation: "\
+A word other than any of those the grammar allows was found.
+
+Erroneous code example:
+
+    for i = 1 until 10 { writeln(i); }
+
+A `for` loop counts either `to` or `downto` its end:
+
+    for i = 1 to 10 { writeln(i); }
+",
+    },
+    ErrorCode {
+        code: "B0009",
+        name: "expected-expr",
+        level: Level::Error,
+        explanation: "\
+An expression was expected, but some other word was found.
+
+Erroneous code example:
+
+    x = ;
+
+Give the value to be assigned:
+
+    x = 1;
+",
+    },
+    ErrorCode {
+        code: "B0010",
+        name: "loop-control-outside-loop",
+        level: Level::Error,
+        explanation: "\
+A `break` or `continue` statement isn't inside of any loop.
+
+Erroneous code example:
+
+    if x > 10 { break; }
+
+Both statements only make sense in the body of a `while`, `for` or
+`repeat` loop:
+
+    while true {
+        if x > 10 { break; }
+        x = x + 1;
+    }
+",
+    },
+    ErrorCode {
+        code: "B0011",
+        name: "duplicate-case-pattern",
+        level: Level::Error,
+        explanation: "\
+A `case` pattern matches a value already matched by a previous pattern, so
+its arm could never run for that value.
+
+Erroneous code example:
+
+    case x of
+        1..5: { writeln(\"small\"); }
+        3: { writeln(\"three\"); }
+
+Remove the duplicated pattern, or narrow the previous one:
+
+    case x of
+        1, 2, 4, 5: { writeln(\"small\"); }
+        3: { writeln(\"three\"); }
+",
+    },
+    ErrorCode {
+        code: "B0012",
+        name: "non-exhaustive-case",
+        level: Level::Warning,
+        explanation: "\
+A `case` statement over a `bool` value doesn't cover both `true` and
+`false`, and has no `else` block.
+
+Erroneous code example:
+
+    case x > 0 of
+        true: { writeln(\"positive\"); }
+
+Nothing runs when the value isn't covered, which is often a mistake. Add
+an arm for the missing value, or an `else` block:
+
+    case x > 0 of
+        true: { writeln(\"positive\"); }
+        false: { writeln(\"not positive\"); }
+",
+    },
+    ErrorCode {
+        code: "B0013",
+        name: "unit-not-found",
+        level: Level::Error,
+        explanation: "\
+A unit named by a `uses` clause wasn't found.
+
+Erroneous code example:
+
+    program p;
+    uses mathutils;
+
+A unit named `mathutils` is looked for in a `mathutils.bl` file next to
+the file using it, then in each directory listed by the `BLAISE_PATH`
+environment variable. Create the file, or add its directory to
+`BLAISE_PATH`.
+",
+    },
+    ErrorCode {
+        code: "B0014",
+        name: "cyclic-unit-use",
+        level: Level::Error,
+        explanation: "\
+A unit uses itself, either directly or through other units.
+
+Erroneous code example, where `a.bl` holds:
+
+    unit a;
+    uses b;
+
+and `b.bl` holds:
+
+    unit b;
+    uses a;
+
+Units are initialized before the code using them, so they can't depend
+on each other. Move what both units need into a third one.
+",
+    },
+    ErrorCode {
+        code: "B0015",
+        name: "unit-name-mismatch",
+        level: Level::Error,
+        explanation: "\
+The unit found in a file has a different name than the one it was used
+by.
+
+Erroneous code example, where `mathutils.bl` holds:
+
+    unit math;
+
+Name the unit after its file:
+
+    unit mathutils;
+",
+    },
+    ErrorCode {
+        code: "B0016",
+        name: "undeclared-variable",
+        level: Level::Error,
+        explanation: "\
+A variable is used without being declared.
+
+Erroneous code example:
+
+    program p;
+    x = 1;
+
+Declare every variable with `let` before the statements of the program:
+
+    program p;
+    let x: int;
+    x = 1;
+",
+    },
+    ErrorCode {
+        code: "B0017",
+        name: "mismatched-types",
+        level: Level::Error,
+        explanation: "\
+A value has a different type than the one its place requires.
+
+Erroneous code example:
+
+    let s: str;
+    s = 42;
+
+Give a value of the expected type, or convert it explicitly:
+
+    s = str(42);
+",
+    },
+    ErrorCode {
+        code: "B0018",
+        name: "lossy-implicit-conversion",
+        level: Level::Error,
+        explanation: "\
+A value would be implicitly converted to a type which can't represent it
+exactly, such as a `float` assigned to an `int`.
+
+Erroneous code example:
+
+    let n: int;
+    n = 2.5;
+
+Convert the value explicitly, to show the loss is intended:
+
+    n = int(2.5);
+",
+    },
+    ErrorCode {
+        code: "B0019",
+        name: "invalid-binary-operands",
+        level: Level::Error,
+        explanation: "\
+A binary operator was applied to operands of types it doesn't work on.
+
+Erroneous code example:
+
+    x = true + 1;
+
+Arithmetic operators take numbers, bitwise ones take integers, logical
+ones take booleans, and only `+` and the comparisons take strings.
+",
+    },
+    ErrorCode {
+        code: "B0020",
+        name: "invalid-unary-operand",
+        level: Level::Error,
+        explanation: "\
+A unary operator was applied to an operand of a type it doesn't work on.
+
+Erroneous code example:
+
+    x = -\"text\";
+
+`-` takes numbers, and `!` takes booleans.
+",
+    },
+    ErrorCode {
+        code: "B0021",
+        name: "invalid-cast",
+        level: Level::Error,
+        explanation: "\
+An explicit conversion was asked between types which can't be converted.
+
+Erroneous code example:
+
+    b = bool(\"yes\");
+
+Numbers and strings convert to each other, and anything converts to a
+string, but only a `bool` converts to `bool`. Compare the value instead:
+
+    b = s == \"yes\";
+",
+    },
+    ErrorCode {
+        code: "B0022",
+        name: "division-by-zero",
+        level: Level::Error,
+        explanation: "\
+A division, integer division or modulo by zero happened while running the
+program.
+
+Erroneous code example:
+
+    n = 0;
+    writeln(10 div n);
+
+Check the divisor before dividing:
+
+    if n != 0 { writeln(10 div n); }
+",
+    },
+    ErrorCode {
+        code: "B0023",
+        name: "invalid-conversion",
+        level: Level::Error,
+        explanation: "\
+A string which doesn't hold a number was converted to a number while
+running the program.
+
+Erroneous code example:
+
+    n = int(\"forty-two\");
+
+Only strings holding a number of the type converted to can be converted.
+",
+    },
+    ErrorCode {
+        code: "B0024",
+        name: "assign-to-constant",
+        level: Level::Error,
+        explanation: "\
+A constant was assigned to, or read into.
+
+Erroneous code example:
+
+    const max: int = 10;
+    max = 20;
+
+Constants keep the value they're declared with. Declare a variable with
+`let` for a value which changes.
+",
+    },
+    ErrorCode {
+        code: "B0025",
+        name: "non-constant-expr",
+        level: Level::Error,
+        explanation: "\
+The value of a constant uses a variable, which has no value before the
+program runs.
+
+Erroneous code example:
+
+    let x: int;
+    const y: int = x + 1;
+
+The value of a constant may only use literals and other constants.
+",
+    },
+    ErrorCode {
+        code: "B0026",
+        name: "undefined-function",
+        level: Level::Error,
+        explanation: "\
+A function which doesn't exist was called.
+
+Erroneous code example:
+
+    x = lenght(s);
+
+Check the name of the function, e.g. `len`.
+",
+    },
+    ErrorCode {
+        code: "B0027",
+        name: "wrong-arg-count",
+        level: Level::Error,
+        explanation: "\
+A function was called with a different number of arguments than it takes.
+
+Erroneous code example:
+
+    x = len(s, t);
+
+Give the function exactly the arguments it takes:
+
+    x = len(s);
+",
+    },
+    ErrorCode {
+        code: "B0028",
+        name: "procedure-in-expr",
+        level: Level::Error,
+        explanation: "\
+A procedure, which returns no value, was called where a value is
+expected.
+
+Erroneous code example:
+
+    x = writeln(1);
+
+Call procedures as statements of their own:
+
+    writeln(1);
+",
+    },
+    ErrorCode {
+        code: "B0029",
+        name: "not-assignable",
+        level: Level::Error,
+        explanation: "\
+An argument of `read` or `readln` isn't a variable, so there's nowhere to
+store what's read.
+
+Erroneous code example:
+
+    read(x + 1);
+
+Read into a variable:
+
+    read(x);
+",
+    },
+    ErrorCode {
+        code: "B0030",
+        name: "misplaced-format-spec",
+        level: Level::Error,
+        explanation: "\
+A field width, as in `x:8`, was given outside of the arguments of `write`
+or `writeln`.
+
+Erroneous code example:
+
+    y = x:8;
+
+Field widths only format output:
+
+    writeln(x:8);
+",
+    },
+    ErrorCode {
+        code: "B0031",
+        name: "precision-on-non-float",
+        level: Level::Error,
+        explanation: "\
+A precision, as in `x:8:2`, was given for a value which isn't a `float`.
+
+Erroneous code example:
+
+    let n: int;
+    writeln(n:8:2);
+
+Only give a field width, or convert the value to `float`:
+
+    writeln(n:8);
+    writeln(float(n):8:2);
+",
+    },
+    ErrorCode {
+        code: "B0032",
+        name: "invalid-input",
+        level: Level::Error,
+        explanation: "\
+The input read by `read` or `readln` doesn't hold a value of the type of
+the variable read into.
+
+Erroneous code example, given `abc` as input:
+
+    let n: int;
+    read(n);
+
+Give the program input of the expected type, or read it into a `str`.
+",
+    },
+    ErrorCode {
+        code: "B0033",
+        name: "unexpected-end-of-input",
+        level: Level::Error,
+        explanation: "\
+`read` or `readln` found no more input to read from.
+
+Give the program as many values as it reads.
+",
+    },
+    ErrorCode {
+        code: "B0034",
+        name: "io-error",
+        level: Level::Error,
+        explanation: "\
+Reading a file failed, e.g. because it doesn't exist or isn't readable.
+
+Failing to read the input of a running program or to write its output is
+B0042 instead.
+",
+    },
+    ErrorCode {
+        code: "B0035",
+        name: "index-out-of-bounds",
+        level: Level::Error,
+        explanation: "\
+A position outside of a string was given to `char_at` or `substr` while
+running the program.
+
+Erroneous code example:
+
+    s = \"abc\";
+    writeln(char_at(s, 3));
+
+Positions count from 0 and have to be less than the length of the string:
+
+    if i < len(s) { writeln(char_at(s, i)); }
+",
+    },
+    ErrorCode {
+        code: "B0036",
+        name: "native-function-failed",
+        level: Level::Error,
+        explanation: "\
+A function defined by the program embedding blaise failed while running the
+program, or returned a value of another type than its signature declares.
+
+The message of the diagnostic comes from the embedding program, whose
+documentation tells when the function fails.
+",
+    },
+    ErrorCode {
+        code: "B0037",
+        name: "limit-exceeded",
+        level: Level::Error,
+        explanation: "\
+A program was stopped for going over a limit on the resources it may use, which
+the program running it sets, e.g. with the `--max-steps` and `--timeout`
+options of `blaise run`. The diagnostic points at the statement being executed
+when it was stopped.
+
+Steps count the statements executed and the times around each loop, so a loop
+which never ends is stopped once it has run the most steps allowed, even if its
+body is empty. Memory counts the bytes of the strings held by variables, along
+with those of any string being built, e.g. by concatenation or a function.
+",
+    },
+    ErrorCode {
+        code: "B0038",
+        name: "integer-overflow",
+        level: Level::Error,
+        explanation: "\
+An integer operation had a result too large or too small for an `int`, which
+holds 64 bits, e.g.:
+
+    let x: int;
+    x = 9223372036854775807 + 1;
+
+Shifting by a negative amount or by 64 bits or more, and converting a `float`
+which is out of range or not a number to an `int`, are overflows too.
+
+Use a `float` for values which may not fit, or check the operands before
+computing with them.
+",
+    },
+    ErrorCode {
+        code: "B0039",
+        name: "expected-stmt",
+        level: Level::Error,
+        explanation: "\
+A statement was expected, but a word which can't start one was found.
+
+Erroneous code example:
+
+    if x > 0 { writeln(x); } else { else }
+
+Statements start with a variable, a function or a keyword like `if` or
+`while`.
+",
+    },
+    ErrorCode {
+        code: "B0040",
+        name: "int-literal-too-large",
+        level: Level::Error,
+        explanation: "\
+An integer literal is larger than the largest `int`, 9223372036854775807.
+
+Erroneous code example:
+
+    x = 99999999999999999999;
+
+Use a `float` literal for numbers which don't fit in an `int`:
+
+    f = 99999999999999999999.0;
+",
+    },
+    ErrorCode {
+        code: "B0041",
+        name: "reversed-case-range",
+        level: Level::Error,
+        explanation: "\
+A range pattern of a `case` statement starts after it ends, so it matches
+no value.
+
+Erroneous code example:
+
+    case x of
+        5..3: { writeln(\"between 3 and 5\"); }
+
+Write the smaller bound first:
+
+    case x of
+        3..5: { writeln(\"between 3 and 5\"); }
+",
+    },
+    ErrorCode {
+        code: "B0042",
+        name: "program-io-error",
+        level: Level::Error,
+        explanation: "\
+A running program failed to read its input or to write its output, e.g.
+because the output was closed.
+
+Failing to read a file while compiling is B0034 instead.
+",
+    },
+    ErrorCode {
+        code: "B0043",
+        name: "unused-variable",
+        level: Level::Warning,
+        explanation: "\
+A variable of a program is declared, but never assigned, read or passed to
+`read`.
+
+Erroneous code example:
+
+    program p;
+    let x: int;
+    let y: int;
+    y = 1;
+    writeln(y);
+
+Remove the declaration of the unused variable:
+
+    program p;
+    let y: int;
+    y = 1;
+    writeln(y);
+
+Variables of units aren't reported, as the programs using them may use them.
+",
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::{lookup, ERROR_CODES};
+
+    #[test]
+    fn test_codes_are_sequential() {
+        for (i, error_code) in ERROR_CODES.iter().enumerate() {
+            assert_eq!(format!("B{:04}", i + 1), error_code.code);
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(Some("B0017"), lookup("B0017").map(|code| code.code));
+        assert_eq!(
+            Some("B0012"),
+            lookup("non-exhaustive-case").map(|code| code.code)
+        );
+        assert_eq!(None, lookup("B9999"));
+    }
+}
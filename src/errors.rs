@@ -1,11 +1,47 @@
+use ast::{BinOp, Ty, UnOp};
+use error_codes::{self, ErrorCode};
+use json::Json;
 use scanner::{Category, Word};
-use source_map::{BytePos, Span, DUMMY_BPOS};
-use std::fmt;
+use source_map::{BytePos, Pos, SourceFile, SourceMap, Span, DUMMY_BPOS};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+/// How severe a diagnostic is. Only errors prevent a program from being
+/// compiled, while notes explain other diagnostics and help suggests how
+/// to fix them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+/// How likely a suggestion is to fix the code as it was meant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Applicability {
+    /// The suggestion is surely right, so tools may apply it on their own.
+    MachineApplicable,
+    /// The suggestion may not be what was meant, so it's only shown.
+    MaybeIncorrect,
+}
+
+/// A change which may fix the code a diagnostic is about, replacing the
+/// text of a span. An insertion replaces an empty span.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
 
 /// A `Diag` value gathers enough information about some error in the
 /// parsing process. It is used by the diagnostics system to report good
 /// quality error messages.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Diag {
     TooManyErrors,
     InvalidDigit {
@@ -20,63 +56,737 @@ pub enum Diag {
         str_start_pos: BytePos,
         eol_pos: BytePos,
     },
-    /// Unknown character in the source code.
+    /// A run of unknown characters in the source code.
     UnknownCharacter {
-        pos: BytePos,
+        span: Span,
+        count: usize,
     },
     UnexpectedEndOfFile,
     ExpectedWord {
         expected: Category,
         got: Word,
+        suggestions: Vec<Suggestion>,
     },
     ExpectedOneOf {
         expected: Vec<Category>,
         got: Word,
+        suggestions: Vec<Suggestion>,
+    },
+    ExpectedExpr {
+        got: Word,
+    },
+    /// A word which can't start a statement where one is expected.
+    ExpectedStmt {
+        got: Word,
+    },
+    /// An integer literal too large for an `int`.
+    IntLiteralTooLarge {
+        span: Span,
+    },
+    /// A `break` or `continue` statement not enclosed by any loop.
+    LoopControlOutsideLoop {
+        keyword: Word,
+    },
+    /// A `case` pattern already matched by a previous pattern.
+    DuplicateCasePattern {
+        pat_span: Span,
+        prev_span: Span,
+    },
+    /// A `case` statement over booleans which doesn't cover both values.
+    NonExhaustiveCase {
+        case_span: Span,
+        missing: bool,
+    },
+    /// A `case` range pattern whose start is greater than its end, e.g.
+    /// `5..3`, which matches nothing.
+    ReversedCaseRange {
+        span: Span,
+    },
+    /// A variable of a program which is declared but never used.
+    UnusedVariable {
+        span: Span,
+    },
+    /// A unit which isn't found in any directory of the search path.
+    UnitNotFound {
+        span: Span,
+    },
+    /// A unit which ends up using itself.
+    CyclicUnitUse {
+        span: Span,
+    },
+    /// A unit whose name differs from the one it's used by.
+    UnitNameMismatch {
+        span: Span,
+    },
+    /// Use of a variable which wasn't declared by a `let`.
+    UndeclaredVariable {
+        span: Span,
+    },
+    MismatchedTypes {
+        span: Span,
+        expected: Ty,
+        found: Ty,
+    },
+    /// An implicit conversion which may lose information, such as from
+    /// `float` to `int`.
+    LossyImplicitConversion {
+        span: Span,
+        from: Ty,
+        to: Ty,
+    },
+    InvalidBinaryOperands {
+        span: Span,
+        op: BinOp,
+        lhs: Ty,
+        rhs: Ty,
+    },
+    InvalidUnaryOperand {
+        span: Span,
+        op: UnOp,
+        ty: Ty,
     },
+    /// An explicit conversion between types which can't be converted.
+    InvalidCast {
+        span: Span,
+        from: Ty,
+        to: Ty,
+    },
+    /// Assignment to a constant, which can't be changed.
+    AssignToConstant {
+        span: Span,
+    },
+    /// Use of a variable in the value of a constant.
+    NonConstantExpr {
+        span: Span,
+    },
+    /// Call to a function which doesn't exist.
+    UndefinedFunction {
+        span: Span,
+    },
+    /// Call with a different number of arguments than the function takes.
+    WrongArgCount {
+        span: Span,
+        expected: usize,
+        found: usize,
+    },
+    /// Call to a procedure where a value is expected.
+    ProcedureInExpr {
+        span: Span,
+    },
+    /// Argument of `read` which isn't a variable.
+    NotAssignable {
+        span: Span,
+    },
+    /// Field width outside of the arguments of `write`.
+    MisplacedFormatSpec {
+        span: Span,
+    },
+    /// Precision for a value which isn't a float.
+    PrecisionOnNonFloat {
+        span: Span,
+        ty: Ty,
+    },
+    /// Failure to read a file.
+    IoError {
+        message: String,
+    },
+    /// An error stopping a program while it runs.
+    Runtime(RuntimeError),
+}
+
+/// An error stopping a program while it runs, at the expression or
+/// statement which failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub span: Span,
+    /// The calls of procedures the error happened in, innermost first. It's
+    /// empty until blaise has procedures.
+    pub trace: Vec<Frame>,
+}
+
+impl RuntimeError {
+    pub fn new(kind: RuntimeErrorKind, span: Span) -> RuntimeError {
+        RuntimeError {
+            kind,
+            span,
+            trace: vec![],
+        }
+    }
+}
+
+impl From<RuntimeError> for Diag {
+    fn from(err: RuntimeError) -> Diag {
+        Diag::Runtime(err)
+    }
+}
+
+/// A call in the trace of a runtime error.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// The name of the procedure called.
+    pub name: String,
+    /// The span of the call.
+    pub span: Span,
+}
+
+/// What went wrong while running a program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeErrorKind {
+    /// Integer division, modulo or float division by zero.
+    DivisionByZero,
+    /// Integer arithmetic whose result doesn't fit in an `int`.
+    Overflow { op: Op },
+    /// A string which doesn't hold a number of the type it's converted to.
+    InvalidConversion { text: String, ty: Ty },
+    /// Input which doesn't hold a value of the type being read.
+    InvalidInput { text: String, ty: Ty },
+    /// Reading past the end of the input.
+    UnexpectedEndOfInput,
+    /// Failure to read from the input or write to the output.
+    IoError { message: String },
+    /// String index outside of the string.
+    IndexOutOfBounds { index: i64, len: usize },
+    /// A function defined by the host failing, or returning a value of the
+    /// wrong type.
+    NativeFunctionFailed { name: String, message: String },
+    /// A program going over a limit on the resources it may use, in the
+    /// statement being executed.
+    LimitExceeded(Limit),
+}
+
+/// An operator, either binary or unary, or a conversion to a type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Binary(BinOp),
+    Unary(UnOp),
+    Cast(Ty),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Op::Binary(op) => write!(f, "{}", op),
+            Op::Unary(op) => write!(f, "{}", op),
+            Op::Cast(ty) => write!(f, "{}(..)", ty),
+        }
+    }
+}
+
+/// A limit on the resources a program may use at runtime, along with its
+/// value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    /// The number of statements executed and times around loops.
+    Steps(u64),
+    /// How deeply blocks and calls nest.
+    Depth(usize),
+    /// The number of bytes taken by the strings of variables, along with
+    /// any string being built.
+    Memory(usize),
+    /// How long the program runs.
+    Time(Duration),
 }
 
 impl Diag {
-    pub fn location(&self) -> BytePos {
+    /// The level a diagnostic is reported at by default.
+    pub fn level(&self) -> Level {
+        self.error_code().level
+    }
+
+    /// The stable code identifying the kind of a diagnostic, e.g. `B0017`.
+    pub fn code(&self) -> &'static str {
         match *self {
-            Diag::InvalidDigit { invalid_span } => invalid_span.start,
-            Diag::MissingExponentDigits { exp_pos } => exp_pos,
-            Diag::MissingTerminatingStringMark { str_start_pos, .. } => str_start_pos,
-            Diag::UnknownCharacter { pos } => pos,
+            Diag::TooManyErrors => "B0001",
+            Diag::InvalidDigit { .. } => "B0002",
+            Diag::MissingExponentDigits { .. } => "B0003",
+            Diag::MissingTerminatingStringMark { .. } => "B0004",
+            Diag::UnknownCharacter { .. } => "B0005",
+            Diag::UnexpectedEndOfFile => "B0006",
+            Diag::ExpectedWord { .. } => "B0007",
+            Diag::ExpectedOneOf { .. } => "B0008",
+            Diag::ExpectedExpr { .. } => "B0009",
+            Diag::LoopControlOutsideLoop { .. } => "B0010",
+            Diag::DuplicateCasePattern { .. } => "B0011",
+            Diag::NonExhaustiveCase { .. } => "B0012",
+            Diag::UnitNotFound { .. } => "B0013",
+            Diag::CyclicUnitUse { .. } => "B0014",
+            Diag::UnitNameMismatch { .. } => "B0015",
+            Diag::UndeclaredVariable { .. } => "B0016",
+            Diag::MismatchedTypes { .. } => "B0017",
+            Diag::LossyImplicitConversion { .. } => "B0018",
+            Diag::InvalidBinaryOperands { .. } => "B0019",
+            Diag::InvalidUnaryOperand { .. } => "B0020",
+            Diag::InvalidCast { .. } => "B0021",
+            Diag::AssignToConstant { .. } => "B0024",
+            Diag::NonConstantExpr { .. } => "B0025",
+            Diag::UndefinedFunction { .. } => "B0026",
+            Diag::WrongArgCount { .. } => "B0027",
+            Diag::ProcedureInExpr { .. } => "B0028",
+            Diag::NotAssignable { .. } => "B0029",
+            Diag::MisplacedFormatSpec { .. } => "B0030",
+            Diag::PrecisionOnNonFloat { .. } => "B0031",
+            Diag::IoError { .. } => "B0034",
+            Diag::ExpectedStmt { .. } => "B0039",
+            Diag::IntLiteralTooLarge { .. } => "B0040",
+            Diag::ReversedCaseRange { .. } => "B0041",
+            Diag::Runtime(ref err) => match err.kind {
+                RuntimeErrorKind::DivisionByZero => "B0022",
+                RuntimeErrorKind::InvalidConversion { .. } => "B0023",
+                RuntimeErrorKind::InvalidInput { .. } => "B0032",
+                RuntimeErrorKind::UnexpectedEndOfInput => "B0033",
+                RuntimeErrorKind::IoError { .. } => "B0042",
+                RuntimeErrorKind::IndexOutOfBounds { .. } => "B0035",
+                RuntimeErrorKind::NativeFunctionFailed { .. } => "B0036",
+                RuntimeErrorKind::LimitExceeded(_) => "B0037",
+                RuntimeErrorKind::Overflow { .. } => "B0038",
+            },
+            Diag::UnusedVariable { .. } => "B0043",
+        }
+    }
+
+    /// The code of a diagnostic along with its explanation.
+    pub fn error_code(&self) -> &'static ErrorCode {
+        error_codes::lookup(self.code()).unwrap()
+    }
+
+    /// The position a diagnostic is reported at, which is the start of its
+    /// primary span.
+    pub fn location(&self) -> BytePos {
+        self.span().map_or(DUMMY_BPOS, |span| span.start)
+    }
+
+    /// The span of code a diagnostic is about, if it's about any code.
+    pub fn span(&self) -> Option<Span> {
+        let span = match *self {
+            Diag::InvalidDigit { invalid_span } => invalid_span,
+            Diag::MissingExponentDigits { exp_pos } => Span {
+                start: exp_pos,
+                end: exp_pos,
+            },
+            Diag::MissingTerminatingStringMark {
+                str_start_pos,
+                eol_pos,
+            } => Span {
+                start: str_start_pos,
+                end: eol_pos,
+            },
+            Diag::UnknownCharacter { span, .. } => span,
             Diag::ExpectedWord {
                 got: Word { lexeme, .. },
                 ..
-            } => lexeme.start,
-            Diag::ExpectedOneOf {
+            }
+            | Diag::ExpectedOneOf {
                 got: Word { lexeme, .. },
                 ..
-            } => lexeme.start,
-            _ => DUMMY_BPOS,
+            }
+            | Diag::ExpectedExpr {
+                got: Word { lexeme, .. },
+            }
+            | Diag::ExpectedStmt {
+                got: Word { lexeme, .. },
+            }
+            | Diag::LoopControlOutsideLoop {
+                keyword: Word { lexeme, .. },
+            } => lexeme,
+            Diag::DuplicateCasePattern { pat_span, .. } => pat_span,
+            Diag::NonExhaustiveCase { case_span, .. } => case_span,
+            Diag::UnitNotFound { span }
+            | Diag::CyclicUnitUse { span }
+            | Diag::UnitNameMismatch { span }
+            | Diag::UndeclaredVariable { span }
+            | Diag::MismatchedTypes { span, .. }
+            | Diag::LossyImplicitConversion { span, .. }
+            | Diag::InvalidBinaryOperands { span, .. }
+            | Diag::InvalidUnaryOperand { span, .. }
+            | Diag::InvalidCast { span, .. }
+            | Diag::AssignToConstant { span }
+            | Diag::NonConstantExpr { span }
+            | Diag::UndefinedFunction { span }
+            | Diag::WrongArgCount { span, .. }
+            | Diag::ProcedureInExpr { span }
+            | Diag::NotAssignable { span }
+            | Diag::MisplacedFormatSpec { span }
+            | Diag::PrecisionOnNonFloat { span, .. }
+            | Diag::IntLiteralTooLarge { span }
+            | Diag::ReversedCaseRange { span }
+            | Diag::UnusedVariable { span }
+            | Diag::Runtime(RuntimeError { span, .. }) => span,
+            _ => return None,
+        };
+        Some(span)
+    }
+
+    /// Other spans of code involved in a diagnostic, each with a label
+    /// telling how.
+    pub fn secondary_spans(&self) -> Vec<(Span, String)> {
+        match *self {
+            Diag::DuplicateCasePattern { prev_span, .. } => {
+                vec![(prev_span, "first covered here".to_owned())]
+            }
+            Diag::Runtime(ref err) => err
+                .trace
+                .iter()
+                .map(|frame| (frame.span, format!("in `{}`, called here", frame.name)))
+                .collect(),
+            _ => vec![],
         }
     }
+
+    /// Further explanations of a diagnostic.
+    pub fn notes(&self) -> Vec<String> {
+        let note = match *self {
+            Diag::NonExhaustiveCase { missing, .. } => format!(
+                "add an arm for `{}` or an `else` block to cover it",
+                missing
+            ),
+            Diag::UnitNotFound { .. } => "units are looked for next to the file using them, \
+                 then in the directories listed by `BLAISE_PATH`"
+                .to_owned(),
+            Diag::LossyImplicitConversion { to, .. } => {
+                format!("use `{}(...)` to convert the value explicitly", to)
+            }
+            _ => return vec![],
+        };
+        vec![note]
+    }
+
+    /// Changes which may fix the code, best first.
+    pub fn suggestions(&self) -> &[Suggestion] {
+        match *self {
+            Diag::ExpectedWord {
+                ref suggestions, ..
+            }
+            | Diag::ExpectedOneOf {
+                ref suggestions, ..
+            } => suggestions,
+            _ => &[],
+        }
+    }
+
+    /// Describes a diagnostic reported at some level in the
+    /// `path:line:col: level[code]: message` form, followed by a note line
+    /// for each of its secondary spans and notes, and a help line for each
+    /// of its suggestions.
+    pub fn render(&self, level: Level, source_map: &SourceMap) -> String {
+        let location = source_map.pos_to_string(self.location());
+        let mut text = match *self {
+            // It's about the whole compilation rather than any file.
+            Diag::TooManyErrors => format!("{}[{}]: {}", level, self.code(), self),
+            _ => format!("{}: {}[{}]: {}", location, level, self.code(), self),
+        };
+        for (span, label) in self.secondary_spans() {
+            let _ = write!(
+                text,
+                "\n{}: {}: {}",
+                source_map.pos_to_string(span.start),
+                Level::Note,
+                label
+            );
+        }
+        for note in self.notes() {
+            let _ = write!(text, "\n{}: {}: {}", location, Level::Note, note);
+        }
+        for suggestion in self.suggestions() {
+            let _ = write!(
+                text,
+                "\n{}: {}: {}",
+                source_map.pos_to_string(suggestion.span.start),
+                Level::Help,
+                suggestion.message
+            );
+        }
+        text
+    }
+
+    /// Describes a diagnostic reported at some level as a JSON object, as
+    /// given to tools by `--error-format=json`.
+    ///
+    /// The object has a `severity` (`"error"` or `"warning"`), a `code`
+    /// (e.g. `"B0017"`), a `message`, the `spans` of code involved,
+    /// the primary one first, a list of `notes` and a list of suggested
+    /// `fixes`. Each span has the `file` it's in, the `byte_start` and
+    /// `byte_end` offsets into the file, the `line_start`, `col_start`,
+    /// `line_end` and `col_end` where it's shown, counted from 1 and with
+    /// columns in characters, whether it `is_primary`, and a `label` or
+    /// `null`. Each fix has a `message`, the `span` it replaces (`null` if
+    /// it's in no file), its `replacement` text and its `applicability`,
+    /// either `"machine-applicable"` or `"maybe-incorrect"`.
+    pub fn to_json(&self, level: Level, source_map: &SourceMap) -> Json {
+        let spans = self
+            .span()
+            .map(|span| (span, None))
+            .into_iter()
+            .chain(
+                self.secondary_spans()
+                    .into_iter()
+                    .map(|(span, label)| (span, Some(label))),
+            )
+            .filter_map(|(span, label)| {
+                let is_primary = label.is_none();
+                span_to_json(source_map, span, is_primary, label)
+            })
+            .collect();
+        Json::object(vec![
+            ("severity", Json::from(level.to_string())),
+            ("code", Json::from(self.code())),
+            ("message", Json::from(self.to_string())),
+            ("spans", Json::Array(spans)),
+            (
+                "notes",
+                Json::Array(self.notes().into_iter().map(Json::from).collect()),
+            ),
+            (
+                "fixes",
+                Json::Array(
+                    self.suggestions()
+                        .iter()
+                        .map(|suggestion| suggestion_to_json(source_map, suggestion))
+                        .collect(),
+                ),
+            ),
+        ])
+    }
 }
 
+/// Describes a suggestion as a JSON object. Its `span` is `null` if it
+/// doesn't belong to a file of the source map, rather than the fix being
+/// left out.
+fn suggestion_to_json(source_map: &SourceMap, suggestion: &Suggestion) -> Json {
+    let applicability = match suggestion.applicability {
+        Applicability::MachineApplicable => "machine-applicable",
+        Applicability::MaybeIncorrect => "maybe-incorrect",
+    };
+    Json::object(vec![
+        ("message", Json::from(suggestion.message.as_str())),
+        (
+            "span",
+            span_to_json(source_map, suggestion.span, false, None).unwrap_or(Json::Null),
+        ),
+        ("replacement", Json::from(suggestion.replacement.as_str())),
+        ("applicability", Json::from(applicability)),
+    ])
+}
+
+/// Applies suggestions to the text of a file, skipping those overlapping
+/// any suggestion applied before. Returns the changed text.
+pub fn apply_suggestions(file: &SourceFile, suggestions: &[&Suggestion]) -> String {
+    let mut suggestions = suggestions.to_vec();
+    suggestions.sort_by_key(|suggestion| (suggestion.span.start.0, suggestion.span.end.0));
+    let mut text = String::new();
+    let mut pos = file.start_pos();
+    let mut last_insertion = None;
+    for suggestion in suggestions {
+        let span = suggestion.span;
+        let overlaps = span.start < pos || (span.start == span.end && last_insertion == Some(pos));
+        if overlaps || span.end > file.end_pos() {
+            continue;
+        }
+        text.push_str(file.span_to_snippet(Span {
+            start: pos,
+            end: span.start,
+        }));
+        text.push_str(&suggestion.replacement);
+        pos = span.end;
+        if span.start == span.end {
+            last_insertion = Some(pos);
+        }
+    }
+    text.push_str(file.span_to_snippet(Span {
+        start: pos,
+        end: file.end_pos(),
+    }));
+    text
+}
+
+/// Describes a span as a JSON object, if it belongs to a file of the
+/// source map.
+fn span_to_json(
+    source_map: &SourceMap,
+    span: Span,
+    is_primary: bool,
+    label: Option<String>,
+) -> Option<Json> {
+    let (file, start) = source_map.lookup_source_location(span.start)?;
+    let end = file.lookup_source_location(span.end)?;
+    let offset = |pos: BytePos| (pos - file.start_pos()).to_usize();
+    Some(Json::object(vec![
+        ("file", Json::from(file.name().to_string())),
+        ("byte_start", Json::from(offset(span.start))),
+        ("byte_end", Json::from(offset(span.end))),
+        ("line_start", Json::from(start.line)),
+        ("col_start", Json::from(start.col_char + 1)),
+        ("line_end", Json::from(end.line)),
+        ("col_end", Json::from(end.col_char + 1)),
+        ("is_primary", Json::from(is_primary)),
+        ("label", label.map_or(Json::Null, Json::from)),
+    ]))
+}
+
+/// Changes to the levels diagnostics are reported at, as asked for on the
+/// command line by `-D` and `-A`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LevelConfig {
+    /// Whether every warning is reported as an error.
+    pub deny_warnings: bool,
+    /// The codes of the warnings reported as errors.
+    pub denied: Vec<&'static str>,
+    /// The codes of the warnings which aren't reported at all.
+    pub allowed: Vec<&'static str>,
+}
+
+impl LevelConfig {
+    /// The level a diagnostic is reported at, or `None` if it's allowed.
+    /// Errors can't be allowed.
+    pub fn level(&self, diag: &Diag) -> Option<Level> {
+        let level = diag.level();
+        if level != Level::Warning {
+            return Some(level);
+        }
+        let code = diag.code();
+        if self.allowed.contains(&code) {
+            None
+        } else if self.deny_warnings || self.denied.contains(&code) {
+            Some(Level::Error)
+        } else {
+            Some(level)
+        }
+    }
+}
+
+/// The diagnostics handler, which gathers the diagnostics reported while
+/// compiling and running a program, and hands them to an emitter.
+///
+/// Diagnostics are held until `flush` is called, or the handler is dropped,
+/// and then emitted sorted by their positions, so that the order they were
+/// found in doesn't matter. Identical diagnostics held at once are emitted
+/// only once.
 pub struct Handler {
-    emitter: Box<dyn Fn(Diag) -> bool>,
+    emitter: Box<dyn Fn(Diag, Level)>,
+    levels: LevelConfig,
+    /// How many errors are reported before the rest are dropped.
+    max_errors: Option<usize>,
+    /// The diagnostics reported since the last flush, along with the levels
+    /// they're reported at.
+    pending: RefCell<Vec<(Diag, Level)>>,
+    /// The indices of the pending diagnostics by their spans, so that
+    /// duplicates are found without going through every diagnostic.
+    pending_by_span: RefCell<HashMap<Option<Span>, Vec<usize>>>,
+    error_count: Cell<usize>,
+    warning_count: Cell<usize>,
 }
 
 impl Handler {
+    /// Creates a handler which gives each diagnostic to an emitter, along
+    /// with the level it's reported at.
     pub fn with_emitter<E>(emitter: E) -> Handler
     where
-        E: Fn(Diag) -> bool + 'static,
+        E: Fn(Diag, Level) + 'static,
     {
         Handler {
             emitter: Box::new(emitter),
+            levels: LevelConfig::default(),
+            max_errors: None,
+            pending: RefCell::new(vec![]),
+            pending_by_span: RefCell::new(HashMap::new()),
+            error_count: Cell::new(0),
+            warning_count: Cell::new(0),
         }
     }
 
     pub fn with_ignoring_emitter() -> Handler {
-        Handler {
-            emitter: Box::new(|_| true),
-        }
+        Handler::with_emitter(|_, _| {})
     }
 
+    /// Reports diagnostics at the levels given, rather than their own.
+    pub fn with_levels(mut self, levels: LevelConfig) -> Handler {
+        self.levels = levels;
+        self
+    }
+
+    /// Drops the diagnostics reported after a number of errors, reporting
+    /// `Diag::TooManyErrors` in their place.
+    pub fn with_max_errors(mut self, max_errors: usize) -> Handler {
+        self.max_errors = Some(max_errors);
+        self
+    }
+
+    /// Reports a diagnostic. Returns whether more diagnostics may be
+    /// reported, which is no longer the case once too many errors were.
     pub fn report(&self, diag: Diag) -> bool {
-        (self.emitter)(diag)
+        if self.has_too_many_errors() {
+            return false;
+        }
+        let level = match self.levels.level(&diag) {
+            Some(level) => level,
+            None => return true,
+        };
+        let mut pending = self.pending.borrow_mut();
+        let mut pending_by_span = self.pending_by_span.borrow_mut();
+        let same_span = pending_by_span.entry(diag.span()).or_default();
+        if same_span.iter().any(|&i| pending[i].0 == diag) {
+            return true;
+        }
+        same_span.push(pending.len());
+        pending.push((diag, level));
+        match level {
+            Level::Error => self.error_count.set(self.error_count.get() + 1),
+            Level::Warning => self.warning_count.set(self.warning_count.get() + 1),
+            Level::Note | Level::Help => {}
+        }
+        if self.has_too_many_errors() {
+            pending.push((Diag::TooManyErrors, Level::Error));
+            return false;
+        }
+        true
+    }
+
+    fn has_too_many_errors(&self) -> bool {
+        self.max_errors
+            .is_some_and(|max_errors| self.error_count.get() >= max_errors)
+    }
+
+    /// Emits the diagnostics reported since the last flush, in the order of
+    /// their positions. Those without a position come last.
+    pub fn flush(&self) {
+        let mut pending = self.pending.replace(vec![]);
+        self.pending_by_span.borrow_mut().clear();
+        pending.sort_by_key(|(diag, _)| diag.span().map_or(usize::MAX, |span| span.start.0));
+        for (diag, level) in pending {
+            (self.emitter)(diag, level);
+        }
+    }
+
+    /// How many errors were reported so far.
+    pub fn error_count(&self) -> usize {
+        self.error_count.get()
+    }
+
+    /// How many warnings were reported so far.
+    pub fn warning_count(&self) -> usize {
+        self.warning_count.get()
+    }
+
+    /// Whether any error was reported so far.
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+}
+
+impl Drop for Handler {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Level::Error => write!(f, "error"),
+            Level::Warning => write!(f, "warning"),
+            Level::Note => write!(f, "note"),
+            Level::Help => write!(f, "help"),
+        }
     }
 }
 
@@ -91,12 +801,15 @@ impl fmt::Display for Diag {
             Diag::MissingTerminatingStringMark { .. } => {
                 write!(f, "missing terminating quotation mark for string literal")
             }
-            Diag::UnknownCharacter { .. } => write!(f, "unknown character"),
+            Diag::UnknownCharacter { count: 1, .. } => write!(f, "unknown character"),
+            Diag::UnknownCharacter { count, .. } => write!(f, "{} unknown characters", count),
             Diag::UnexpectedEndOfFile => write!(f, "unexpected end of file"),
-            Diag::ExpectedWord { expected, got } => {
+            Diag::ExpectedWord { expected, got, .. } => {
                 write!(f, "expected {}, but got {}", expected, got.category)
             }
-            Diag::ExpectedOneOf { ref expected, got } => {
+            Diag::ExpectedOneOf {
+                ref expected, got, ..
+            } => {
                 let one_of = expected
                     .iter()
                     .map(|c| c.to_string())
@@ -104,6 +817,292 @@ impl fmt::Display for Diag {
                     .join(", ");
                 write!(f, "expected one of {}, but got {}", one_of, got.category)
             }
+            Diag::ExpectedExpr { got } => {
+                write!(f, "expected expression, but got {}", got.category)
+            }
+            Diag::ExpectedStmt { got } => {
+                write!(f, "expected statement, but got {}", got.category)
+            }
+            Diag::IntLiteralTooLarge { .. } => write!(f, "integer literal too large"),
+            Diag::LoopControlOutsideLoop { keyword } => {
+                write!(f, "{} outside of a loop", keyword.category)
+            }
+            Diag::DuplicateCasePattern { .. } => {
+                write!(f, "pattern is already covered by a previous case arm")
+            }
+            Diag::NonExhaustiveCase { missing, .. } => {
+                write!(f, "non-exhaustive case: `{}` not covered", missing)
+            }
+            Diag::ReversedCaseRange { .. } => {
+                write!(
+                    f,
+                    "range pattern is empty, as its start is greater than its end"
+                )
+            }
+            Diag::UnusedVariable { .. } => write!(f, "unused variable"),
+            Diag::UnitNotFound { .. } => write!(f, "unit not found in the search path"),
+            Diag::CyclicUnitUse { .. } => write!(f, "unit is used by itself, directly or not"),
+            Diag::UnitNameMismatch { .. } => {
+                write!(f, "unit name doesn't match the name it's used by")
+            }
+            Diag::UndeclaredVariable { .. } => write!(f, "use of undeclared variable"),
+            Diag::MismatchedTypes {
+                expected, found, ..
+            } => write!(
+                f,
+                "mismatched types: expected {}, but found {}",
+                expected, found
+            ),
+            Diag::LossyImplicitConversion { from, to, .. } => write!(
+                f,
+                "implicit conversion from {} to {} may lose precision; convert it explicitly",
+                from, to
+            ),
+            Diag::InvalidBinaryOperands { op, lhs, rhs, .. } => write!(
+                f,
+                "operator `{}` cannot be applied to {} and {}",
+                op, lhs, rhs
+            ),
+            Diag::InvalidUnaryOperand { op, ty, .. } => {
+                write!(f, "operator `{}` cannot be applied to {}", op, ty)
+            }
+            Diag::InvalidCast { from, to, .. } => {
+                write!(f, "cannot convert {} to {}", from, to)
+            }
+            Diag::AssignToConstant { .. } => write!(f, "cannot assign to a constant"),
+            Diag::NonConstantExpr { .. } => {
+                write!(f, "variables cannot be used in the value of a constant")
+            }
+            Diag::UndefinedFunction { .. } => write!(f, "call to undefined function"),
+            Diag::WrongArgCount {
+                expected, found, ..
+            } => write!(
+                f,
+                "function takes {} argument(s), but {} were supplied",
+                expected, found
+            ),
+            Diag::ProcedureInExpr { .. } => write!(f, "procedure call doesn't have a value"),
+            Diag::NotAssignable { .. } => write!(f, "expected a variable to read into"),
+            Diag::MisplacedFormatSpec { .. } => {
+                write!(f, "field width is only allowed in arguments of `write`")
+            }
+            Diag::PrecisionOnNonFloat { ty, .. } => {
+                write!(
+                    f,
+                    "precision is only allowed for `float` values, not {}",
+                    ty
+                )
+            }
+            Diag::IoError { ref message } => write!(f, "I/O error: {}", message),
+            Diag::Runtime(ref err) => write!(f, "{}", err.kind),
         }
     }
 }
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuntimeErrorKind::DivisionByZero => write!(f, "division by zero"),
+            RuntimeErrorKind::Overflow { op: Op::Cast(ty) } => {
+                write!(f, "integer overflow in conversion to {}", ty)
+            }
+            RuntimeErrorKind::Overflow { op } => {
+                write!(f, "integer overflow in operator `{}`", op)
+            }
+            RuntimeErrorKind::InvalidConversion { ref text, ty } => {
+                write!(f, "cannot convert {:?} to {}", text, ty)
+            }
+            RuntimeErrorKind::InvalidInput { ref text, ty } => {
+                write!(
+                    f,
+                    "invalid input {:?}, expected a value of type {}",
+                    text, ty
+                )
+            }
+            RuntimeErrorKind::UnexpectedEndOfInput => write!(f, "unexpected end of input"),
+            RuntimeErrorKind::IoError { ref message } => write!(f, "I/O error: {}", message),
+            RuntimeErrorKind::IndexOutOfBounds { index, len } => write!(
+                f,
+                "index {} is out of bounds for a string of length {}",
+                index, len
+            ),
+            RuntimeErrorKind::NativeFunctionFailed {
+                ref name,
+                ref message,
+            } => write!(f, "`{}` failed: {}", name, message),
+            RuntimeErrorKind::LimitExceeded(limit) => match limit {
+                Limit::Steps(max) => write!(f, "execution stopped after {} steps", max),
+                Limit::Depth(max) => {
+                    write!(f, "execution stopped at {} nested blocks and calls", max)
+                }
+                Limit::Memory(max) => write!(
+                    f,
+                    "execution stopped with more than {} bytes of strings",
+                    max
+                ),
+                Limit::Time(max) => write!(f, "execution stopped after {:?}", max),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Applicability, Diag, Frame, Handler, Level, RuntimeError, RuntimeErrorKind, Suggestion,
+    };
+    use scanner::{Category, Word};
+    use source_map::{BytePos, SourceMap, Span, DUMMY_SPAN};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn unknown_char(pos: usize) -> Diag {
+        Diag::UnknownCharacter {
+            span: Span {
+                start: BytePos(pos),
+                end: BytePos(pos + 1),
+            },
+            count: 1,
+        }
+    }
+
+    fn non_exhaustive_case(pos: usize) -> Diag {
+        Diag::NonExhaustiveCase {
+            case_span: Span {
+                start: BytePos(pos),
+                end: BytePos(pos + 4),
+            },
+            missing: true,
+        }
+    }
+
+    type Emitted = Rc<RefCell<Vec<(Diag, Level)>>>;
+
+    fn handler() -> (Handler, Emitted) {
+        let diags = Rc::new(RefCell::new(vec![]));
+        let handler = {
+            let diags = diags.clone();
+            Handler::with_emitter(move |diag, level| diags.borrow_mut().push((diag, level)))
+        };
+        (handler, diags)
+    }
+
+    #[test]
+    fn test_handler_counts_errors_and_warnings() {
+        let (handler, _) = handler();
+        assert!(!handler.has_errors());
+        handler.report(non_exhaustive_case(0));
+        assert!(!handler.has_errors());
+        handler.report(unknown_char(1));
+        handler.report(unknown_char(2));
+        assert!(handler.has_errors());
+        assert_eq!(2, handler.error_count());
+        assert_eq!(1, handler.warning_count());
+    }
+
+    #[test]
+    fn test_handler_drops_duplicates() {
+        let (handler, diags) = handler();
+        handler.report(unknown_char(3));
+        handler.report(unknown_char(3));
+        handler.flush();
+        assert_eq!(vec![(unknown_char(3), Level::Error)], diags.take());
+        assert_eq!(1, handler.error_count());
+    }
+
+    #[test]
+    fn test_handler_emits_in_source_order() {
+        let (handler, diags) = handler();
+        handler.report(Diag::UnexpectedEndOfFile);
+        handler.report(unknown_char(7));
+        handler.report(non_exhaustive_case(2));
+        handler.flush();
+        assert_eq!(
+            vec![
+                (non_exhaustive_case(2), Level::Warning),
+                (unknown_char(7), Level::Error),
+                (Diag::UnexpectedEndOfFile, Level::Error),
+            ],
+            diags.take()
+        );
+    }
+
+    #[test]
+    fn test_handler_stops_at_max_errors() {
+        let (handler, diags) = handler();
+        let handler = handler.with_max_errors(2);
+        assert!(handler.report(unknown_char(1)));
+        assert!(handler.report(non_exhaustive_case(2)));
+        assert!(!handler.report(unknown_char(3)));
+        assert!(!handler.report(unknown_char(4)));
+        handler.flush();
+        assert_eq!(
+            vec![
+                (unknown_char(1), Level::Error),
+                (non_exhaustive_case(2), Level::Warning),
+                (unknown_char(3), Level::Error),
+                (Diag::TooManyErrors, Level::Error),
+            ],
+            diags.take()
+        );
+    }
+
+    #[test]
+    fn test_render_runtime_error_trace() {
+        let source_map = SourceMap::new();
+        source_map.add_file("test".into(), "x = f(1);\ny = g();\n".to_owned());
+        let span = |start, end| Span {
+            start: BytePos(start),
+            end: BytePos(end),
+        };
+        let mut err = RuntimeError::new(RuntimeErrorKind::DivisionByZero, span(4, 8));
+        err.trace.push(Frame {
+            name: "g".to_owned(),
+            span: span(14, 17),
+        });
+        assert_eq!(
+            "<test>:1:5: error[B0022]: division by zero\n\
+             <test>:2:5: note: in `g`, called here",
+            Diag::from(err).render(Level::Error, &source_map)
+        );
+    }
+
+    #[test]
+    fn test_io_error_codes() {
+        let message = "broken pipe".to_owned();
+        assert_eq!("B0034", Diag::IoError { message }.code());
+        let message = "broken pipe".to_owned();
+        let err = RuntimeError::new(RuntimeErrorKind::IoError { message }, DUMMY_SPAN);
+        assert_eq!("B0042", Diag::from(err).code());
+    }
+
+    #[test]
+    fn test_json_keeps_fixes_outside_files() {
+        let source_map = SourceMap::new();
+        source_map.add_file("test".into(), "x = 1".to_owned());
+        let span = Span {
+            start: BytePos(9),
+            end: BytePos(9),
+        };
+        let diag = Diag::ExpectedWord {
+            expected: Category::Semi,
+            got: Word {
+                category: Category::Eof,
+                lexeme: span,
+            },
+            suggestions: vec![Suggestion {
+                message: "add `;` here".to_owned(),
+                span,
+                replacement: ";".to_owned(),
+                applicability: Applicability::MachineApplicable,
+            }],
+        };
+        assert!(diag
+            .to_json(Level::Error, &source_map)
+            .to_string()
+            .ends_with(
+                "\"spans\":[],\"notes\":[],\"fixes\":[{\"message\":\"add `;` here\",\
+                 \"span\":null,\"replacement\":\";\",\"applicability\":\"machine-applicable\"}]}"
+            ));
+    }
+}
@@ -0,0 +1,82 @@
+use errors::{self, Applicability, Diag};
+use parser::Parser;
+use scanner::{Scanner, WordStream};
+use source_map::SourceFile;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The most rounds of fixes applied to a file.
+const MAX_ROUNDS: usize = 100;
+
+/// Applies the machine-applicable suggestions made for the program or unit
+/// of a source file, returning its fixed text.
+///
+/// Parsing stops at the first error, so the file is parsed again after each
+/// round of fixes, until no more fixes can be applied.
+pub fn fix_source(file: &SourceFile) -> String {
+    let mut src = (*file.src).clone();
+    for _ in 0..MAX_ROUNDS {
+        let file = SourceFile::new(file.name().clone(), src);
+        let suggestions = parse_diags(&file)
+            .into_iter()
+            .flat_map(|diag| diag.suggestions().to_vec())
+            .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable)
+            .collect::<Vec<_>>();
+        let fixed_src = errors::apply_suggestions(&file, &suggestions.iter().collect::<Vec<_>>());
+        if fixed_src == *file.src {
+            return fixed_src;
+        }
+        src = fixed_src;
+    }
+    src
+}
+
+/// Parses a file, returning the diagnostics reported.
+fn parse_diags(file: &SourceFile) -> Vec<Diag> {
+    let diags = Rc::new(RefCell::new(vec![]));
+    let handler = {
+        let diags = diags.clone();
+        errors::Handler::with_emitter(move |diag, _| diags.borrow_mut().push(diag))
+    };
+    let file = Rc::new(SourceFile::new(file.name().clone(), (*file.src).clone()));
+    let mut parser = Parser::new(WordStream::new(Scanner::new(file), &handler));
+    let result = if parser.is_unit() {
+        parser.parse_unit().map(|_| ())
+    } else {
+        parser.parse_program().map(|_| ())
+    };
+    if let Err(diag) = result {
+        handler.report(diag);
+    }
+    handler.flush();
+    diags.take()
+}
+
+#[cfg(test)]
+mod test {
+    use super::fix_source;
+    use source_map::SourceFile;
+
+    fn fix(src: &str) -> String {
+        fix_source(&SourceFile::new("test".into(), src.into()))
+    }
+
+    #[test]
+    fn test_fix_missing_semis() {
+        assert_eq!(
+            "program p;\nlet x: int;\nx = 1;\nwriteln(x);\n",
+            fix("program p\nlet x: int\nx = 1\nwriteln(x)\n")
+        );
+    }
+
+    #[test]
+    fn test_fix_leaves_maybe_incorrect_suggestions() {
+        let src = "program p; let x: int; whlie x < 3 { x = x + 1; }";
+        assert_eq!(src, fix(src));
+    }
+
+    #[test]
+    fn test_fix_stops_at_unfixable_errors() {
+        assert_eq!("program p; x = 1; y = ;", fix("program p; x = 1 y = ;"));
+    }
+}
@@ -0,0 +1,750 @@
+use ast;
+use errors::{self, Diag};
+use parser::Parser;
+use scanner::{Scanner, WordStream};
+use source_map::{BytePos, SourceFile, Span};
+use std::rc::Rc;
+use std::result;
+
+type Result<T> = result::Result<T, Diag>;
+
+const INDENT: &str = "    ";
+
+/// Formats the program or unit of a source file in the canonical style,
+/// keeping its comments.
+pub fn format_source(file: Rc<SourceFile>, handler: &errors::Handler) -> Result<String> {
+    let mut parser = Parser::new(WordStream::new(Scanner::new(file.clone()), handler));
+    if parser.is_unit() {
+        let unit = parser.parse_unit()?;
+        let mut formatter = Formatter::new(&file, parser.ident_table(), parser.comments());
+        formatter.unit(&unit);
+        Ok(formatter.finish())
+    } else {
+        let program = parser.parse_program()?;
+        let mut formatter = Formatter::new(&file, parser.ident_table(), parser.comments());
+        formatter.program(&program);
+        Ok(formatter.finish())
+    }
+}
+
+/// A declaration, either of a constant or of a variable.
+enum AnyDecl<'a> {
+    Const(&'a ast::ConstDecl),
+    Var(&'a ast::Decl),
+}
+
+impl<'a> AnyDecl<'a> {
+    fn ident(&self) -> &'a ast::Ident {
+        match *self {
+            AnyDecl::Const(decl) => &decl.ident,
+            AnyDecl::Var(decl) => &decl.ident,
+        }
+    }
+}
+
+/// The pretty-printer.
+///
+/// Statements are laid out one per line, indented by nesting level, and
+/// expressions are written with spaces around binary operators and only the
+/// parentheses their operators' precedences require. Comments are written
+/// before the first construct following them in the source, or at the end
+/// of the previous line if they followed some code in their line. At most
+/// one blank line is kept wherever the source had blank lines.
+struct Formatter<'a> {
+    source_file: &'a SourceFile,
+    ident_table: &'a ast::IdentTable,
+    comments: &'a [Span],
+    next_comment: usize,
+    out: String,
+    indent: usize,
+    /// Whether the next line starts a new section of the file, which is
+    /// preceded by a blank line.
+    in_new_section: bool,
+    /// Whether the next line is the first of a block, which is never
+    /// preceded by a blank line.
+    in_new_block: bool,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(
+        source_file: &'a SourceFile,
+        ident_table: &'a ast::IdentTable,
+        comments: &'a [Span],
+    ) -> Formatter<'a> {
+        Formatter {
+            source_file,
+            ident_table,
+            comments,
+            next_comment: 0,
+            out: String::new(),
+            indent: 0,
+            in_new_section: false,
+            in_new_block: true,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let end_pos = self.source_file.end_pos();
+        self.comments_before(end_pos + BytePos(1));
+        self.out
+    }
+
+    fn program(&mut self, program: &ast::Program) {
+        self.header("program", &program.name);
+        self.uses(&program.uses);
+        self.decls(&program.consts, &program.decls);
+        for stmt in &program.stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn unit(&mut self, unit: &ast::Unit) {
+        self.header("unit", &unit.name);
+        self.uses(&unit.uses);
+        self.decls(&unit.consts, &unit.decls);
+    }
+
+    fn header(&mut self, keyword: &str, name: &ast::Ident) {
+        self.comments_before(name.span.start);
+        self.begin_line(None);
+        self.out.push_str(keyword);
+        self.out.push(' ');
+        self.out.push_str(self.ident_table.get(name.name));
+        self.out.push(';');
+        self.end_line();
+        self.in_new_section = true;
+    }
+
+    fn uses(&mut self, uses: &[ast::Ident]) {
+        if uses.is_empty() {
+            return;
+        }
+        self.comments_before(uses[0].span.start);
+        self.begin_line(None);
+        self.out.push_str("uses ");
+        let names = uses
+            .iter()
+            .map(|ident| self.ident_table.get(ident.name))
+            .collect::<Vec<_>>();
+        self.out.push_str(&names.join(", "));
+        self.out.push(';');
+        self.end_line();
+        self.in_new_section = true;
+    }
+
+    /// Writes the declarations in the order they were given, one per line.
+    fn decls(&mut self, consts: &[ast::ConstDecl], decls: &[ast::Decl]) {
+        let mut all_decls = consts
+            .iter()
+            .map(AnyDecl::Const)
+            .chain(decls.iter().map(AnyDecl::Var))
+            .collect::<Vec<_>>();
+        all_decls.sort_by_key(|decl| decl.ident().span.start.0);
+
+        for decl in &all_decls {
+            let ident = decl.ident();
+            let start = self.decl_start(ident.span.start);
+            self.comments_before(start);
+            self.begin_line(Some(start));
+            match *decl {
+                AnyDecl::Const(decl) => {
+                    self.out.push_str("const ");
+                    self.out.push_str(self.ident_table.get(ident.name));
+                    self.out.push_str(": ");
+                    self.out.push_str(decl.ty.name());
+                    self.out.push_str(" = ");
+                    self.expr(&decl.expr);
+                }
+                AnyDecl::Var(decl) => {
+                    self.out.push_str("let ");
+                    self.out.push_str(self.ident_table.get(ident.name));
+                    self.out.push_str(": ");
+                    self.out.push_str(decl.ty.name());
+                }
+            }
+            self.out.push(';');
+            self.end_line();
+        }
+        if !all_decls.is_empty() {
+            self.in_new_section = true;
+        }
+    }
+
+    /// Finds the position of the keyword starting the declaration of an
+    /// identifier, as declarations only keep the identifier's span.
+    fn decl_start(&self, ident_pos: BytePos) -> BytePos {
+        let before = self.src_before(ident_pos).trim_end();
+        ["let", "const"]
+            .iter()
+            .find(|keyword| before.ends_with(*keyword))
+            .map_or(ident_pos, |keyword| {
+                self.source_file.start_pos() + BytePos(before.len() - keyword.len())
+            })
+    }
+
+    fn stmt(&mut self, stmt: &ast::Stmt) {
+        use ast::StmtKind;
+        self.comments_before(stmt.span.start);
+        self.begin_line(Some(stmt.span.start));
+        match stmt.kind {
+            StmtKind::While(ref cond_expr, ref block) => {
+                self.out.push_str("while ");
+                self.expr(cond_expr);
+                self.out.push(' ');
+                self.block(block);
+                self.end_line();
+            }
+            StmtKind::For(ref ident, ref start_expr, ref dir, ref end_expr, ref block) => {
+                self.out.push_str("for ");
+                self.out.push_str(self.ident_table.get(ident.name));
+                self.out.push_str(" = ");
+                self.expr(start_expr);
+                self.out.push_str(match *dir {
+                    ast::ForDir::To => " to ",
+                    ast::ForDir::Downto => " downto ",
+                });
+                self.expr(end_expr);
+                self.out.push(' ');
+                self.block(block);
+                self.end_line();
+            }
+            StmtKind::Repeat(ref block, ref cond_expr) => {
+                self.out.push_str("repeat ");
+                self.block(block);
+                self.out.push_str(" until ");
+                self.expr(cond_expr);
+                self.out.push(';');
+                self.end_line();
+            }
+            StmtKind::Break => {
+                self.out.push_str("break;");
+                self.end_line();
+            }
+            StmtKind::Continue => {
+                self.out.push_str("continue;");
+                self.end_line();
+            }
+            StmtKind::If(ref cond_expr, ref then_block, ref else_block) => {
+                self.if_chain(cond_expr, then_block, else_block);
+                self.end_line();
+            }
+            StmtKind::Case(ref expr, ref arms, ref else_block) => {
+                self.out.push_str("case ");
+                self.expr(expr);
+                self.out.push_str(" of");
+                self.end_line();
+                self.indent += 1;
+                self.in_new_block = true;
+                for arm in arms {
+                    self.comments_before(arm.block.span.start);
+                    self.begin_line(None);
+                    let pats = arm.pats.iter().map(pat_to_string).collect::<Vec<_>>();
+                    self.out.push_str(&pats.join(", "));
+                    self.out.push_str(": ");
+                    self.block(&arm.block);
+                    self.end_line();
+                }
+                if let Some(ref else_block) = *else_block {
+                    self.comments_before(else_block.span.start);
+                    self.begin_line(None);
+                    self.out.push_str("else ");
+                    self.block(else_block);
+                    self.end_line();
+                }
+                self.indent -= 1;
+            }
+            StmtKind::Assign(ref ident, ref expr) => {
+                self.out.push_str(self.ident_table.get(ident.name));
+                self.out.push_str(" = ");
+                self.expr(expr);
+                self.out.push(';');
+                self.end_line();
+            }
+            StmtKind::BlockStmt(ref block) => {
+                self.block(block);
+                self.end_line();
+            }
+            StmtKind::Call(ref ident, ref args) => {
+                self.call(ident, args);
+                self.out.push(';');
+                self.end_line();
+            }
+        }
+    }
+
+    /// Writes an `if` statement along with the `else if`s chained to it.
+    fn if_chain(
+        &mut self,
+        cond_expr: &ast::Expr,
+        then_block: &ast::Block,
+        else_block: &Option<Box<ast::Block>>,
+    ) {
+        self.out.push_str("if ");
+        self.expr(cond_expr);
+        self.out.push(' ');
+        self.block(then_block);
+        let else_block = match *else_block {
+            Some(ref else_block) => else_block,
+            None => return,
+        };
+        self.out.push_str(" else ");
+        // An `else if` is an `else` block spanning just the chained `if`.
+        if let [ref stmt] = else_block.stmts[..] {
+            if let ast::StmtKind::If(ref cond_expr, ref then_block, ref else_else) = stmt.kind {
+                if stmt.span == else_block.span {
+                    return self.if_chain(cond_expr, then_block, else_else);
+                }
+            }
+        }
+        self.block(else_block);
+    }
+
+    /// Writes a block from its `{` up to its `}`, leaving the line open.
+    fn block(&mut self, block: &ast::Block) {
+        self.out.push('{');
+        self.end_line();
+        self.indent += 1;
+        self.in_new_block = true;
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+        self.comments_before(block.span.end);
+        self.indent -= 1;
+        self.begin_line(None);
+        self.out.push('}');
+    }
+
+    fn call(&mut self, ident: &ast::Ident, args: &[ast::Expr]) {
+        self.out.push_str(self.ident_table.get(ident.name));
+        self.out.push('(');
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(arg);
+        }
+        self.out.push(')');
+    }
+
+    fn expr(&mut self, expr: &ast::Expr) {
+        use ast::ExprKind;
+        let expr = strip_parens(expr);
+        match expr.kind {
+            ExprKind::BinaryOp(op, ref lhs, ref rhs) => {
+                let prec = op.precedence();
+                let lhs_prec = expr_prec(lhs);
+                let rhs_prec = expr_prec(rhs);
+                self.operand(
+                    lhs,
+                    lhs_prec < prec || (lhs_prec == prec && op.is_right_assoc()),
+                );
+                self.out.push(' ');
+                self.out.push_str(&op.to_string());
+                self.out.push(' ');
+                // A unary operator on the right-hand side is parsed before
+                // anything else, so it never needs parentheses there.
+                let is_unary = matches!(strip_parens(rhs).kind, ExprKind::UnaryOp(..));
+                let needs_parens = rhs_prec < prec || (rhs_prec == prec && !op.is_right_assoc());
+                self.operand(rhs, !is_unary && needs_parens);
+            }
+            ExprKind::UnaryOp(op, ref operand) => {
+                self.out.push_str(&op.to_string());
+                self.operand(operand, expr_prec(operand) <= op.precedence());
+            }
+            ExprKind::Lit(_) => {
+                // Literals are written as they were, e.g. keeping the
+                // exponent of a float.
+                let lexeme = self.source_file.span_to_snippet(expr.span);
+                self.out.push_str(lexeme);
+            }
+            ExprKind::Ident(ref ident) => self.out.push_str(self.ident_table.get(ident.name)),
+            ExprKind::Paren(_) => panic!("has to be stripped of parentheses!"),
+            ExprKind::Cast(ty, ref operand) => {
+                self.out.push_str(ty.name());
+                self.out.push('(');
+                self.expr(operand);
+                self.out.push(')');
+            }
+            ExprKind::Call(ref ident, ref args) => self.call(ident, args),
+            ExprKind::Formatted(ref value, ref width, ref prec) => {
+                self.expr(value);
+                self.out.push(':');
+                self.expr(width);
+                if let Some(ref prec) = *prec {
+                    self.out.push(':');
+                    self.expr(prec);
+                }
+            }
+        }
+    }
+
+    fn operand(&mut self, expr: &ast::Expr, needs_parens: bool) {
+        if needs_parens {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    /// Writes the comments found before a position which weren't written
+    /// yet.
+    fn comments_before(&mut self, pos: BytePos) {
+        while let Some(&span) = self.comments.get(self.next_comment) {
+            if span.start >= pos {
+                break;
+            }
+            self.next_comment += 1;
+            let text = self.source_file.span_to_snippet(span).trim_end();
+            let follows_code = !self.line_before(span.start).trim().is_empty();
+            if follows_code && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push(' ');
+                self.out.push_str(text);
+            } else {
+                self.begin_line(Some(span.start));
+                self.out.push_str(text);
+            }
+            self.end_line();
+        }
+    }
+
+    /// Starts a line, preceding it with a blank line if it starts a new
+    /// section, or if there was one before `pos` in the source.
+    fn begin_line(&mut self, pos: Option<BytePos>) {
+        let follows_blank_line =
+            !self.in_new_block && pos.is_some_and(|pos| self.follows_blank_line(pos));
+        if (self.in_new_section || follows_blank_line) && !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.in_new_section = false;
+        self.in_new_block = false;
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.out.push('\n');
+    }
+
+    /// Whether there's a blank line between a position and the code or
+    /// comment preceding it.
+    fn follows_blank_line(&self, pos: BytePos) -> bool {
+        let before = self.src_before(pos);
+        let spacing = &before[before.trim_end().len()..];
+        spacing.matches('\n').count() >= 2
+    }
+
+    /// The source text from the start of a position's line up to it.
+    fn line_before(&self, pos: BytePos) -> &'a str {
+        let before = self.src_before(pos);
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        &before[line_start..]
+    }
+
+    fn src_before(&self, pos: BytePos) -> &'a str {
+        let source_file: &'a SourceFile = self.source_file;
+        source_file.span_to_snippet(Span {
+            start: source_file.start_pos(),
+            end: pos,
+        })
+    }
+}
+
+fn strip_parens(mut expr: &ast::Expr) -> &ast::Expr {
+    while let ast::ExprKind::Paren(ref inner) = expr.kind {
+        expr = inner;
+    }
+    expr
+}
+
+/// How tightly an expression binds its operands, where operands of looser
+/// operators need parentheses.
+fn expr_prec(expr: &ast::Expr) -> u32 {
+    match strip_parens(expr).kind {
+        ast::ExprKind::BinaryOp(op, ..) => op.precedence(),
+        ast::ExprKind::UnaryOp(op, _) => op.precedence(),
+        _ => u32::MAX,
+    }
+}
+
+fn pat_to_string(pat: &ast::Pat) -> String {
+    match pat.kind {
+        ast::PatKind::Int(value) => value.to_string(),
+        ast::PatKind::Range(low, high) => format!("{}..{}", low, high),
+        ast::PatKind::Lit(ast::Lit::IntLit(value)) => value.to_string(),
+        ast::PatKind::Lit(ast::Lit::FloatLit(value)) => format!("{:?}", value),
+        ast::PatKind::Lit(ast::Lit::StrLit(ref value)) => format!("\"{}\"", value),
+        ast::PatKind::Lit(ast::Lit::BoolLit(value)) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::format_source;
+    use ast;
+    use errors;
+    use parser::Parser;
+    use scanner::{Scanner, WordStream};
+    use source_map::{SourceFile, DUMMY_SPAN};
+    use std::mem;
+    use std::rc::Rc;
+
+    fn format(src: &str) -> String {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let file = Rc::new(SourceFile::new("test".into(), src.into()));
+        format_source(file, &handler).unwrap()
+    }
+
+    fn parse(src: &str, ident_table: ast::IdentTable) -> (ast::Program, ast::IdentTable) {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let file = Rc::new(SourceFile::new("test".into(), src.into()));
+        let word_stream = WordStream::new(Scanner::new(file), &handler);
+        let mut parser = Parser::with_ident_table(word_stream, ident_table);
+        let mut program = parser.parse_program().unwrap();
+        normalize_program(&mut program);
+        (program, parser.into_ident_table())
+    }
+
+    /// Drops what formatting may change from a program, namely spans and
+    /// parentheses.
+    fn normalize_program(program: &mut ast::Program) {
+        program.name.span = DUMMY_SPAN;
+        for ident in &mut program.uses {
+            ident.span = DUMMY_SPAN;
+        }
+        for decl in &mut program.consts {
+            decl.ident.span = DUMMY_SPAN;
+            normalize_expr(&mut decl.expr);
+        }
+        for decl in &mut program.decls {
+            decl.ident.span = DUMMY_SPAN;
+        }
+        for stmt in &mut program.stmts {
+            normalize_stmt(stmt);
+        }
+    }
+
+    fn normalize_block(block: &mut ast::Block) {
+        block.span = DUMMY_SPAN;
+        for stmt in &mut block.stmts {
+            normalize_stmt(stmt);
+        }
+    }
+
+    fn normalize_stmt(stmt: &mut ast::Stmt) {
+        use ast::StmtKind;
+        stmt.span = DUMMY_SPAN;
+        match stmt.kind {
+            StmtKind::While(ref mut expr, ref mut block) => {
+                normalize_expr(expr);
+                normalize_block(block);
+            }
+            StmtKind::For(ref mut ident, ref mut start, _, ref mut end, ref mut block) => {
+                ident.span = DUMMY_SPAN;
+                normalize_expr(start);
+                normalize_expr(end);
+                normalize_block(block);
+            }
+            StmtKind::Repeat(ref mut block, ref mut expr) => {
+                normalize_block(block);
+                normalize_expr(expr);
+            }
+            StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::If(ref mut expr, ref mut then_block, ref mut else_block) => {
+                normalize_expr(expr);
+                normalize_block(then_block);
+                if let Some(ref mut else_block) = *else_block {
+                    normalize_block(else_block);
+                }
+            }
+            StmtKind::Case(ref mut expr, ref mut arms, ref mut else_block) => {
+                normalize_expr(expr);
+                for arm in arms {
+                    for pat in &mut arm.pats {
+                        pat.span = DUMMY_SPAN;
+                    }
+                    normalize_block(&mut arm.block);
+                }
+                if let Some(ref mut else_block) = *else_block {
+                    normalize_block(else_block);
+                }
+            }
+            StmtKind::Assign(ref mut ident, ref mut expr) => {
+                ident.span = DUMMY_SPAN;
+                normalize_expr(expr);
+            }
+            StmtKind::BlockStmt(ref mut block) => normalize_block(block),
+            StmtKind::Call(ref mut ident, ref mut args) => {
+                ident.span = DUMMY_SPAN;
+                for arg in args {
+                    normalize_expr(arg);
+                }
+            }
+        }
+    }
+
+    fn normalize_expr(expr: &mut ast::Expr) {
+        use ast::ExprKind;
+        let kind = mem::replace(&mut expr.kind, ExprKind::Lit(ast::Lit::BoolLit(false)));
+        expr.kind = match kind {
+            ExprKind::Paren(inner) => {
+                *expr = *inner;
+                return normalize_expr(expr);
+            }
+            kind => kind,
+        };
+        expr.span = DUMMY_SPAN;
+        match expr.kind {
+            ExprKind::BinaryOp(_, ref mut lhs, ref mut rhs) => {
+                normalize_expr(lhs);
+                normalize_expr(rhs);
+            }
+            ExprKind::UnaryOp(_, ref mut operand) | ExprKind::Cast(_, ref mut operand) => {
+                normalize_expr(operand)
+            }
+            ExprKind::Lit(_) | ExprKind::Paren(_) => {}
+            ExprKind::Ident(ref mut ident) => ident.span = DUMMY_SPAN,
+            ExprKind::Call(ref mut ident, ref mut args) => {
+                ident.span = DUMMY_SPAN;
+                for arg in args {
+                    normalize_expr(arg);
+                }
+            }
+            ExprKind::Formatted(ref mut value, ref mut width, ref mut prec) => {
+                normalize_expr(value);
+                normalize_expr(width);
+                if let Some(ref mut prec) = *prec {
+                    normalize_expr(prec);
+                }
+            }
+        }
+    }
+
+    /// Checks that formatting a program keeps its meaning, and that the
+    /// formatted program is left as it is.
+    fn assert_round_trip(src: &str) -> String {
+        let formatted = format(src);
+        let (program, ident_table) = parse(src, ast::IdentTable::default());
+        let (formatted_program, _) = parse(&formatted, ident_table);
+        assert_eq!(program, formatted_program);
+        assert_eq!(formatted, format(&formatted));
+        formatted
+    }
+
+    #[test]
+    fn test_format_program() {
+        let formatted = assert_round_trip(
+            "program   p; uses a,b; let x: int; const k: int = (1+2)*3;\n\
+             x = k; if x == 1 { writeln(x:4); } else if x == 2 { x = 1; x = 2; }\n\
+             else { if x > 3 { x = 1; } }\n\
+             while x<10 {x = x + 1; if x mod 2 == 0 { continue; } }\n\
+             case x of 1, 2: { writeln(\"a\"); } 3..5: { x = 0; } else { writeln(\"z\"); }\n\
+             repeat { x = x - 1; } until x <= 0; for x = 3 downto 1 { writeln(float(x) / 2.5e1); }\n\
+             { x = len(\"ab\"); }",
+        );
+        assert_eq!(
+            "program p;\n\
+             \n\
+             uses a, b;\n\
+             \n\
+             let x: int;\n\
+             const k: int = (1 + 2) * 3;\n\
+             \n\
+             x = k;\n\
+             if x == 1 {\n    writeln(x:4);\n\
+             } else if x == 2 {\n    x = 1;\n    x = 2;\n\
+             } else {\n    if x > 3 {\n        x = 1;\n    }\n}\n\
+             while x < 10 {\n    x = x + 1;\n    if x mod 2 == 0 {\n        continue;\n    }\n}\n\
+             case x of\n    1, 2: {\n        writeln(\"a\");\n    }\n    3..5: {\n        x = 0;\n    }\n\
+             \x20   else {\n        writeln(\"z\");\n    }\n\
+             repeat {\n    x = x - 1;\n} until x <= 0;\n\
+             for x = 3 downto 1 {\n    writeln(float(x) / 2.5e1);\n}\n\
+             {\n    x = len(\"ab\");\n}\n",
+            formatted
+        );
+    }
+
+    #[test]
+    fn test_format_minimal_parens() {
+        let formatted = assert_round_trip(
+            "program p; let x: int; let b: bool;\n\
+             x = ((1 + 2)) + 3;\n\
+             x = 1 + (2 + 3);\n\
+             x = (1 - 2) - (3 - 4);\n\
+             x = 2 ** (3 ** 2);\n\
+             x = (2 ** 3) ** 2;\n\
+             x = (-2) ** 2;\n\
+             x = -(2 ** 2);\n\
+             x = 2 ** -x;\n\
+             x = -(-x);\n\
+             x = (1 + 2) * -(3);\n\
+             b = (x < 1) & (x > 0 | b);",
+        );
+        assert_eq!(
+            "program p;\n\
+             \n\
+             let x: int;\n\
+             let b: bool;\n\
+             \n\
+             x = 1 + 2 + 3;\n\
+             x = 1 + (2 + 3);\n\
+             x = 1 - 2 - (3 - 4);\n\
+             x = 2 ** 3 ** 2;\n\
+             x = (2 ** 3) ** 2;\n\
+             x = (-2) ** 2;\n\
+             x = -2 ** 2;\n\
+             x = 2 ** -x;\n\
+             x = -(-x);\n\
+             x = (1 + 2) * -3;\n\
+             b = x < 1 & (x > 0 | b);\n",
+            formatted
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let formatted = assert_round_trip(
+            "// Header.\n\
+             program p; // Trailing.\n\
+             let x: int;\n\
+             \n\
+             \n\
+             \n\
+             // Before a statement.\n\
+             x = 1;\n\
+             while x < 3 {\n\
+             \x20 // Inside a block.\n\
+             \x20 x = x + 1; // After a statement.\n\
+             \x20 // Before the end of a block.\n\
+             }\n\
+             // At the end.",
+        );
+        assert_eq!(
+            "// Header.\n\
+             program p; // Trailing.\n\
+             \n\
+             let x: int;\n\
+             \n\
+             // Before a statement.\n\
+             x = 1;\n\
+             while x < 3 {\n\
+             \x20   // Inside a block.\n\
+             \x20   x = x + 1; // After a statement.\n\
+             \x20   // Before the end of a block.\n\
+             }\n\
+             // At the end.\n",
+            formatted
+        );
+    }
+
+    #[test]
+    fn test_format_unit() {
+        assert_eq!(
+            "unit u;\n\nuses v;\n\nconst n: int = 1;\n\nlet x: float;\n",
+            format("unit u; uses v;\nconst n: int = 1;\n\nlet x: float;")
+        );
+    }
+}
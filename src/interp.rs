@@ -0,0 +1,1348 @@
+use ast;
+use builtins::{self, Builtin, Native, Natives};
+use errors::{Limit, Op, RuntimeError, RuntimeErrorKind};
+use source_map::{Span, DUMMY_SPAN};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::rc::Rc;
+use std::result;
+use std::time::{Duration, Instant};
+
+type Result<T> = result::Result<T, RuntimeError>;
+/// The result of an operation which doesn't know the code it's run for, so
+/// its caller gives its error a span.
+type OpResult<T> = result::Result<T, RuntimeErrorKind>;
+
+/// A value computed while running a program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Value {
+    /// The value a variable of type `ty` holds before being assigned.
+    fn default_of(ty: &ast::Ty) -> Value {
+        match *ty {
+            ast::Ty::BoolTy => Value::Bool(false),
+            ast::Ty::IntTy => Value::Int(0),
+            ast::Ty::FloatTy => Value::Float(0.0),
+            ast::Ty::StrTy => Value::Str(String::new()),
+        }
+    }
+
+    fn ty(&self) -> ast::Ty {
+        match *self {
+            Value::Bool(_) => ast::Ty::BoolTy,
+            Value::Int(_) => ast::Ty::IntTy,
+            Value::Float(_) => ast::Ty::FloatTy,
+            Value::Str(_) => ast::Ty::StrTy,
+        }
+    }
+
+    /// The number of bytes a value takes beyond its own, which only
+    /// strings do.
+    fn size(&self) -> usize {
+        match *self {
+            Value::Str(ref value) => value.len(),
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            // Floats always show a fractional part, e.g. `3.0`.
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Str(ref value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Limits on the resources a program may use while running, for programs
+/// which can't be trusted to finish. Going over one stops the program with
+/// a `LimitExceeded` runtime error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// How many statements may be executed, counting every statement of a
+    /// loop, and the loop itself, each time around.
+    pub max_steps: Option<u64>,
+    /// How deeply blocks and calls may nest.
+    pub max_depth: Option<usize>,
+    /// How many bytes the strings held by variables may take altogether,
+    /// along with any string being built.
+    pub max_memory: Option<usize>,
+    /// How long a program may run, from the first statement executed.
+    pub timeout: Option<Duration>,
+}
+
+/// How control leaves a statement.
+#[derive(Debug, PartialEq)]
+enum Flow {
+    Next,
+    Break,
+    Continue,
+}
+
+/// The input read by `read` and `readln`, consumed a word at a time.
+struct Input<'a> {
+    reader: Box<dyn BufRead + 'a>,
+    /// What's left of the current line, if any has been read.
+    rest_of_line: Option<String>,
+}
+
+impl<'a> Input<'a> {
+    fn read_line(&mut self) -> OpResult<Option<String>> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(line)),
+            Err(err) => Err(io_error(&err)),
+        }
+    }
+
+    /// Reads the next whitespace separated word, going through as many
+    /// lines as needed.
+    fn read_word(&mut self) -> OpResult<String> {
+        loop {
+            if let Some(ref mut rest_of_line) = self.rest_of_line {
+                let line = rest_of_line.trim_start();
+                if !line.is_empty() {
+                    let end = line.find(char::is_whitespace).unwrap_or(line.len());
+                    let word = line[..end].to_owned();
+                    *rest_of_line = line[end..].to_owned();
+                    return Ok(word);
+                }
+            }
+            self.rest_of_line = Some(
+                self.read_line()?
+                    .ok_or(RuntimeErrorKind::UnexpectedEndOfInput)?,
+            );
+        }
+    }
+
+    /// Reads what's left of the current line, without leading whitespace
+    /// and the line break.
+    fn read_rest_of_line(&mut self) -> OpResult<String> {
+        let line = match self.rest_of_line.take() {
+            Some(rest_of_line) => rest_of_line,
+            None => self
+                .read_line()?
+                .ok_or(RuntimeErrorKind::UnexpectedEndOfInput)?,
+        };
+        self.rest_of_line = Some(String::new());
+        Ok(line
+            .trim_start()
+            .trim_end_matches(&['\n', '\r'][..])
+            .to_owned())
+    }
+
+    fn skip_line(&mut self) -> OpResult<()> {
+        if self.rest_of_line.take().is_none() {
+            self.read_line()?;
+        }
+        Ok(())
+    }
+}
+
+/// A tree-walking interpreter, which executes a program straight from its
+/// AST.
+pub struct Interpreter<'a> {
+    builtins: HashMap<ast::Name, Builtin>,
+    natives: HashMap<ast::Name, Rc<Native>>,
+    vars: HashMap<ast::Name, Value>,
+    input: Input<'a>,
+    output: Box<dyn Write + 'a>,
+    limits: Limits,
+    /// The number of statements executed so far.
+    steps: u64,
+    /// How deeply the blocks and calls being executed nest.
+    depth: Cell<usize>,
+    /// The span of the innermost statement being executed, which limits
+    /// going over are reported at.
+    stmt_span: Span,
+    /// The number of bytes taken by the strings of `vars`.
+    memory: usize,
+    /// When the program has to be stopped, once it has started running
+    /// with a timeout.
+    deadline: Cell<Option<Instant>>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Creates an interpreter for a program with the given identifiers,
+    /// which reads from `input` and writes to `output`.
+    pub fn new<R, W>(ident_table: &ast::IdentTable, input: R, output: W) -> Interpreter<'a>
+    where
+        R: BufRead + 'a,
+        W: Write + 'a,
+    {
+        Interpreter::with_natives(ident_table, &Natives::new(), input, output)
+    }
+
+    /// Creates an interpreter which calls the functions defined by the
+    /// host, besides the builtins.
+    pub fn with_natives<R, W>(
+        ident_table: &ast::IdentTable,
+        natives: &Natives,
+        input: R,
+        output: W,
+    ) -> Interpreter<'a>
+    where
+        R: BufRead + 'a,
+        W: Write + 'a,
+    {
+        Interpreter {
+            builtins: builtins::resolve(ident_table),
+            natives: natives.resolve(ident_table),
+            vars: HashMap::new(),
+            input: Input {
+                reader: Box::new(input),
+                rest_of_line: None,
+            },
+            output: Box::new(output),
+            limits: Limits::default(),
+            steps: 0,
+            depth: Cell::new(0),
+            stmt_span: DUMMY_SPAN,
+            memory: 0,
+            deadline: Cell::new(None),
+        }
+    }
+
+    /// Sets the limits on the resources the code run may use.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the current value of a variable, if it has been declared.
+    pub fn var(&self, name: ast::Name) -> Option<&Value> {
+        self.vars.get(&name)
+    }
+
+    /// Declares the constants and variables of a unit, so they are
+    /// available to the units and program run afterwards.
+    pub fn init_unit(&mut self, unit: &ast::Unit) -> Result<()> {
+        self.init_decls(&unit.consts, &unit.decls)
+    }
+
+    /// Runs a whole program, stopping at the first runtime error.
+    pub fn run_program(&mut self, program: &ast::Program) -> Result<()> {
+        self.init_decls(&program.consts, &program.decls)?;
+        self.exec_stmts(&program.stmts)
+    }
+
+    /// Declares the constants and variables typed into the REPL, replacing
+    /// those of the same names. If the value of a constant can't be
+    /// computed, nothing is declared.
+    pub fn declare(&mut self, items: &ast::Items) -> Result<()> {
+        let (vars, memory) = (self.vars.clone(), self.memory);
+        let result = self.init_decls(&items.consts, &items.decls);
+        if result.is_err() {
+            self.vars = vars;
+            self.memory = memory;
+        }
+        result
+    }
+
+    /// Runs statements one after another, stopping at the first runtime
+    /// error.
+    pub fn exec_stmts(&mut self, stmts: &[ast::Stmt]) -> Result<()> {
+        for stmt in stmts {
+            self.exec_stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets every declared constant and variable.
+    pub fn clear_vars(&mut self) {
+        self.vars.clear();
+        self.memory = 0;
+    }
+
+    fn init_decls(&mut self, consts: &[ast::ConstDecl], decls: &[ast::Decl]) -> Result<()> {
+        for const_decl in consts {
+            let value = promote(self.eval_expr(&const_decl.expr)?, const_decl.ty);
+            self.set_var(const_decl.ident.name, value, const_decl.ident.span)?;
+        }
+
+        for decl in decls {
+            self.set_var(
+                decl.ident.name,
+                Value::default_of(&decl.ty),
+                decl.ident.span,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Assigns a value to a variable, unless its strings would take more
+    /// memory than allowed.
+    fn set_var(&mut self, name: ast::Name, value: Value, span: Span) -> Result<()> {
+        let old_size = self.vars.get(&name).map_or(0, Value::size);
+        let memory = self.memory - old_size + value.size();
+        if let Some(max_memory) = self.limits.max_memory {
+            if memory > max_memory {
+                let kind = RuntimeErrorKind::LimitExceeded(Limit::Memory(max_memory));
+                return Err(RuntimeError::new(kind, span));
+            }
+        }
+        self.memory = memory;
+        self.vars.insert(name, value);
+        Ok(())
+    }
+
+    /// Makes room for a string of `size` bytes being built, unless it
+    /// wouldn't fit in the memory left by the strings of the variables.
+    fn alloc(&self, size: usize) -> Result<()> {
+        match self.limits.max_memory {
+            Some(max_memory) if self.memory + size > max_memory => Err(RuntimeError::new(
+                RuntimeErrorKind::LimitExceeded(Limit::Memory(max_memory)),
+                self.stmt_span,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Enters a nested block or call, unless it nests deeper than allowed.
+    fn enter(&self) -> Result<()> {
+        let depth = self.depth.get() + 1;
+        match self.limits.max_depth {
+            Some(max_depth) if depth > max_depth => Err(RuntimeError::new(
+                RuntimeErrorKind::LimitExceeded(Limit::Depth(max_depth)),
+                self.stmt_span,
+            )),
+            _ => {
+                self.depth.set(depth);
+                Ok(())
+            }
+        }
+    }
+
+    fn leave(&self) {
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    /// Counts a statement about to be executed, or another time around a
+    /// loop, unless the program has run as many steps or as long as
+    /// allowed.
+    fn step(&mut self) -> Result<()> {
+        self.steps += 1;
+        if let Some(max_steps) = self.limits.max_steps {
+            if self.steps > max_steps {
+                let kind = RuntimeErrorKind::LimitExceeded(Limit::Steps(max_steps));
+                return Err(RuntimeError::new(kind, self.stmt_span));
+            }
+        }
+        self.check_time()
+    }
+
+    /// Fails once the program has run as long as allowed.
+    fn check_time(&self) -> Result<()> {
+        if let Some(timeout) = self.limits.timeout {
+            let deadline = match self.deadline.get() {
+                Some(deadline) => deadline,
+                None => {
+                    let deadline = Instant::now() + timeout;
+                    self.deadline.set(Some(deadline));
+                    deadline
+                }
+            };
+            if Instant::now() >= deadline {
+                let kind = RuntimeErrorKind::LimitExceeded(Limit::Time(timeout));
+                return Err(RuntimeError::new(kind, self.stmt_span));
+            }
+        }
+        Ok(())
+    }
+
+    fn exec_block(&mut self, block: &ast::Block) -> Result<Flow> {
+        self.enter()?;
+        let result = self.exec_block_stmts(block);
+        self.leave();
+        result
+    }
+
+    fn exec_block_stmts(&mut self, block: &ast::Block) -> Result<Flow> {
+        for stmt in &block.stmts {
+            let flow = self.exec_stmt(stmt)?;
+            if flow != Flow::Next {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn exec_stmt(&mut self, stmt: &ast::Stmt) -> Result<Flow> {
+        let outer_span = mem::replace(&mut self.stmt_span, stmt.span);
+        let result = self.step().and_then(|_| self.exec_stmt_kind(stmt));
+        self.stmt_span = outer_span;
+        result
+    }
+
+    fn exec_stmt_kind(&mut self, stmt: &ast::Stmt) -> Result<Flow> {
+        use ast::StmtKind;
+        match stmt.kind {
+            StmtKind::While(ref cond_expr, ref block) => {
+                while self.eval_cond(cond_expr)? {
+                    self.step()?;
+                    if self.exec_block(block)? == Flow::Break {
+                        break;
+                    }
+                }
+            }
+            StmtKind::For(ref ident, ref start_expr, ref dir, ref end_expr, ref block) => {
+                let start = self.eval_int(start_expr)?;
+                let end = self.eval_int(end_expr)?;
+                let (step, in_range): (i64, fn(i64, i64) -> bool) = match *dir {
+                    ast::ForDir::To => (1, |i, end| i <= end),
+                    ast::ForDir::Downto => (-1, |i, end| i >= end),
+                };
+                let mut i = start;
+                while in_range(i, end) {
+                    self.step()?;
+                    self.set_var(ident.name, Value::Int(i), stmt.span)?;
+                    if self.exec_block(block)? == Flow::Break {
+                        break;
+                    }
+                    match i.checked_add(step) {
+                        Some(next) => i = next,
+                        None => break,
+                    }
+                }
+            }
+            StmtKind::Repeat(ref block, ref cond_expr) => loop {
+                self.step()?;
+                if self.exec_block(block)? == Flow::Break || self.eval_cond(cond_expr)? {
+                    break;
+                }
+            },
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+            StmtKind::If(ref cond_expr, ref then_block, ref else_block) => {
+                if self.eval_cond(cond_expr)? {
+                    return self.exec_block(then_block);
+                } else if let Some(ref else_block) = *else_block {
+                    return self.exec_block(else_block);
+                }
+            }
+            StmtKind::Case(ref expr, ref arms, ref else_block) => {
+                let value = self.eval_expr(expr)?;
+                let arm = arms
+                    .iter()
+                    .find(|arm| arm.pats.iter().any(|pat| pat_matches(pat, &value)));
+                if let Some(arm) = arm {
+                    return self.exec_block(&arm.block);
+                } else if let Some(ref else_block) = *else_block {
+                    return self.exec_block(else_block);
+                }
+            }
+            StmtKind::Assign(ref ident, ref expr) => {
+                let value = promote(self.eval_expr(expr)?, self.vars[&ident.name].ty());
+                self.set_var(ident.name, value, stmt.span)?;
+            }
+            StmtKind::BlockStmt(ref block) => return self.exec_block(block),
+            StmtKind::Call(ref func, ref args) => match self.builtins.get(&func.name) {
+                Some(Builtin::Write) => self.exec_write(args, false, stmt.span)?,
+                Some(Builtin::Writeln) => self.exec_write(args, true, stmt.span)?,
+                Some(Builtin::Read) => self.exec_read(args, false, stmt.span)?,
+                Some(Builtin::Readln) => self.exec_read(args, true, stmt.span)?,
+                _ => {
+                    self.call(func, args, stmt.span)?;
+                }
+            },
+        }
+        Ok(Flow::Next)
+    }
+
+    fn exec_write(&mut self, args: &[ast::Expr], line_break: bool, span: Span) -> Result<()> {
+        let mut text = String::new();
+        for arg in args {
+            if let ast::ExprKind::Formatted(ref expr, ref width, ref prec) = arg.kind {
+                let value = self.eval_expr(expr)?;
+                let width = self.eval_int(width)?;
+                let prec = match *prec {
+                    Some(ref prec) => Some(self.eval_int(prec)?),
+                    None => None,
+                };
+                // The padding is known before it's allocated.
+                self.alloc(text.len() + width.max(0) as usize)?;
+                text.push_str(&format_value(&value, width, prec));
+            } else {
+                text.push_str(&self.eval_expr(arg)?.to_string());
+            }
+            self.alloc(text.len())?;
+        }
+        if line_break {
+            text.push('\n');
+        }
+        self.output
+            .write_all(text.as_bytes())
+            .and_then(|_| self.output.flush())
+            .map_err(|err| RuntimeError::new(io_error(&err), span))
+    }
+
+    /// Reads a value into each variable of `args`, parsed according to the
+    /// variable's type.
+    fn exec_read(&mut self, args: &[ast::Expr], whole_line: bool, span: Span) -> Result<()> {
+        for (i, arg) in args.iter().enumerate() {
+            let ident = match arg.kind {
+                ast::ExprKind::Ident(ref ident) => ident,
+                _ => panic!("has to be a variable!"),
+            };
+            let ty = self.vars[&ident.name].ty();
+            let is_last = i + 1 == args.len();
+            let text = if whole_line && is_last && ty == ast::Ty::StrTy {
+                self.input.read_rest_of_line()
+            } else {
+                self.input.read_word()
+            };
+            let value = text
+                .and_then(|text| parse_input(text, ty))
+                .map_err(|kind| RuntimeError::new(kind, arg.span))?;
+            self.set_var(ident.name, value, span)?;
+        }
+        if whole_line {
+            self.input
+                .skip_line()
+                .map_err(|kind| RuntimeError::new(kind, span))?;
+        }
+        Ok(())
+    }
+
+    fn eval_cond(&self, expr: &ast::Expr) -> Result<bool> {
+        match self.eval_expr(expr)? {
+            Value::Bool(value) => Ok(value),
+            _ => panic!("has to be a boolean!"),
+        }
+    }
+
+    fn eval_int(&self, expr: &ast::Expr) -> Result<i64> {
+        match self.eval_expr(expr)? {
+            Value::Int(value) => Ok(value),
+            _ => panic!("has to be an integer!"),
+        }
+    }
+
+    /// Evaluates an expression. The expression has to be well-typed.
+    pub fn eval_expr(&self, expr: &ast::Expr) -> Result<Value> {
+        use ast::ExprKind;
+        self.check_time()?;
+        match expr.kind {
+            ExprKind::BinaryOp(op, ref lhs_expr, ref rhs_expr) => {
+                let lhs = self.eval_expr(lhs_expr)?;
+                let rhs = self.eval_expr(rhs_expr)?;
+                if op == ast::BinOp::Add {
+                    // Concatenating strings builds a new one.
+                    self.alloc(lhs.size() + rhs.size())?;
+                }
+                eval_binary_op(op, lhs, rhs).map_err(|kind| RuntimeError::new(kind, expr.span))
+            }
+            ExprKind::UnaryOp(op, ref operand) => {
+                let value = match (op, &operand.kind) {
+                    // The literal of `-9223372036854775808` doesn't fit in an
+                    // `int`, but its negation does.
+                    (ast::UnOp::Neg, &ExprKind::Lit(ast::Lit::IntLit(value))) => {
+                        return Ok(Value::Int((value as i64).wrapping_neg()))
+                    }
+                    _ => self.eval_expr(operand)?,
+                };
+                eval_unary_op(op, value).map_err(|kind| RuntimeError::new(kind, expr.span))
+            }
+            ExprKind::Lit(ref lit) => Ok(match *lit {
+                ast::Lit::IntLit(value) => Value::Int(value as i64),
+                ast::Lit::FloatLit(value) => Value::Float(value),
+                ast::Lit::StrLit(ref value) => Value::Str(value.clone()),
+                ast::Lit::BoolLit(value) => Value::Bool(value),
+            }),
+            ExprKind::Ident(ref ident) => Ok(self.vars[&ident.name].clone()),
+            ExprKind::Paren(ref expr) => self.eval_expr(expr),
+            ExprKind::Cast(ty, ref operand) => {
+                let value = cast_value(ty, self.eval_expr(operand)?)
+                    .map_err(|kind| RuntimeError::new(kind, expr.span))?;
+                self.alloc(value.size())?;
+                Ok(value)
+            }
+            ExprKind::Call(ref func, ref args) => self
+                .call(func, args, expr.span)
+                .map(|value| value.expect("has to be a function!")),
+            ExprKind::Formatted(..) => panic!("has to be an argument of `write`!"),
+        }
+    }
+
+    /// Calls a builtin or native, returning its value unless it's a
+    /// procedure. Errors of the callee are reported at the `span` of the
+    /// call.
+    fn call(&self, func: &ast::Ident, args: &[ast::Expr], span: Span) -> Result<Option<Value>> {
+        self.enter()?;
+        let result = self.call_entered(func, args, span);
+        self.leave();
+        result
+    }
+
+    fn call_entered(
+        &self,
+        func: &ast::Ident,
+        args: &[ast::Expr],
+        span: Span,
+    ) -> Result<Option<Value>> {
+        let args = args
+            .iter()
+            .map(|arg| self.eval_expr(arg))
+            .collect::<Result<Vec<_>>>()?;
+        let result = match self.natives.get(&func.name) {
+            Some(native) => call_native(native, args),
+            None => {
+                let builtin = *self.builtins.get(&func.name).expect("has to be a builtin!");
+                call_builtin(builtin, &args).map(Some)
+            }
+        };
+        let value = result.map_err(|kind| RuntimeError::new(kind, span))?;
+        self.alloc(value.as_ref().map_or(0, Value::size))?;
+        Ok(value)
+    }
+}
+
+/// Converts a value to the type of the variable it's assigned to, which
+/// only changes integers assigned to floats.
+fn promote(value: Value, ty: ast::Ty) -> Value {
+    match (value, ty) {
+        (Value::Int(value), ast::Ty::FloatTy) => Value::Float(value as f64),
+        (value, _) => value,
+    }
+}
+
+/// Formats a value right-aligned to `width` characters. Floats are shown
+/// with `prec` digits after the decimal point, if given.
+fn format_value(value: &Value, width: i64, prec: Option<i64>) -> String {
+    let width = width.max(0) as usize;
+    match (value, prec) {
+        (&Value::Float(value), Some(prec)) => {
+            format!(
+                "{:>width$.prec$}",
+                value,
+                width = width,
+                prec = prec.max(0) as usize
+            )
+        }
+        _ => format!("{:>width$}", value.to_string(), width = width),
+    }
+}
+
+fn parse_input(text: String, ty: ast::Ty) -> OpResult<Value> {
+    let value = match ty {
+        ast::Ty::BoolTy => text.parse().ok().map(Value::Bool),
+        ast::Ty::IntTy => text.parse().ok().map(Value::Int),
+        ast::Ty::FloatTy => text.parse().ok().map(Value::Float),
+        ast::Ty::StrTy => return Ok(Value::Str(text)),
+    };
+    value.ok_or(RuntimeErrorKind::InvalidInput { text, ty })
+}
+
+fn io_error(err: &io::Error) -> RuntimeErrorKind {
+    RuntimeErrorKind::IoError {
+        message: err.to_string(),
+    }
+}
+
+/// Calls a builtin with already evaluated arguments, which have to match
+/// its signature.
+fn call_builtin(builtin: Builtin, args: &[Value]) -> OpResult<Value> {
+    let value = match builtin {
+        Builtin::Len => Value::Int(str_arg(&args[0]).chars().count() as i64),
+        Builtin::Substr => {
+            let s = str_arg(&args[0]);
+            let start = int_arg(&args[1]);
+            let len = s.chars().count();
+            if start < 0 || start as usize > len {
+                return Err(RuntimeErrorKind::IndexOutOfBounds { index: start, len });
+            }
+            let count = int_arg(&args[2]).max(0) as usize;
+            Value::Str(s.chars().skip(start as usize).take(count).collect())
+        }
+        Builtin::CharAt => {
+            let s = str_arg(&args[0]);
+            let index = int_arg(&args[1]);
+            let len = s.chars().count();
+            if index < 0 || index as usize >= len {
+                return Err(RuntimeErrorKind::IndexOutOfBounds { index, len });
+            }
+            Value::Str(s.chars().nth(index as usize).unwrap().to_string())
+        }
+        Builtin::Find => {
+            let s = str_arg(&args[0]);
+            let index = s
+                .find(str_arg(&args[1]))
+                .map_or(-1, |byte_index| s[..byte_index].chars().count() as i64);
+            Value::Int(index)
+        }
+        Builtin::Upper => Value::Str(str_arg(&args[0]).to_uppercase()),
+        Builtin::Lower => Value::Str(str_arg(&args[0]).to_lowercase()),
+        Builtin::Write | Builtin::Writeln | Builtin::Read | Builtin::Readln => {
+            panic!("has to be a function!")
+        }
+    };
+    Ok(value)
+}
+
+/// Calls a native with already evaluated arguments, checking that it
+/// returns a value of its return type.
+fn call_native(native: &Native, args: Vec<Value>) -> OpResult<Option<Value>> {
+    let args = args
+        .into_iter()
+        .zip(&native.params)
+        .map(|(arg, &ty)| promote(arg, ty))
+        .collect::<Vec<_>>();
+    let failed = |message| RuntimeErrorKind::NativeFunctionFailed {
+        name: native.name.clone(),
+        message,
+    };
+    match (native.call(&args).map_err(failed)?, native.ret_ty) {
+        (None, None) => Ok(None),
+        (Some(value), Some(ty)) => {
+            let value = promote(value, ty);
+            if value.ty() == ty {
+                Ok(Some(value))
+            } else {
+                Err(failed(format!(
+                    "returned a value of type {} rather than {}",
+                    value.ty(),
+                    ty
+                )))
+            }
+        }
+        (Some(value), None) => Err(failed(format!(
+            "returned a value of type {} from a procedure",
+            value.ty()
+        ))),
+        (None, Some(ty)) => Err(failed(format!(
+            "returned nothing rather than a value of type {}",
+            ty
+        ))),
+    }
+}
+
+fn str_arg(value: &Value) -> &str {
+    match *value {
+        Value::Str(ref value) => value,
+        _ => panic!("has to be a string!"),
+    }
+}
+
+fn int_arg(value: &Value) -> i64 {
+    match *value {
+        Value::Int(value) => value,
+        _ => panic!("has to be an integer!"),
+    }
+}
+
+fn eval_unary_op(op: ast::UnOp, value: Value) -> OpResult<Value> {
+    let value = match (op, value) {
+        (ast::UnOp::Neg, Value::Int(value)) => {
+            let value = value
+                .checked_neg()
+                .ok_or(RuntimeErrorKind::Overflow { op: Op::Unary(op) })?;
+            Value::Int(value)
+        }
+        (ast::UnOp::Neg, Value::Float(value)) => Value::Float(-value),
+        (ast::UnOp::Not, Value::Bool(value)) => Value::Bool(!value),
+        _ => panic!("has to be well-typed!"),
+    };
+    Ok(value)
+}
+
+/// Evaluates a binary operator. Integers are promoted to floats when mixed
+/// with floats, and `/` always divides floats. Strings are concatenated by
+/// `+` and compared lexicographically.
+fn eval_binary_op(op: ast::BinOp, lhs: Value, rhs: Value) -> OpResult<Value> {
+    use ast::BinOp;
+    match (lhs, rhs) {
+        (Value::Int(lhs), Value::Int(rhs)) => match op {
+            BinOp::Div => eval_float_op(op, lhs as f64, rhs as f64),
+            _ => eval_int_op(op, lhs, rhs),
+        },
+        (Value::Int(lhs), Value::Float(rhs)) => eval_float_op(op, lhs as f64, rhs),
+        (Value::Float(lhs), Value::Int(rhs)) => eval_float_op(op, lhs, rhs as f64),
+        (Value::Float(lhs), Value::Float(rhs)) => eval_float_op(op, lhs, rhs),
+        (Value::Bool(lhs), Value::Bool(rhs)) => Ok(Value::Bool(match op {
+            BinOp::BitAnd => lhs & rhs,
+            BinOp::BitOr => lhs | rhs,
+            BinOp::BitXor => lhs ^ rhs,
+            BinOp::Eq => lhs == rhs,
+            BinOp::Ne => lhs != rhs,
+            _ => panic!("has to be a boolean operator!"),
+        })),
+        (Value::Str(lhs), Value::Str(rhs)) => Ok(match op {
+            BinOp::Add => Value::Str(lhs + &rhs),
+            BinOp::Eq => Value::Bool(lhs == rhs),
+            BinOp::Ne => Value::Bool(lhs != rhs),
+            BinOp::Lt => Value::Bool(lhs < rhs),
+            BinOp::Le => Value::Bool(lhs <= rhs),
+            BinOp::Gt => Value::Bool(lhs > rhs),
+            BinOp::Ge => Value::Bool(lhs >= rhs),
+            _ => panic!("has to be a string operator!"),
+        }),
+        _ => panic!("has to be well-typed!"),
+    }
+}
+
+/// The magnitude of `i64::MIN`, which bounds the floats that convert to an
+/// `int`.
+const INT_RANGE_END: f64 = 9_223_372_036_854_775_808.0;
+
+/// Converts a value to another type. Floats are truncated towards zero when
+/// converted to integers.
+fn cast_value(ty: ast::Ty, value: Value) -> OpResult<Value> {
+    use ast::Ty;
+    let value = match (ty, value) {
+        (Ty::IntTy, Value::Int(value)) => Value::Int(value),
+        // Every float in this range, which excludes NaN, truncates to an
+        // `int`.
+        (Ty::IntTy, Value::Float(value)) if (-INT_RANGE_END..INT_RANGE_END).contains(&value) => {
+            Value::Int(value.trunc() as i64)
+        }
+        (Ty::IntTy, Value::Float(_)) => {
+            return Err(RuntimeErrorKind::Overflow { op: Op::Cast(ty) })
+        }
+        (Ty::IntTy, Value::Str(text)) => match text.trim().parse() {
+            Ok(value) => Value::Int(value),
+            Err(_) => return Err(RuntimeErrorKind::InvalidConversion { text, ty }),
+        },
+        (Ty::FloatTy, Value::Int(value)) => Value::Float(value as f64),
+        (Ty::FloatTy, Value::Float(value)) => Value::Float(value),
+        (Ty::FloatTy, Value::Str(text)) => match text.trim().parse() {
+            Ok(value) => Value::Float(value),
+            Err(_) => return Err(RuntimeErrorKind::InvalidConversion { text, ty }),
+        },
+        (Ty::StrTy, value) => Value::Str(value.to_string()),
+        _ => panic!("has to be a valid conversion!"),
+    };
+    Ok(value)
+}
+
+/// Evaluates an operator on integers, failing rather than wrapping around
+/// when the result doesn't fit.
+fn eval_int_op(op: ast::BinOp, lhs: i64, rhs: i64) -> OpResult<Value> {
+    use ast::BinOp;
+    let overflow = RuntimeErrorKind::Overflow { op: Op::Binary(op) };
+    let value = match op {
+        BinOp::Add => lhs.checked_add(rhs).ok_or(overflow)?,
+        BinOp::Sub => lhs.checked_sub(rhs).ok_or(overflow)?,
+        BinOp::Mult => lhs.checked_mul(rhs).ok_or(overflow)?,
+        BinOp::IntDiv | BinOp::Mod if rhs == 0 => return Err(RuntimeErrorKind::DivisionByZero),
+        BinOp::IntDiv => lhs.checked_div(rhs).ok_or(overflow)?,
+        // The remainder of `i64::MIN` by -1 is 0, even though the quotient
+        // overflows.
+        BinOp::Mod => lhs.wrapping_rem(rhs),
+        BinOp::Pow => int_pow(lhs, rhs)?,
+        BinOp::BitAnd => lhs & rhs,
+        BinOp::BitOr => lhs | rhs,
+        BinOp::BitXor => lhs ^ rhs,
+        BinOp::Shl => u32::try_from(rhs)
+            .ok()
+            .and_then(|rhs| lhs.checked_shl(rhs))
+            .ok_or(overflow)?,
+        BinOp::Shr => u32::try_from(rhs)
+            .ok()
+            .and_then(|rhs| lhs.checked_shr(rhs))
+            .ok_or(overflow)?,
+        BinOp::Eq => return Ok(Value::Bool(lhs == rhs)),
+        BinOp::Ne => return Ok(Value::Bool(lhs != rhs)),
+        BinOp::Lt => return Ok(Value::Bool(lhs < rhs)),
+        BinOp::Le => return Ok(Value::Bool(lhs <= rhs)),
+        BinOp::Gt => return Ok(Value::Bool(lhs > rhs)),
+        BinOp::Ge => return Ok(Value::Bool(lhs >= rhs)),
+        BinOp::Div => panic!("has to be an integer operator!"),
+    };
+    Ok(Value::Int(value))
+}
+
+/// Raises `base` to `exp`, unless the power overflows. A negative exponent
+/// is the integer division of one by the positive power, so it is zero
+/// unless `base` is -1 or 1.
+fn int_pow(base: i64, exp: i64) -> OpResult<i64> {
+    let power = match base {
+        0 if exp < 0 => return Err(RuntimeErrorKind::DivisionByZero),
+        0 if exp > 0 => Some(0),
+        1 => Some(1),
+        -1 if exp % 2 == 0 => Some(1),
+        -1 => Some(-1),
+        _ if exp < 0 => Some(0),
+        _ if exp > i64::from(u32::MAX) => None,
+        _ => base.checked_pow(exp as u32),
+    };
+    power.ok_or(RuntimeErrorKind::Overflow {
+        op: Op::Binary(ast::BinOp::Pow),
+    })
+}
+
+fn eval_float_op(op: ast::BinOp, lhs: f64, rhs: f64) -> OpResult<Value> {
+    use ast::BinOp;
+    let value = match op {
+        BinOp::Add => lhs + rhs,
+        BinOp::Sub => lhs - rhs,
+        BinOp::Mult => lhs * rhs,
+        BinOp::Div if rhs == 0.0 => return Err(RuntimeErrorKind::DivisionByZero),
+        BinOp::Div => lhs / rhs,
+        BinOp::Pow => lhs.powf(rhs),
+        BinOp::Eq => return Ok(Value::Bool(lhs == rhs)),
+        BinOp::Ne => return Ok(Value::Bool(lhs != rhs)),
+        BinOp::Lt => return Ok(Value::Bool(lhs < rhs)),
+        BinOp::Le => return Ok(Value::Bool(lhs <= rhs)),
+        BinOp::Gt => return Ok(Value::Bool(lhs > rhs)),
+        BinOp::Ge => return Ok(Value::Bool(lhs >= rhs)),
+        _ => panic!("has to be a float operator!"),
+    };
+    Ok(Value::Float(value))
+}
+
+fn pat_matches(pat: &ast::Pat, value: &Value) -> bool {
+    use ast::{Lit, PatKind};
+    match (&pat.kind, value) {
+        (PatKind::Int(pat), Value::Int(value)) => value == pat,
+        (PatKind::Range(low, high), Value::Int(value)) => low <= value && value <= high,
+        (PatKind::Lit(Lit::StrLit(pat)), Value::Str(value)) => pat == value,
+        (PatKind::Lit(Lit::BoolLit(pat)), Value::Bool(value)) => pat == value,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Interpreter, Limits, Value};
+    use ast;
+    use errors::{self, Limit, Op, RuntimeError, RuntimeErrorKind};
+    use parser::Parser;
+    use scanner::{Scanner, WordStream};
+    use source_map::SourceFile;
+    use std::io;
+    use std::rc::Rc;
+
+    fn parse(src: &str) -> (ast::Program, ast::IdentTable) {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let file = Rc::new(SourceFile::new("test".into(), src.into()));
+        let word_stream = WordStream::new(Scanner::new(file), &handler);
+        let mut parser = Parser::new(word_stream);
+        let program = parser.parse_program().unwrap();
+        (program, parser.into_ident_table())
+    }
+
+    fn run(src: &str) -> (Result<(), RuntimeErrorKind>, Interpreter<'static>) {
+        let (program, ident_table) = parse(src);
+        let mut interp = Interpreter::new(&ident_table, io::empty(), io::sink());
+        let result = interp.run_program(&program).map_err(|err| err.kind);
+        (result, interp)
+    }
+
+    /// Runs a program reading from `input`, returning what it has written.
+    fn run_with_input(src: &str, input: &str) -> (Result<(), RuntimeErrorKind>, String) {
+        let (program, ident_table) = parse(src);
+        let mut output = vec![];
+        let result = Interpreter::new(&ident_table, input.as_bytes(), &mut output)
+            .run_program(&program)
+            .map_err(|err| err.kind);
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    /// Runs a program within some limits, returning the limit it went over.
+    fn run_with_limits(src: &str, limits: Limits) -> Option<Limit> {
+        let (program, ident_table) = parse(src);
+        let result = Interpreter::new(&ident_table, io::empty(), io::sink())
+            .with_limits(limits)
+            .run_program(&program);
+        match result {
+            Ok(()) => None,
+            Err(RuntimeError {
+                kind: RuntimeErrorKind::LimitExceeded(limit),
+                ..
+            }) => Some(limit),
+            Err(err) => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    /// Runs a program which fails, returning its error along with the code
+    /// it points at.
+    fn run_failing(src: &str, input: &str) -> (RuntimeErrorKind, String) {
+        let (program, ident_table) = parse(src);
+        let err = Interpreter::new(&ident_table, input.as_bytes(), io::sink())
+            .run_program(&program)
+            .unwrap_err();
+        let code = src[err.span.start.0..err.span.end.0].to_owned();
+        (err.kind, code)
+    }
+
+    /// The first variable of a program, named right after the program.
+    fn first_var<'a>(interp: &'a Interpreter) -> &'a Value {
+        interp.var(ast::Name(1)).unwrap()
+    }
+
+    #[test]
+    fn test_default_values() {
+        let (result, interp) = run("program p; let x: float; x = x;");
+        assert_eq!(Ok(()), result);
+        assert_eq!(&Value::Float(0.0), first_var(&interp));
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        let (_, interp) = run("program p; let x: int; x = 1 + 2 * 3 ** 2 - 10 div 3;");
+        assert_eq!(&Value::Int(16), first_var(&interp));
+    }
+
+    #[test]
+    fn test_arithmetic_operators() {
+        let (_, interp) = run("program p; let x: int; x = 7 mod 4 + 7 % 4 - -2 ** 2;");
+        assert_eq!(&Value::Int(10), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: int; x = 2 ** 3 ** 2;");
+        assert_eq!(&Value::Int(512), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: int; x = 2 ** -1;");
+        assert_eq!(&Value::Int(0), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: float; x = 7 / 2;");
+        assert_eq!(&Value::Float(3.5), first_var(&interp));
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        let (_, interp) = run("program p; let x: int; x = 12 & 10 | 1 ^ 3 << 2 >> 1;");
+        assert_eq!(&Value::Int(15), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: bool; x = true & false | !false;");
+        assert_eq!(&Value::Bool(true), first_var(&interp));
+    }
+
+    #[test]
+    fn test_numeric_promotion() {
+        let (_, interp) = run("program p; let x: float; x = 1 + 0.5 * 3;");
+        assert_eq!(&Value::Float(2.5), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: bool; x = 1 < 1.5;");
+        assert_eq!(&Value::Bool(true), first_var(&interp));
+    }
+
+    #[test]
+    fn test_casts() {
+        let (_, interp) = run("program p; let x: int; x = int(-3.9);");
+        assert_eq!(&Value::Int(-3), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: int; x = int(\"42\") + 1;");
+        assert_eq!(&Value::Int(43), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: float; x = float(\"2.5\") + float(1);");
+        assert_eq!(&Value::Float(3.5), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: str; x = str(2.0);");
+        assert_eq!(&Value::Str("2.0".into()), first_var(&interp));
+
+        let (result, _) = run("program p; let x: int; x = int(\"4x2\");");
+        assert_eq!(
+            Err(RuntimeErrorKind::InvalidConversion {
+                text: "4x2".into(),
+                ty: ast::Ty::IntTy,
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let (result, _) = run("program p; let x: int; x = 1 div 0;");
+        assert_eq!(Err(RuntimeErrorKind::DivisionByZero), result);
+
+        let (result, _) = run("program p; let x: int; x = 1 mod (2 - 2);");
+        assert_eq!(Err(RuntimeErrorKind::DivisionByZero), result);
+
+        let (result, _) = run("program p; let x: float; x = 1.0 / 0.0;");
+        assert_eq!(Err(RuntimeErrorKind::DivisionByZero), result);
+
+        let (result, _) = run("program p; let x: int; x = 0 ** -1;");
+        assert_eq!(Err(RuntimeErrorKind::DivisionByZero), result);
+    }
+
+    #[test]
+    fn test_string_operations() {
+        let (_, interp) = run("program p; let x: str; x = upper(\"ab\" + \"c\") + lower(\"ÀB\");");
+        assert_eq!(&Value::Str("ABCàb".into()), first_var(&interp));
+
+        let (_, interp) =
+            run("program p; let x: int; x = len(\"héllo\") * 10 + find(\"héllo\", \"l\");");
+        assert_eq!(&Value::Int(52), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: int; x = find(\"abc\", \"d\");");
+        assert_eq!(&Value::Int(-1), first_var(&interp));
+
+        let (_, interp) =
+            run("program p; let x: str; x = substr(\"héllo\", 1, 3) + char_at(\"abc\", 2);");
+        assert_eq!(&Value::Str("éllc".into()), first_var(&interp));
+
+        let (_, interp) =
+            run("program p; let x: str; x = substr(\"abc\", 3, 1) + substr(\"abc\", 1, 9);");
+        assert_eq!(&Value::Str("bc".into()), first_var(&interp));
+    }
+
+    #[test]
+    fn test_string_comparisons() {
+        let (_, interp) = run(
+            "program p; let x: bool; x = \"abc\" < \"abd\" & \"b\" > \"abc\" & \"ab\" <= \"ab\";",
+        );
+        assert_eq!(&Value::Bool(true), first_var(&interp));
+
+        let (_, interp) =
+            run("program p; let x: bool; x = \"a\" + \"b\" == \"ab\" & \"a\" != \"A\";");
+        assert_eq!(&Value::Bool(true), first_var(&interp));
+    }
+
+    #[test]
+    fn test_string_index_out_of_bounds() {
+        let (result, _) = run("program p; let x: str; x = char_at(\"abc\", 3);");
+        assert_eq!(
+            Err(RuntimeErrorKind::IndexOutOfBounds { index: 3, len: 3 }),
+            result
+        );
+
+        let (result, _) = run("program p; let x: str; x = substr(\"abc\", -1, 1);");
+        assert_eq!(
+            Err(RuntimeErrorKind::IndexOutOfBounds { index: -1, len: 3 }),
+            result
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let (result, output) = run_with_input(
+            "program p; let x: float; x = 2.0 / 3;
+             write(\"x = \", x, \"; \"); writeln(1 + 1, true); writeln(x:7:3, 42:4, \"ab\":3);",
+            "",
+        );
+        assert_eq!(Ok(()), result);
+        assert_eq!("x = 0.6666666666666666; 2true\n  0.667  42 ab\n", output);
+    }
+
+    #[test]
+    fn test_read() {
+        let (result, output) = run_with_input(
+            "program p; let i: int; let f: float; let b: bool; let s: str;
+             read(i, f); read(b, s); writeln(i + 1, \" \", f * 2, \" \", b, \" \", s);",
+            "41 1.5\n\n  true  hey there\n",
+        );
+        assert_eq!(Ok(()), result);
+        assert_eq!("42 3.0 true hey\n", output);
+    }
+
+    #[test]
+    fn test_readln() {
+        let (result, output) = run_with_input(
+            "program p; let i: int; let s: str; let t: str;
+             readln(i); readln(s); readln(i, t); write(s, \"|\", i, \"|\", t);",
+            "1 2 3\nhello world \n7  the rest\n",
+        );
+        assert_eq!(Ok(()), result);
+        assert_eq!("hello world |7|the rest", output);
+    }
+
+    #[test]
+    fn test_read_invalid_input() {
+        let (result, _) = run_with_input("program p; let i: int; read(i);", "4.5");
+        assert_eq!(
+            Err(RuntimeErrorKind::InvalidInput {
+                text: "4.5".into(),
+                ty: ast::Ty::IntTy,
+            }),
+            result
+        );
+
+        let (result, _) = run_with_input("program p; let i: int; read(i, i);", "1\n");
+        assert_eq!(Err(RuntimeErrorKind::UnexpectedEndOfInput), result);
+    }
+
+    #[test]
+    fn test_consts() {
+        let (_, interp) = run(
+            "program p; let x: float; const k: float = 2; const n: int = 3 * 2;
+             x = k * n;",
+        );
+        assert_eq!(&Value::Float(12.0), first_var(&interp));
+    }
+
+    #[test]
+    fn test_int_assigned_to_float() {
+        let (_, interp) = run("program p; let x: float; x = 3;");
+        assert_eq!(&Value::Float(3.0), first_var(&interp));
+    }
+
+    #[test]
+    fn test_loops() {
+        let (_, interp) = run("program p; let x: int; let i: int; for i = 1 to 4 { x = x + i; }");
+        assert_eq!(&Value::Int(10), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: int; let i: int;
+             for i = 10 downto 1 { if i == 3 { break; } if i % 2 == 0 { continue; } x = x + i; }");
+        assert_eq!(&Value::Int(21), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: int; repeat { x = x + 1; } until x >= 5;");
+        assert_eq!(&Value::Int(5), first_var(&interp));
+    }
+
+    #[test]
+    fn test_case() {
+        let (_, interp) = run("program p; let x: int; x = 4;
+             case x of 1, 2: { x = 10; } 3..5: { x = 20; } else { x = 30; }");
+        assert_eq!(&Value::Int(20), first_var(&interp));
+
+        let (_, interp) =
+            run("program p; let x: int; x = 9; case x of 1: { x = 10; } else { x = 30; }");
+        assert_eq!(&Value::Int(30), first_var(&interp));
+
+        let (_, interp) = run("program p; let x: int; x = -2;
+             case x of -5..-3: { x = 10; } -2, 0: { x = 20; }");
+        assert_eq!(&Value::Int(20), first_var(&interp));
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let limits = Limits {
+            max_depth: Some(3),
+            ..Limits::default()
+        };
+        let src = "program p; let s: str; { s = upper(lower(\"a\")); }";
+        assert_eq!(None, run_with_limits(src, limits));
+        let src = "program p; let s: str; { { s = upper(lower(\"a\")); } }";
+        assert_eq!(Some(Limit::Depth(3)), run_with_limits(src, limits));
+        let src = "program p; let s: str; { { { { s = \"a\"; } } } }";
+        assert_eq!(Some(Limit::Depth(3)), run_with_limits(src, limits));
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let limits = Limits {
+            max_memory: Some(4),
+            ..Limits::default()
+        };
+        let src = "program p; let s: str; let t: str; s = \"ab\"; t = \"cd\"; s = \"\";";
+        assert_eq!(None, run_with_limits(src, limits));
+        let src = "program p; let s: str; let t: str; s = \"ab\"; t = \"cde\";";
+        assert_eq!(Some(Limit::Memory(4)), run_with_limits(src, limits));
+
+        // Strings being built count, even if no variable holds them.
+        let src = "program p; let s: str; s = \"ab\"; writeln(s + \"cde\");";
+        assert_eq!(Some(Limit::Memory(4)), run_with_limits(src, limits));
+        let src = "program p; let s: str; s = \"ab\"; writeln(len(s + s));";
+        assert_eq!(Some(Limit::Memory(4)), run_with_limits(src, limits));
+        let src = "program p; let s: str; s = \"a\"; writeln(upper(\"bcde\"));";
+        assert_eq!(Some(Limit::Memory(4)), run_with_limits(src, limits));
+        let src = "program p; writeln(str(12345));";
+        assert_eq!(Some(Limit::Memory(4)), run_with_limits(src, limits));
+        let src = "program p; write(1:1000000000000);";
+        assert_eq!(Some(Limit::Memory(4)), run_with_limits(src, limits));
+        let src = "program p; let s: str; s = \"ab\"; writeln(s:2);";
+        assert_eq!(None, run_with_limits(src, limits));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let overflow = |op| Err(RuntimeErrorKind::Overflow { op: Op::Binary(op) });
+        let (result, _) = run("program p; let x: int; x = 9223372036854775807 + 1;");
+        assert_eq!(overflow(ast::BinOp::Add), result);
+        let (result, _) = run("program p; let x: int; x = -9223372036854775807 - 2;");
+        assert_eq!(overflow(ast::BinOp::Sub), result);
+        let (result, _) = run("program p; let x: int; x = 3037000500 * 3037000500;");
+        assert_eq!(overflow(ast::BinOp::Mult), result);
+        let (result, _) = run("program p; let x: int; x = 2 ** 63;");
+        assert_eq!(overflow(ast::BinOp::Pow), result);
+        let (result, _) = run("program p; let x: int; x = 2 ** 4294967296;");
+        assert_eq!(overflow(ast::BinOp::Pow), result);
+        let (result, interp) = run("program p; let x: int; x = (-1) ** 4294967296;");
+        assert_eq!(Ok(()), result);
+        assert_eq!(&Value::Int(1), first_var(&interp));
+        let (result, _) = run("program p; let x: int; x = -9223372036854775807 - 1; x = -x;");
+        assert_eq!(
+            Err(RuntimeErrorKind::Overflow {
+                op: Op::Unary(ast::UnOp::Neg),
+            }),
+            result
+        );
+        let (result, interp) = run("program p; let x: int; x = -9223372036854775808;");
+        assert_eq!(Ok(()), result);
+        assert_eq!(&Value::Int(i64::MIN), first_var(&interp));
+    }
+
+    #[test]
+    fn test_shift_overflow() {
+        let overflow = |op| Err(RuntimeErrorKind::Overflow { op: Op::Binary(op) });
+        let (result, interp) = run("program p; let x: int; x = (1 << 63) >> 63;");
+        assert_eq!(Ok(()), result);
+        assert_eq!(&Value::Int(-1), first_var(&interp));
+        let (result, _) = run("program p; let x: int; x = 1 << 64;");
+        assert_eq!(overflow(ast::BinOp::Shl), result);
+        let (result, _) = run("program p; let x: int; x = 1 << -1;");
+        assert_eq!(overflow(ast::BinOp::Shl), result);
+        let (result, _) = run("program p; let x: int; x = 1 >> 4294967296;");
+        assert_eq!(overflow(ast::BinOp::Shr), result);
+    }
+
+    #[test]
+    fn test_cast_overflow() {
+        let overflow = Err(RuntimeErrorKind::Overflow {
+            op: Op::Cast(ast::Ty::IntTy),
+        });
+        let (result, interp) = run("program p; let x: int; x = int(-9223372036854775808.0);");
+        assert_eq!(Ok(()), result);
+        assert_eq!(&Value::Int(i64::MIN), first_var(&interp));
+        let (result, _) = run("program p; let x: int; x = int(9223372036854775808.0);");
+        assert_eq!(overflow, result);
+        let (result, _) = run("program p; let x: int; x = int(1e30);");
+        assert_eq!(overflow, result);
+        let (result, _) = run("program p; let x: int; x = int(0.0 / 0.0);");
+        assert_eq!(Err(RuntimeErrorKind::DivisionByZero), result);
+        let (result, _) =
+            run("program p; let x: int; let f: float; f = 1e300; x = int(f * f - f * f);");
+        assert_eq!(overflow, result);
+    }
+
+    #[test]
+    fn test_error_spans() {
+        assert_eq!(
+            (RuntimeErrorKind::DivisionByZero, "x div 0".to_owned()),
+            run_failing("program p; let x: int; writeln(1 + x div 0);", "")
+        );
+        assert_eq!(
+            (
+                RuntimeErrorKind::IndexOutOfBounds { index: 5, len: 2 },
+                "char_at(\"ab\", 5)".to_owned()
+            ),
+            run_failing("program p; let s: str; s = upper(char_at(\"ab\", 5));", "")
+        );
+        assert_eq!(
+            (
+                RuntimeErrorKind::InvalidInput {
+                    text: "b".to_owned(),
+                    ty: ast::Ty::IntTy,
+                },
+                "y".to_owned()
+            ),
+            run_failing("program p; let x: int; let y: int; read(x, y);", "1 b")
+        );
+        assert_eq!(
+            (
+                RuntimeErrorKind::InvalidConversion {
+                    text: "1.5".to_owned(),
+                    ty: ast::Ty::IntTy,
+                },
+                "int(\"1.5\")".to_owned()
+            ),
+            run_failing("program p; let x: int; x = int(\"1.5\");", "")
+        );
+    }
+}
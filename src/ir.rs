@@ -0,0 +1,694 @@
+//! The lowered form of a checked program or unit, as written by
+//! `blaise build --emit=ir`.
+//!
+//! Expressions are broken down into instructions computing one operation
+//! each into a temporary, and statements into basic blocks, each of them
+//! ending in a jump, a branch or a return. Values convert implicitly as they
+//! do in the source, e.g. an `int` added to a `float`.
+
+use ast::{self, BinOp, Ty, UnOp};
+use driver::{Module, Root};
+use std::fmt::Write;
+
+/// A temporary, which holds the result of an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Temp(pub usize);
+
+/// The index of a basic block in its body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockId(pub usize);
+
+#[derive(Debug, PartialEq)]
+pub enum Operand {
+    Temp(Temp),
+    Var(ast::Name),
+    Const(Const),
+}
+
+/// A constant, which unlike a literal may be a negative integer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Const {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+/// Where an instruction stores its result.
+#[derive(Debug, PartialEq)]
+pub enum Place {
+    Temp(Temp),
+    Var(ast::Name),
+}
+
+/// An argument of a call, which `write` and `writeln` may give a field
+/// width and a precision, e.g. `x:8:2`.
+#[derive(Debug, PartialEq)]
+pub struct Arg {
+    pub value: Operand,
+    pub width: Option<Operand>,
+    pub prec: Option<Operand>,
+}
+
+/// A value computed by a single operation.
+#[derive(Debug, PartialEq)]
+pub enum Rvalue {
+    Use(Operand),
+    BinaryOp(BinOp, Operand, Operand),
+    UnaryOp(UnOp, Operand),
+    Cast(Ty, Operand),
+    Call(ast::Name, Vec<Arg>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Inst {
+    Assign(Place, Rvalue),
+    /// A call whose result, if any, isn't used, e.g. `writeln(x)`.
+    Call(ast::Name, Vec<Arg>),
+}
+
+/// How a basic block passes control on.
+#[derive(Debug, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// Goes to the first block if the condition holds, and to the second one
+    /// otherwise.
+    Branch(Operand, BlockId, BlockId),
+    Return,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BasicBlock {
+    pub insts: Vec<Inst>,
+    pub terminator: Terminator,
+}
+
+/// A global of the program or unit, or of a unit it uses.
+#[derive(Debug, PartialEq)]
+pub struct Global {
+    pub name: ast::Name,
+    pub ty: Ty,
+    pub is_const: bool,
+}
+
+/// A lowered program or unit. The body of a program starts by setting the
+/// constants, then runs its statements, while the body of a unit only sets
+/// its constants.
+#[derive(Debug, PartialEq)]
+pub struct Body {
+    pub name: ast::Name,
+    pub is_unit: bool,
+    pub globals: Vec<Global>,
+    /// The basic blocks, the first one being the entry.
+    pub blocks: Vec<BasicBlock>,
+}
+
+/// Lowers a checked program or unit, along with the units it uses.
+pub fn lower(module: &Module) -> Body {
+    let mut lowerer = Lowerer::new();
+    let mut globals = vec![];
+    let units = module
+        .units
+        .iter()
+        .map(|unit| (&unit.consts[..], &unit.decls[..]));
+    let root = (module.root.consts(), module.root.decls());
+    for (consts, decls) in units.chain(Some(root)) {
+        for decl in consts.iter() {
+            globals.push(Global {
+                name: decl.ident.name,
+                ty: decl.ty,
+                is_const: true,
+            });
+            lowerer.assign(Place::Var(decl.ident.name), &decl.expr);
+        }
+        for decl in decls.iter() {
+            globals.push(Global {
+                name: decl.ident.name,
+                ty: decl.ty,
+                is_const: false,
+            });
+        }
+    }
+    lowerer.stmts(module.root.stmts());
+    lowerer.terminate(Terminator::Return);
+
+    Body {
+        name: module.root.name().name,
+        is_unit: match module.root {
+            Root::Program(_) => false,
+            Root::Unit(_) => true,
+        },
+        globals,
+        blocks: lowerer.finish(),
+    }
+}
+
+/// Builds the basic blocks of a body, adding instructions to the current
+/// block until it's terminated.
+struct Lowerer {
+    blocks: Vec<(Vec<Inst>, Option<Terminator>)>,
+    current: BlockId,
+    temp_count: usize,
+    /// The blocks `continue` and `break` go to in each loop entered, the
+    /// innermost one last.
+    loops: Vec<(BlockId, BlockId)>,
+}
+
+impl Lowerer {
+    fn new() -> Lowerer {
+        Lowerer {
+            blocks: vec![(vec![], None)],
+            current: BlockId(0),
+            temp_count: 0,
+            loops: vec![],
+        }
+    }
+
+    fn finish(self) -> Vec<BasicBlock> {
+        self.blocks
+            .into_iter()
+            .map(|(insts, terminator)| BasicBlock {
+                insts,
+                terminator: terminator.expect("every block has to be terminated!"),
+            })
+            .collect()
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push((vec![], None));
+        BlockId(self.blocks.len() - 1)
+    }
+
+    fn new_temp(&mut self) -> Temp {
+        self.temp_count += 1;
+        Temp(self.temp_count - 1)
+    }
+
+    /// Adds an instruction to the current block. The code following a
+    /// `break` or `continue` in its block is never run, so it's dropped.
+    fn push(&mut self, inst: Inst) {
+        let block = &mut self.blocks[self.current.0];
+        if block.1.is_none() {
+            block.0.push(inst);
+        }
+    }
+
+    /// Terminates the current block, unless a `break` or `continue` already
+    /// did.
+    fn terminate(&mut self, terminator: Terminator) {
+        let block = &mut self.blocks[self.current.0];
+        if block.1.is_none() {
+            block.1 = Some(terminator);
+        }
+    }
+
+    /// Terminates the current block, then continues in another one.
+    fn switch_to(&mut self, terminator: Terminator, block: BlockId) {
+        self.terminate(terminator);
+        self.current = block;
+    }
+
+    fn stmts(&mut self, stmts: &[ast::Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &ast::Stmt) {
+        use ast::StmtKind;
+        match stmt.kind {
+            StmtKind::While(ref cond_expr, ref block) => {
+                let head = self.new_block();
+                let body = self.new_block();
+                let exit = self.new_block();
+                self.switch_to(Terminator::Jump(head), head);
+                let cond = self.operand(cond_expr);
+                self.switch_to(Terminator::Branch(cond, body, exit), body);
+                self.loop_body(block, head, exit);
+                self.switch_to(Terminator::Jump(head), exit);
+            }
+            StmtKind::For(ref ident, ref start_expr, ref dir, ref end_expr, ref block) => {
+                // The bounds are evaluated once, and the loop stops once the
+                // control variable reaches the end, so it never overflows.
+                let i = self.new_temp();
+                self.assign(Place::Temp(i), start_expr);
+                let end = self.new_temp();
+                self.assign(Place::Temp(end), end_expr);
+                let (step_op, cmp_op) = match *dir {
+                    ast::ForDir::To => (BinOp::Add, BinOp::Le),
+                    ast::ForDir::Downto => (BinOp::Sub, BinOp::Ge),
+                };
+                let body = self.new_block();
+                let step = self.new_block();
+                let next = self.new_block();
+                let exit = self.new_block();
+                let in_range = self.binary_op(cmp_op, Operand::Temp(i), Operand::Temp(end));
+                self.switch_to(Terminator::Branch(in_range, body, exit), body);
+                let value = Rvalue::Use(Operand::Temp(i));
+                self.push(Inst::Assign(Place::Var(ident.name), value));
+                self.loop_body(block, step, exit);
+                self.switch_to(Terminator::Jump(step), step);
+                let at_end = self.binary_op(BinOp::Eq, Operand::Temp(i), Operand::Temp(end));
+                self.switch_to(Terminator::Branch(at_end, exit, next), next);
+                let one = Operand::Const(Const::Int(1));
+                let value = Rvalue::BinaryOp(step_op, Operand::Temp(i), one);
+                self.push(Inst::Assign(Place::Temp(i), value));
+                self.switch_to(Terminator::Jump(body), exit);
+            }
+            StmtKind::Repeat(ref block, ref cond_expr) => {
+                let body = self.new_block();
+                let cond_block = self.new_block();
+                let exit = self.new_block();
+                self.switch_to(Terminator::Jump(body), body);
+                self.loop_body(block, cond_block, exit);
+                self.switch_to(Terminator::Jump(cond_block), cond_block);
+                let cond = self.operand(cond_expr);
+                self.switch_to(Terminator::Branch(cond, exit, body), exit);
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let &(continue_target, break_target) =
+                    self.loops.last().expect("has to be in a loop!");
+                let target = match stmt.kind {
+                    StmtKind::Break => break_target,
+                    _ => continue_target,
+                };
+                self.terminate(Terminator::Jump(target));
+            }
+            StmtKind::If(ref cond_expr, ref then_block, ref else_block) => {
+                let cond = self.operand(cond_expr);
+                let then_id = self.new_block();
+                let else_id = else_block.as_ref().map(|_| self.new_block());
+                let join = self.new_block();
+                self.switch_to(
+                    Terminator::Branch(cond, then_id, else_id.unwrap_or(join)),
+                    then_id,
+                );
+                self.block(then_block);
+                if let (Some(else_block), Some(else_id)) = (else_block.as_ref(), else_id) {
+                    self.switch_to(Terminator::Jump(join), else_id);
+                    self.block(else_block);
+                }
+                self.switch_to(Terminator::Jump(join), join);
+            }
+            StmtKind::Case(ref expr, ref arms, ref else_block) => self.case(expr, arms, else_block),
+            StmtKind::Assign(ref ident, ref expr) => self.assign(Place::Var(ident.name), expr),
+            StmtKind::BlockStmt(ref block) => self.block(block),
+            StmtKind::Call(ref ident, ref args) => {
+                let args = self.args(args);
+                self.push(Inst::Call(ident.name, args));
+            }
+        }
+    }
+
+    fn block(&mut self, block: &ast::Block) {
+        self.stmts(&block.stmts);
+    }
+
+    fn loop_body(&mut self, block: &ast::Block, continue_target: BlockId, break_target: BlockId) {
+        self.loops.push((continue_target, break_target));
+        self.block(block);
+        self.loops.pop();
+    }
+
+    /// Tests the patterns of the arms in order, running the block of the
+    /// first arm with a matching pattern.
+    fn case(
+        &mut self,
+        expr: &ast::Expr,
+        arms: &[ast::CaseArm],
+        else_block: &Option<Box<ast::Block>>,
+    ) {
+        let value_temp = self.new_temp();
+        self.assign(Place::Temp(value_temp), expr);
+        let pats = arms
+            .iter()
+            .enumerate()
+            .flat_map(|(i, arm)| arm.pats.iter().map(move |pat| (i, pat)))
+            .collect::<Vec<_>>();
+        // Each pattern but the first is tested in a block of its own.
+        let test_ids = pats
+            .iter()
+            .skip(1)
+            .map(|_| self.new_block())
+            .collect::<Vec<_>>();
+        let arm_ids = arms.iter().map(|_| self.new_block()).collect::<Vec<_>>();
+        let else_id = else_block.as_ref().map(|_| self.new_block());
+        let join = self.new_block();
+        let no_match = else_id.unwrap_or(join);
+
+        if pats.is_empty() {
+            self.terminate(Terminator::Jump(no_match));
+        }
+        for (i, &(arm_index, pat)) in pats.iter().enumerate() {
+            let value = || Operand::Temp(value_temp);
+            let int = |n| Operand::Const(Const::Int(n));
+            let matches = match pat.kind {
+                ast::PatKind::Int(n) => self.binary_op(BinOp::Eq, value(), int(n)),
+                ast::PatKind::Range(start, end) => {
+                    let above = self.binary_op(BinOp::Ge, value(), int(start));
+                    let below = self.binary_op(BinOp::Le, value(), int(end));
+                    self.binary_op(BinOp::BitAnd, above, below)
+                }
+                ast::PatKind::Lit(ref lit) => {
+                    self.binary_op(BinOp::Eq, value(), Operand::Const(lit_const(lit)))
+                }
+            };
+            let next = test_ids.get(i).cloned().unwrap_or(no_match);
+            self.switch_to(Terminator::Branch(matches, arm_ids[arm_index], next), next);
+        }
+
+        for (arm, &arm_id) in arms.iter().zip(&arm_ids) {
+            self.current = arm_id;
+            self.block(&arm.block);
+            self.terminate(Terminator::Jump(join));
+        }
+        if let (Some(else_block), Some(else_id)) = (else_block.as_ref(), else_id) {
+            self.current = else_id;
+            self.block(else_block);
+            self.terminate(Terminator::Jump(join));
+        }
+        self.current = join;
+    }
+
+    /// Lowers an expression into the instructions computing it, returning
+    /// the operand holding its value.
+    fn operand(&mut self, expr: &ast::Expr) -> Operand {
+        use ast::ExprKind;
+        if let Some(n) = negated_int_lit(expr) {
+            return Operand::Const(Const::Int(n));
+        }
+        match expr.kind {
+            ExprKind::Lit(ref lit) => Operand::Const(lit_const(lit)),
+            ExprKind::Ident(ref ident) => Operand::Var(ident.name),
+            ExprKind::Paren(ref expr) => self.operand(expr),
+            _ => {
+                let rvalue = self.rvalue(expr);
+                self.new_temp_with(rvalue)
+            }
+        }
+    }
+
+    /// Lowers an expression, storing its value in a place.
+    fn assign(&mut self, place: Place, expr: &ast::Expr) {
+        let rvalue = self.rvalue(expr);
+        self.push(Inst::Assign(place, rvalue));
+    }
+
+    /// Lowers the operands of an expression, returning the operation left
+    /// to compute its value.
+    fn rvalue(&mut self, expr: &ast::Expr) -> Rvalue {
+        use ast::ExprKind;
+        match expr.kind {
+            _ if negated_int_lit(expr).is_some() => Rvalue::Use(self.operand(expr)),
+            ExprKind::BinaryOp(op, ref lhs, ref rhs) => {
+                let lhs = self.operand(lhs);
+                let rhs = self.operand(rhs);
+                Rvalue::BinaryOp(op, lhs, rhs)
+            }
+            ExprKind::UnaryOp(op, ref operand) => Rvalue::UnaryOp(op, self.operand(operand)),
+            ExprKind::Lit(_) | ExprKind::Ident(_) => Rvalue::Use(self.operand(expr)),
+            ExprKind::Paren(ref expr) => self.rvalue(expr),
+            ExprKind::Cast(ty, ref operand) => Rvalue::Cast(ty, self.operand(operand)),
+            ExprKind::Call(ref ident, ref args) => Rvalue::Call(ident.name, self.args(args)),
+            ExprKind::Formatted(..) => panic!("has to be an argument of a call!"),
+        }
+    }
+
+    fn binary_op(&mut self, op: BinOp, lhs: Operand, rhs: Operand) -> Operand {
+        self.new_temp_with(Rvalue::BinaryOp(op, lhs, rhs))
+    }
+
+    /// Stores a value in a new temporary.
+    fn new_temp_with(&mut self, rvalue: Rvalue) -> Operand {
+        let temp = self.new_temp();
+        self.push(Inst::Assign(Place::Temp(temp), rvalue));
+        Operand::Temp(temp)
+    }
+
+    fn args(&mut self, args: &[ast::Expr]) -> Vec<Arg> {
+        args.iter()
+            .map(|arg| match arg.kind {
+                ast::ExprKind::Formatted(ref value, ref width, ref prec) => Arg {
+                    value: self.operand(value),
+                    width: Some(self.operand(width)),
+                    prec: prec.as_ref().map(|prec| self.operand(prec)),
+                },
+                _ => Arg {
+                    value: self.operand(arg),
+                    width: None,
+                    prec: None,
+                },
+            })
+            .collect()
+    }
+}
+
+fn lit_const(lit: &ast::Lit) -> Const {
+    match *lit {
+        ast::Lit::IntLit(value) => Const::Int(value as i64),
+        ast::Lit::FloatLit(value) => Const::Float(value),
+        ast::Lit::StrLit(ref value) => Const::Str(value.clone()),
+        ast::Lit::BoolLit(value) => Const::Bool(value),
+    }
+}
+
+/// The value of a negated integer literal, e.g. `-1`, which is folded into
+/// a constant so that `-9223372036854775808` needs no literal out of range.
+fn negated_int_lit(expr: &ast::Expr) -> Option<i64> {
+    match expr.kind {
+        ast::ExprKind::UnaryOp(UnOp::Neg, ref operand) => match operand.kind {
+            ast::ExprKind::Lit(ast::Lit::IntLit(value)) => Some((value as i64).wrapping_neg()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl Body {
+    /// Shows the globals, then the basic blocks with one instruction per
+    /// line.
+    pub fn dump(&self, ident_table: &ast::IdentTable) -> String {
+        let mut text = String::new();
+        let keyword = if self.is_unit { "unit" } else { "program" };
+        let _ = writeln!(text, "{} {}", keyword, ident_table.get(self.name));
+        if !self.globals.is_empty() {
+            text.push('\n');
+        }
+        for global in &self.globals {
+            let keyword = if global.is_const { "const" } else { "var" };
+            let name = ident_table.get(global.name);
+            let _ = writeln!(text, "{} {}: {}", keyword, name, global.ty.name());
+        }
+        let dumper = Dumper { ident_table };
+        for (id, block) in self.blocks.iter().enumerate() {
+            let _ = writeln!(text, "\nbb{}:", id);
+            for inst in &block.insts {
+                let _ = writeln!(text, "    {}", dumper.inst(inst));
+            }
+            let _ = writeln!(text, "    {}", dumper.terminator(&block.terminator));
+        }
+        text
+    }
+}
+
+/// Shows the parts of a body, looking names up in the identifier table.
+struct Dumper<'a> {
+    ident_table: &'a ast::IdentTable,
+}
+
+impl<'a> Dumper<'a> {
+    fn inst(&self, inst: &Inst) -> String {
+        match *inst {
+            Inst::Assign(ref place, ref rvalue) => {
+                let place = match *place {
+                    Place::Temp(temp) => self.operand(&Operand::Temp(temp)),
+                    Place::Var(name) => self.ident_table.get(name).to_owned(),
+                };
+                format!("{} = {}", place, self.rvalue(rvalue))
+            }
+            Inst::Call(name, ref args) => format!("call {}", self.call(name, args)),
+        }
+    }
+
+    fn rvalue(&self, rvalue: &Rvalue) -> String {
+        match *rvalue {
+            Rvalue::Use(ref operand) => self.operand(operand),
+            Rvalue::BinaryOp(op, ref lhs, ref rhs) => {
+                format!("{} {} {}", self.operand(lhs), op, self.operand(rhs))
+            }
+            Rvalue::UnaryOp(op, ref operand) => format!("{}{}", op, self.operand(operand)),
+            Rvalue::Cast(ty, ref operand) => format!("{}({})", ty.name(), self.operand(operand)),
+            Rvalue::Call(name, ref args) => self.call(name, args),
+        }
+    }
+
+    fn call(&self, name: ast::Name, args: &[Arg]) -> String {
+        let args = args
+            .iter()
+            .map(|arg| {
+                let mut text = self.operand(&arg.value);
+                for operand in arg.width.iter().chain(&arg.prec) {
+                    text.push(':');
+                    text.push_str(&self.operand(operand));
+                }
+                text
+            })
+            .collect::<Vec<_>>();
+        format!("{}({})", self.ident_table.get(name), args.join(", "))
+    }
+
+    fn terminator(&self, terminator: &Terminator) -> String {
+        match *terminator {
+            Terminator::Jump(target) => format!("jump bb{}", target.0),
+            Terminator::Branch(ref cond, then_target, else_target) => format!(
+                "branch {}, bb{}, bb{}",
+                self.operand(cond),
+                then_target.0,
+                else_target.0
+            ),
+            Terminator::Return => "return".to_owned(),
+        }
+    }
+
+    fn operand(&self, operand: &Operand) -> String {
+        match *operand {
+            Operand::Temp(temp) => format!("%{}", temp.0),
+            Operand::Var(name) => self.ident_table.get(name).to_owned(),
+            Operand::Const(Const::Int(value)) => value.to_string(),
+            Operand::Const(Const::Float(value)) => format!("{:?}", value),
+            Operand::Const(Const::Str(ref value)) => format!("{:?}", value),
+            Operand::Const(Const::Bool(value)) => value.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::lower;
+    use driver::{compile_str, Options};
+
+    fn dump(src: &str) -> String {
+        let compilation = compile_str("test", src, &Options::default());
+        assert_eq!("", compilation.render_diagnostics());
+        let module = compilation.module.unwrap();
+        lower(&module).dump(&module.ident_table)
+    }
+
+    #[test]
+    fn test_lower_exprs() {
+        assert_eq!(
+            "program p
+
+const k: int
+var s: str
+var x: float
+
+bb0:
+    k = -3
+    call readln(s)
+    %0 = len(s)
+    %1 = %0 * k
+    %2 = float(%1)
+    x = 1 + %2
+    call writeln(x:8:2)
+    return
+",
+            dump(
+                "program p; const k: int = -3; let s: str; let x: float;
+                 readln(s); x = 1 + float(len(s) * k); writeln(x:8:2);"
+            )
+        );
+    }
+
+    #[test]
+    fn test_lower_loops() {
+        assert_eq!(
+            "program p
+
+var i: int
+
+bb0:
+    %0 = 1
+    %1 = 3
+    %2 = %0 <= %1
+    branch %2, bb1, bb4
+
+bb1:
+    i = %0
+    %3 = i == 2
+    branch %3, bb5, bb6
+
+bb2:
+    %4 = %0 == %1
+    branch %4, bb4, bb3
+
+bb3:
+    %0 = %0 + 1
+    jump bb1
+
+bb4:
+    jump bb7
+
+bb5:
+    jump bb2
+
+bb6:
+    call writeln(i)
+    jump bb2
+
+bb7:
+    %5 = i > 0
+    branch %5, bb8, bb9
+
+bb8:
+    i = i - 1
+    jump bb7
+
+bb9:
+    return
+",
+            dump(
+                "program p; let i: int;
+                 for i = 1 to 3 { if i == 2 { continue; } writeln(i); }
+                 while i > 0 { i = i - 1; }"
+            )
+        );
+    }
+
+    #[test]
+    fn test_lower_case() {
+        assert_eq!(
+            "program p
+
+var i: int
+
+bb0:
+    i = 0
+    %0 = i
+    %1 = %0 == -1
+    branch %1, bb2, bb1
+
+bb1:
+    %2 = %0 >= 2
+    %3 = %0 <= 5
+    %4 = %2 & %3
+    branch %4, bb2, bb3
+
+bb2:
+    call writeln(\"a\")
+    jump bb4
+
+bb3:
+    call writeln(\"b\")
+    jump bb4
+
+bb4:
+    return
+",
+            dump(
+                "program p; let i: int; i = 0;
+                 case i of -1, 2..5: { writeln(\"a\"); } else { writeln(\"b\"); }"
+            )
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::fmt;
+use std::result;
+
+type Result<T> = result::Result<T, JsonError>;
+
+/// A JSON value, as exchanged with editors and other tools.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// An object, whose members keep the order they were given in.
+    Object(Vec<(String, Json)>),
+}
+
+/// Malformed JSON text, with the byte offset where parsing stopped.
+#[derive(Debug, PartialEq)]
+pub struct JsonError {
+    pub offset: usize,
+}
+
+impl Json {
+    /// Parses a JSON text, which has to hold exactly one value.
+    pub fn parse(text: &str) -> Result<Json> {
+        let mut parser = JsonParser { text, pos: 0 };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos != text.len() {
+            return Err(parser.error());
+        }
+        Ok(value)
+    }
+
+    /// Creates an object from its members.
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    /// Returns the value of an object's member, if this is an object and
+    /// has such member.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref members) => members
+                .iter()
+                .find(|&(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Json::Number(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a number if it's a non-negative integer.
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Json::Number(value) if value >= 0.0 && value.fract() == 0.0 => Some(value as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match *self {
+            Json::Array(ref values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Json {
+    fn from(value: &'a str) -> Json {
+        Json::String(value.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json {
+        Json::Number(value as f64)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            // Integers are written without a fractional part, as most
+            // tools expect.
+            Json::Number(value) if value.fract() == 0.0 && value.abs() < 1e15 => {
+                write!(f, "{}", value as i64)
+            }
+            Json::Number(value) if value.is_finite() => write!(f, "{}", value),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(ref value) => write_json_str(f, value),
+            Json::Array(ref values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(ref members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_str(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_json_str(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self) -> JsonError {
+        JsonError { offset: self.pos }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !matches!(c, ' ' | '\t' | '\n' | '\r') {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, value: Json) -> Result<Json> {
+        if self.text[self.pos..].starts_with(keyword) {
+            self.pos += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.parse_keyword("null", Json::Null),
+            Some('t') => self.parse_keyword("true", Json::Bool(true)),
+            Some('f') => self.parse_keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.parse_str()?)),
+            Some('[') => self.parse_array(),
+            Some('{') => self.parse_object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                break;
+            }
+            self.pos += 1;
+        }
+        self.text[start..self.pos]
+            .parse()
+            .map(Json::Number)
+            .map_err(|_| JsonError { offset: start })
+    }
+
+    fn parse_str(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error())?;
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = self.peek().ok_or_else(|| self.error())?;
+                    self.pos += 1;
+                    value.push(match escaped {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => self.parse_unicode_escape()?,
+                        _ => return Err(self.error()),
+                    });
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// Parses the digits of a `\u` escape, which may be the first half of a
+    /// UTF-16 surrogate pair.
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let first = self.parse_hex4()?;
+        let code = if (0xd800..0xdc00).contains(&first) {
+            if !self.text[self.pos..].starts_with("\\u") {
+                return Err(self.error());
+            }
+            self.pos += 2;
+            let second = self.parse_hex4()?;
+            if !(0xdc00..0xe000).contains(&second) {
+                return Err(self.error());
+            }
+            0x10000 + ((first - 0xd800) << 10) + (second - 0xdc00)
+        } else {
+            first
+        };
+        ::std::char::from_u32(code).ok_or_else(|| self.error())
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error())?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| self.error())?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn parse_array(&mut self) -> Result<Json> {
+        self.expect('[')?;
+        let mut values = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json> {
+        self.expect('{')?;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_str()?;
+            self.expect(':')?;
+            members.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Json, JsonError};
+
+    #[test]
+    fn test_parse_values() {
+        let value = Json::parse(
+            r#" {"id": 1, "params": {"text": "a\"b\né😀", "ok": [true, false, null]},
+                "x": -2.5e1} "#,
+        )
+        .unwrap();
+        assert_eq!(Some(1), value.get("id").and_then(Json::as_usize));
+        assert_eq!(Some(-25.0), value.get("x").and_then(Json::as_f64));
+        let params = value.get("params").unwrap();
+        assert_eq!(Some("a\"b\né😀"), params.get("text").and_then(Json::as_str));
+        assert_eq!(
+            Some(&[Json::Bool(true), Json::Bool(false), Json::Null][..]),
+            params.get("ok").and_then(Json::as_array)
+        );
+        assert_eq!(None, params.get("missing"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Err(JsonError { offset: 5 }), Json::parse("[1, 2"));
+        assert_eq!(Err(JsonError { offset: 5 }), Json::parse("{\"a\" 1}"));
+        assert_eq!(Err(JsonError { offset: 3 }), Json::parse("{} x"));
+        assert_eq!(Err(JsonError { offset: 0 }), Json::parse("nul"));
+    }
+
+    #[test]
+    fn test_write_values() {
+        let value = Json::object(vec![
+            ("n", Json::from(3)),
+            ("f", Json::Number(0.5)),
+            ("s", Json::from("tab\there \"quoted\"\u{1}")),
+            ("a", Json::Array(vec![Json::Null, Json::from(true)])),
+            ("o", Json::object(vec![])),
+        ]);
+        let text = value.to_string();
+        assert_eq!(
+            r#"{"n":3,"f":0.5,"s":"tab\there \"quoted\"\u0001","a":[null,true],"o":{}}"#,
+            text
+        );
+        assert_eq!(Ok(value), Json::parse(&text));
+    }
+}
@@ -0,0 +1,91 @@
+//! The blaise compiler, as a library.
+//!
+//! `compile_str` and `check_file` compile a program or unit, returning the
+//! diagnostics found, and `run_program` runs a compiled program with the
+//! input and output given:
+//!
+//! ```
+//! let src = "program hello; let name: str; readln(name); writeln(\"hi, \", name);";
+//! let compilation = blaise::compile_str("hello", src, &blaise::Options::default());
+//! assert!(!compilation.has_errors());
+//!
+//! let module = compilation.module.unwrap();
+//! let mut output = vec![];
+//! blaise::run_program(&module, &b"Blaise\n"[..], &mut output).unwrap();
+//! assert_eq!("hi, Blaise\n", String::from_utf8(output).unwrap());
+//! ```
+//!
+//! Diagnostics render as `blaise` writes them, and runtime errors, which
+//! point at the code which failed, render as diagnostics as well:
+//!
+//! ```
+//! use blaise::errors::{Diag, Level, RuntimeErrorKind};
+//!
+//! let src = "program p;\nlet x: int;\nx = true;\n";
+//! let compilation = blaise::compile_str("p", src, &blaise::Options::default());
+//! assert!(compilation.module.is_none());
+//! assert_eq!(
+//!     "<p>:3:5: error[B0017]: mismatched types: expected `int`, but found `bool`\n",
+//!     compilation.render_diagnostics()
+//! );
+//!
+//! let src = "program p;\nwriteln(1 div 0);\n";
+//! let compilation = blaise::compile_str("p", src, &blaise::Options::default());
+//! let module = compilation.module.as_ref().unwrap();
+//! let err = blaise::run_program(module, &b""[..], vec![]).unwrap_err();
+//! assert_eq!(RuntimeErrorKind::DivisionByZero, err.kind);
+//! assert_eq!(
+//!     "<p>:2:9: error[B0022]: division by zero",
+//!     Diag::from(err).render(Level::Error, &compilation.source_map)
+//! );
+//! ```
+//!
+//! The host may define functions of its own, which programs call like
+//! builtins once their signatures are registered:
+//!
+//! ```
+//! use blaise::ast::Ty;
+//! use blaise::{Natives, Value};
+//!
+//! let mut natives = Natives::new();
+//! natives.register("double", &[Ty::IntTy], Some(Ty::IntTy), |args| match args[0] {
+//!     Value::Int(x) => Ok(Some(Value::Int(2 * x))),
+//!     _ => unreachable!(),
+//! });
+//! let options = blaise::Options {
+//!     natives,
+//!     ..blaise::Options::default()
+//! };
+//!
+//! let compilation = blaise::compile_str("p", "program p; writeln(double(21));", &options);
+//! let mut output = vec![];
+//! blaise::run_program(&compilation.module.unwrap(), &b""[..], &mut output).unwrap();
+//! assert_eq!("42\n", String::from_utf8(output).unwrap());
+//! ```
+//!
+//! The modules below give access to every stage of the compiler.
+
+pub mod ast;
+pub mod builtins;
+pub mod cst;
+pub mod driver;
+pub mod error_codes;
+pub mod errors;
+pub mod fix;
+pub mod formatter;
+pub mod interp;
+pub mod ir;
+pub mod json;
+pub mod loader;
+pub mod lsp;
+pub mod parser;
+pub mod repl;
+pub mod scanner;
+pub mod source_map;
+pub mod typeck;
+
+pub use builtins::Natives;
+pub use driver::{
+    check_file, compile_str, run_program, run_program_with_limits, Compilation, Diagnostic, Options,
+};
+pub use interp::{Limits, Value};
@@ -0,0 +1,257 @@
+use ast;
+use errors::{self, Diag};
+use parser::Parser;
+use scanner::{Scanner, WordStream};
+use source_map::{SourceFile, SourceMap};
+use std::collections::HashSet;
+use std::mem;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::result;
+
+type Result<T> = result::Result<T, Diag>;
+
+/// A program along with every unit it uses, directly or not.
+pub struct LoadedProgram {
+    pub program: ast::Program,
+    /// The units used by the program, each one placed after the units it
+    /// uses.
+    pub units: Vec<ast::Unit>,
+    pub ident_table: ast::IdentTable,
+}
+
+/// A unit along with every unit it uses, directly or not.
+pub struct LoadedUnit {
+    pub unit: ast::Unit,
+    /// The units used by the unit, each one placed after the units it uses.
+    pub units: Vec<ast::Unit>,
+    pub ident_table: ast::IdentTable,
+}
+
+/// The module loader.
+///
+/// It parses a program and then every unit named by a `uses` clause, looking
+/// for the file `<unit name>.bl` in each directory of the search path, in
+/// order. Every file loaded is added to the source map.
+pub struct Loader<'a> {
+    handler: &'a errors::Handler,
+    source_map: &'a SourceMap,
+    search_path: Vec<PathBuf>,
+    ident_table: ast::IdentTable,
+    units: Vec<ast::Unit>,
+    loaded: HashSet<ast::Name>,
+    /// The units being loaded, each one used by the previous one.
+    loading: Vec<ast::Name>,
+}
+
+impl<'a> Loader<'a> {
+    pub fn new(
+        handler: &'a errors::Handler,
+        source_map: &'a SourceMap,
+        search_path: Vec<PathBuf>,
+    ) -> Loader<'a> {
+        Loader {
+            handler,
+            source_map,
+            search_path,
+            ident_table: ast::IdentTable::default(),
+            units: vec![],
+            loaded: HashSet::new(),
+            loading: vec![],
+        }
+    }
+
+    /// Loads the program of a file, along with the units it uses.
+    pub fn load_program(mut self, file: Rc<SourceFile>) -> Result<LoadedProgram> {
+        let program = self.parse_file(file, Parser::parse_program)?;
+        self.load_units(&program.uses)?;
+        Ok(LoadedProgram {
+            program,
+            units: self.units,
+            ident_table: self.ident_table,
+        })
+    }
+
+    /// Loads the unit of a file, along with the units it uses.
+    pub fn load_unit_file(mut self, file: Rc<SourceFile>) -> Result<LoadedUnit> {
+        let unit = self.parse_file(file, Parser::parse_unit)?;
+        self.loading.push(unit.name.name);
+        self.load_units(&unit.uses)?;
+        Ok(LoadedUnit {
+            unit,
+            units: self.units,
+            ident_table: self.ident_table,
+        })
+    }
+
+    fn load_units(&mut self, uses: &[ast::Ident]) -> Result<()> {
+        for unit_ident in uses {
+            self.load_unit(unit_ident)?;
+        }
+        Ok(())
+    }
+
+    fn load_unit(&mut self, unit_ident: &ast::Ident) -> Result<()> {
+        let name = unit_ident.name;
+        if self.loading.contains(&name) {
+            return Err(Diag::CyclicUnitUse {
+                span: unit_ident.span,
+            });
+        }
+        if self.loaded.contains(&name) {
+            return Ok(());
+        }
+
+        let file_name = format!("{}.bl", self.ident_table.get(name));
+        let path = self
+            .search_path
+            .iter()
+            .map(|dir| dir.join(&file_name))
+            .find(|path| path.is_file())
+            .ok_or(Diag::UnitNotFound {
+                span: unit_ident.span,
+            })?;
+        let file = self
+            .source_map
+            .load_file(&path)
+            .map_err(|err| Diag::IoError {
+                message: format!("{}: {}", path.display(), err),
+            })?;
+        let unit = self.parse_file(file, Parser::parse_unit)?;
+        if unit.name.name != name {
+            return Err(Diag::UnitNameMismatch {
+                span: unit.name.span,
+            });
+        }
+
+        self.loading.push(name);
+        self.load_units(&unit.uses)?;
+        self.loading.pop();
+
+        self.loaded.insert(name);
+        self.units.push(unit);
+        Ok(())
+    }
+
+    /// Parses a file with `parse`, sharing the identifier table with every
+    /// other file.
+    fn parse_file<T, F>(&mut self, file: Rc<SourceFile>, parse: F) -> Result<T>
+    where
+        F: FnOnce(&mut Parser<'a>) -> Result<T>,
+    {
+        let word_stream = WordStream::new(Scanner::new(file), self.handler);
+        let ident_table = mem::take(&mut self.ident_table);
+        let mut parser = Parser::with_ident_table(word_stream, ident_table);
+        let result = parse(&mut parser);
+        self.ident_table = parser.into_ident_table();
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Loader;
+    use errors::{self, Diag};
+    use source_map::{BytePos, FileName, SourceMap, Span};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Creates a directory with the given files, whose path is unique to
+    /// the test.
+    fn create_dir(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("blaise-loader-{}", test_name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for &(name, src) in files {
+            fs::write(dir.join(name), src).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_load_units() {
+        let dir = create_dir(
+            "units",
+            &[
+                ("a.bl", "unit a; uses b, c; let x: int;"),
+                ("b.bl", "unit b; uses c; const y: int = z;"),
+                ("c.bl", "unit c; const z: int = 1;"),
+            ],
+        );
+        let handler = errors::Handler::with_ignoring_emitter();
+        let source_map = SourceMap::new();
+        let file = source_map.add_file("main.bl".into(), "program p; uses a, c;".into());
+        let loaded = Loader::new(&handler, &source_map, vec![dir.clone()])
+            .load_program(file)
+            .unwrap();
+
+        let unit_names = loaded
+            .units
+            .iter()
+            .map(|unit| loaded.ident_table.get(unit.name.name))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["c", "b", "a"], unit_names);
+
+        let b_file = source_map.lookup_file(BytePos(60)).unwrap();
+        assert_eq!(&FileName::Real(dir.join("b.bl")), b_file.name());
+    }
+
+    #[test]
+    fn test_load_unit_file() {
+        let dir = create_dir(
+            "unit-file",
+            &[("b.bl", "unit b; uses c;"), ("c.bl", "unit c; uses a;")],
+        );
+        let handler = errors::Handler::with_ignoring_emitter();
+        let source_map = SourceMap::new();
+        let file = source_map.add_file("a.bl".into(), "unit a; uses b;".into());
+        let result = Loader::new(&handler, &source_map, vec![dir]).load_unit_file(file);
+        // The `a` used by `c.bl`, which starts after `a.bl` and `b.bl`.
+        let span = Span {
+            start: BytePos(45),
+            end: BytePos(46),
+        };
+        assert_eq!(Err(Diag::CyclicUnitUse { span }), result.map(|_| ()));
+
+        let source_map = SourceMap::new();
+        let file = source_map.add_file("d.bl".into(), "unit d; uses b; let x: int;".into());
+        let dir = create_dir("unit-file-ok", &[("b.bl", "unit b;")]);
+        let loaded = Loader::new(&handler, &source_map, vec![dir])
+            .load_unit_file(file)
+            .unwrap();
+        assert_eq!(1, loaded.unit.decls.len());
+        assert_eq!(1, loaded.units.len());
+    }
+
+    #[test]
+    fn test_unit_not_found() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let source_map = SourceMap::new();
+        let file = source_map.add_file("main.bl".into(), "program p; uses nope;".into());
+        let result = Loader::new(&handler, &source_map, vec![]).load_program(file);
+        let span = Span {
+            start: BytePos(16),
+            end: BytePos(20),
+        };
+        assert_eq!(Err(Diag::UnitNotFound { span }), result.map(|_| ()));
+    }
+
+    #[test]
+    fn test_cyclic_units() {
+        let dir = create_dir(
+            "cycle",
+            &[("a.bl", "unit a; uses b;"), ("b.bl", "unit b; uses a;")],
+        );
+        let handler = errors::Handler::with_ignoring_emitter();
+        let source_map = SourceMap::new();
+        let file = source_map.add_file("main.bl".into(), "program p; uses a;".into());
+        let result = Loader::new(&handler, &source_map, vec![dir]).load_program(file);
+        // The `a` used by `b.bl`, which starts after `main.bl` and `a.bl`.
+        let span = Span {
+            start: BytePos(48),
+            end: BytePos(49),
+        };
+        assert_eq!(Err(Diag::CyclicUnitUse { span }), result.map(|_| ()));
+    }
+}
@@ -0,0 +1,922 @@
+use ast;
+use builtins::BUILTINS;
+use driver::{self, Diagnostic, Module, Root};
+use errors::Level;
+use json::Json;
+use scanner::KEYWORDS;
+use source_map::{BytePos, ColUnit, FileName, SourceFile, SourceMap, Span};
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+// Kinds of symbols and completion items, as numbered by the protocol.
+const SYMBOL_MODULE: usize = 2;
+const SYMBOL_VARIABLE: usize = 13;
+const SYMBOL_CONSTANT: usize = 14;
+const COMPLETION_FUNCTION: usize = 3;
+const COMPLETION_VARIABLE: usize = 6;
+const COMPLETION_MODULE: usize = 9;
+const COMPLETION_KEYWORD: usize = 14;
+const COMPLETION_CONSTANT: usize = 21;
+
+/// Runs a language server, reading messages from `input` and writing
+/// messages to `output` until the client asks it to exit.
+///
+/// Returns whether the client asked the server to shut down before exiting,
+/// which is how a session is meant to end.
+pub fn run<R: BufRead, W: Write>(mut input: R, output: W) -> io::Result<bool> {
+    let mut server = Server::new(output);
+    while let Some(content) = read_message(&mut input)? {
+        let message = match Json::parse(&content) {
+            Ok(message) => message,
+            Err(_) => {
+                server.send_error(Json::Null, PARSE_ERROR, "invalid JSON")?;
+                continue;
+            }
+        };
+        if message.get("method").and_then(Json::as_str) == Some("exit") {
+            return Ok(server.shutdown);
+        }
+        server.handle(&message)?;
+    }
+    Ok(false)
+}
+
+/// Reads the content of a message framed by a `Content-Length` header.
+/// Returns `None` once the input ends.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                let length = value.trim().parse::<usize>().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")
+                })?;
+                content_length = Some(length);
+            }
+        }
+    }
+
+    let mut content = vec![0; content_length.unwrap()];
+    input.read_exact(&mut content)?;
+    String::from_utf8(content)
+        .map(Some)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "message isn't UTF-8"))
+}
+
+/// Writes a message, framed by a `Content-Length` header.
+pub fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let content = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
+
+/// Converts a `file://` URI into a path.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = if bytes[i] == b'%' {
+            encoded
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+/// Converts an absolute path into a `file://` URI.
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for b in path.to_string_lossy().bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(b as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    uri
+}
+
+/// An error answering a request.
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+type Response = Result<Json, ResponseError>;
+
+/// What an identifier refers to.
+enum Symbol<'a> {
+    Program(&'a ast::Ident),
+    Unit(&'a ast::Ident),
+    Const(&'a ast::ConstDecl),
+    Var(&'a ast::Decl),
+}
+
+impl<'a> Symbol<'a> {
+    fn ident(&self) -> &'a ast::Ident {
+        match *self {
+            Symbol::Program(ident) | Symbol::Unit(ident) => ident,
+            Symbol::Const(decl) => &decl.ident,
+            Symbol::Var(decl) => &decl.ident,
+        }
+    }
+}
+
+/// Lookups of the names in a document, along with the units it uses.
+impl Module {
+    /// Returns the identifier found at a position of the document.
+    fn ident_at(&self, pos: BytePos) -> Option<&ast::Ident> {
+        let mut idents = vec![self.root.name()];
+        idents.extend(self.root.uses());
+        for decl in self.root.consts() {
+            idents.push(&decl.ident);
+            collect_expr_idents(&decl.expr, &mut idents);
+        }
+        idents.extend(self.root.decls().iter().map(|decl| &decl.ident));
+        for stmt in self.root.stmts() {
+            collect_stmt_idents(stmt, &mut idents);
+        }
+        idents
+            .into_iter()
+            .find(|ident| ident.span.start <= pos && pos <= ident.span.end)
+    }
+
+    /// Finds the declaration an identifier of the document refers to.
+    fn resolve(&self, ident: &ast::Ident) -> Option<Symbol<'_>> {
+        if ident == self.root.name() {
+            return Some(match self.root {
+                Root::Program(ref program) => Symbol::Program(&program.name),
+                Root::Unit(ref unit) => Symbol::Unit(&unit.name),
+            });
+        }
+        if self.root.uses().contains(ident) {
+            return self
+                .units
+                .iter()
+                .find(|unit| unit.name.name == ident.name)
+                .map(|unit| Symbol::Unit(&unit.name));
+        }
+        let scopes = Some((self.root.consts(), self.root.decls()))
+            .into_iter()
+            .chain(
+                self.units
+                    .iter()
+                    .map(|unit| (&unit.consts[..], &unit.decls[..])),
+            );
+        for (consts, decls) in scopes {
+            if let Some(decl) = consts.iter().find(|decl| decl.ident.name == ident.name) {
+                return Some(Symbol::Const(decl));
+            }
+            if let Some(decl) = decls.iter().find(|decl| decl.ident.name == ident.name) {
+                return Some(Symbol::Var(decl));
+            }
+        }
+        None
+    }
+
+    /// Describes a symbol as it's declared, e.g. `let x: int`.
+    fn describe(&self, symbol: &Symbol) -> String {
+        let name = self.ident_table.get(symbol.ident().name);
+        match *symbol {
+            Symbol::Program(_) => format!("program {}", name),
+            Symbol::Unit(_) => format!("unit {}", name),
+            Symbol::Const(decl) => format!("const {}: {}", name, decl.ty.name()),
+            Symbol::Var(decl) => format!("let {}: {}", name, decl.ty.name()),
+        }
+    }
+
+    /// Returns the completion items for the names declared by the document
+    /// and by the units it uses.
+    fn completion_items(&self) -> Vec<Json> {
+        let mut items = vec![];
+        let scopes = Some((self.root.consts(), self.root.decls()))
+            .into_iter()
+            .chain(
+                self.units
+                    .iter()
+                    .map(|unit| (&unit.consts[..], &unit.decls[..])),
+            );
+        for (consts, decls) in scopes {
+            for decl in consts {
+                items.push(completion_item(
+                    self.ident_table.get(decl.ident.name),
+                    COMPLETION_CONSTANT,
+                    Some(decl.ty.name()),
+                ));
+            }
+            for decl in decls {
+                items.push(completion_item(
+                    self.ident_table.get(decl.ident.name),
+                    COMPLETION_VARIABLE,
+                    Some(decl.ty.name()),
+                ));
+            }
+        }
+        for unit in &self.units {
+            items.push(completion_item(
+                self.ident_table.get(unit.name.name),
+                COMPLETION_MODULE,
+                None,
+            ));
+        }
+        items
+    }
+}
+
+fn collect_block_idents<'a>(block: &'a ast::Block, idents: &mut Vec<&'a ast::Ident>) {
+    for stmt in &block.stmts {
+        collect_stmt_idents(stmt, idents);
+    }
+}
+
+fn collect_stmt_idents<'a>(stmt: &'a ast::Stmt, idents: &mut Vec<&'a ast::Ident>) {
+    match stmt.kind {
+        ast::StmtKind::While(ref cond, ref block) => {
+            collect_expr_idents(cond, idents);
+            collect_block_idents(block, idents);
+        }
+        ast::StmtKind::For(ref ident, ref from, _, ref to, ref block) => {
+            idents.push(ident);
+            collect_expr_idents(from, idents);
+            collect_expr_idents(to, idents);
+            collect_block_idents(block, idents);
+        }
+        ast::StmtKind::Repeat(ref block, ref cond) => {
+            collect_block_idents(block, idents);
+            collect_expr_idents(cond, idents);
+        }
+        ast::StmtKind::Break | ast::StmtKind::Continue => {}
+        ast::StmtKind::If(ref cond, ref then_block, ref else_block) => {
+            collect_expr_idents(cond, idents);
+            collect_block_idents(then_block, idents);
+            if let Some(ref else_block) = *else_block {
+                collect_block_idents(else_block, idents);
+            }
+        }
+        ast::StmtKind::Case(ref expr, ref arms, ref else_block) => {
+            collect_expr_idents(expr, idents);
+            for arm in arms {
+                collect_block_idents(&arm.block, idents);
+            }
+            if let Some(ref else_block) = *else_block {
+                collect_block_idents(else_block, idents);
+            }
+        }
+        ast::StmtKind::Assign(ref ident, ref expr) => {
+            idents.push(ident);
+            collect_expr_idents(expr, idents);
+        }
+        ast::StmtKind::BlockStmt(ref block) => collect_block_idents(block, idents),
+        ast::StmtKind::Call(ref ident, ref args) => {
+            idents.push(ident);
+            for arg in args {
+                collect_expr_idents(arg, idents);
+            }
+        }
+    }
+}
+
+fn collect_expr_idents<'a>(expr: &'a ast::Expr, idents: &mut Vec<&'a ast::Ident>) {
+    match expr.kind {
+        ast::ExprKind::BinaryOp(_, ref lhs, ref rhs) => {
+            collect_expr_idents(lhs, idents);
+            collect_expr_idents(rhs, idents);
+        }
+        ast::ExprKind::UnaryOp(_, ref operand) => collect_expr_idents(operand, idents),
+        ast::ExprKind::Lit(_) => {}
+        ast::ExprKind::Ident(ref ident) => idents.push(ident),
+        ast::ExprKind::Paren(ref inner) | ast::ExprKind::Cast(_, ref inner) => {
+            collect_expr_idents(inner, idents)
+        }
+        ast::ExprKind::Call(ref ident, ref args) => {
+            idents.push(ident);
+            for arg in args {
+                collect_expr_idents(arg, idents);
+            }
+        }
+        ast::ExprKind::Formatted(ref value, ref width, ref prec) => {
+            collect_expr_idents(value, idents);
+            collect_expr_idents(width, idents);
+            if let Some(ref prec) = *prec {
+                collect_expr_idents(prec, idents);
+            }
+        }
+    }
+}
+
+fn completion_item(label: &str, kind: usize, detail: Option<&str>) -> Json {
+    let mut members = vec![("label", Json::from(label)), ("kind", Json::from(kind))];
+    if let Some(detail) = detail {
+        members.push(("detail", Json::from(detail)));
+    }
+    Json::object(members)
+}
+
+/// The result of checking a document.
+struct Analysis {
+    /// The source map holding the document and the units it uses.
+    source_map: Rc<SourceMap>,
+    file: Rc<SourceFile>,
+    /// The document's program or unit, if it could be parsed.
+    module: Option<Module>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    /// Parses and type checks a document. Units are loaded from the file
+    /// system, next to the document and then in the directories listed by
+    /// `BLAISE_PATH`, just as the compiler does.
+    fn new(uri: &str, text: &str) -> Analysis {
+        let path = uri_to_path(uri);
+        let name = path
+            .as_ref()
+            .map_or_else(|| uri.to_owned(), |path| path.display().to_string());
+        let mut search_path = path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(Path::to_path_buf)
+            .into_iter()
+            .collect::<Vec<_>>();
+        if let Some(paths) = env::var_os("BLAISE_PATH") {
+            search_path.extend(env::split_paths(&paths));
+        }
+
+        let options = driver::Options {
+            search_path,
+            keep_ill_typed: true,
+            ..driver::Options::default()
+        };
+        let compilation = driver::compile_str(&name, text, &options);
+        Analysis {
+            source_map: compilation.source_map,
+            file: compilation.file,
+            module: compilation.module,
+            diagnostics: compilation.diagnostics,
+        }
+    }
+}
+
+/// A document opened by the client.
+struct Document {
+    file: SourceFile,
+    analysis: Analysis,
+    /// The completion items for the names declared by the last version of
+    /// the document which could be parsed, kept so that names can still be
+    /// completed while a statement is being typed.
+    completion_items: Vec<Json>,
+}
+
+/// The language server.
+///
+/// It keeps the documents opened by the client, checking each one as it
+/// changes. Units used by a document are always read from the file system,
+/// even if they're opened as well.
+struct Server<W> {
+    output: W,
+    documents: HashMap<String, Document>,
+    initialized: bool,
+    shutdown: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn new(output: W) -> Server<W> {
+        Server {
+            output,
+            documents: HashMap::new(),
+            initialized: false,
+            shutdown: false,
+        }
+    }
+
+    fn handle(&mut self, message: &Json) -> io::Result<()> {
+        let method = match message.get("method").and_then(Json::as_str) {
+            Some(method) => method,
+            // The server sends no requests, so there are no responses to
+            // handle.
+            None => return Ok(()),
+        };
+        let params = message.get("params").unwrap_or(&Json::Null);
+        match message.get("id") {
+            Some(id) => {
+                let response = self.handle_request(method, params);
+                match response {
+                    Ok(result) => self.send(Json::object(vec![
+                        ("jsonrpc", Json::from("2.0")),
+                        ("id", id.clone()),
+                        ("result", result),
+                    ])),
+                    Err(err) => self.send_error(id.clone(), err.code, &err.message),
+                }
+            }
+            None if self.initialized && !self.shutdown => self.handle_notification(method, params),
+            None => Ok(()),
+        }
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Json) -> Response {
+        if method == "initialize" {
+            self.initialized = true;
+            return Ok(Json::object(vec![
+                (
+                    "capabilities",
+                    Json::object(vec![
+                        (
+                            "textDocumentSync",
+                            Json::object(vec![
+                                ("openClose", Json::from(true)),
+                                // Incremental changes.
+                                ("change", Json::from(2)),
+                            ]),
+                        ),
+                        ("hoverProvider", Json::from(true)),
+                        ("definitionProvider", Json::from(true)),
+                        ("documentSymbolProvider", Json::from(true)),
+                        ("completionProvider", Json::object(vec![])),
+                    ]),
+                ),
+                (
+                    "serverInfo",
+                    Json::object(vec![("name", Json::from("blaise-lsp"))]),
+                ),
+            ]));
+        }
+        if !self.initialized {
+            return Err(ResponseError {
+                code: SERVER_NOT_INITIALIZED,
+                message: "server not initialized".to_owned(),
+            });
+        }
+        if self.shutdown {
+            return Err(ResponseError {
+                code: INVALID_REQUEST,
+                message: "server is shutting down".to_owned(),
+            });
+        }
+        match method {
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Json::Null)
+            }
+            "textDocument/hover" => Ok(self.hover(params).unwrap_or(Json::Null)),
+            "textDocument/definition" => Ok(self.definition(params).unwrap_or(Json::Null)),
+            "textDocument/documentSymbol" => {
+                Ok(self.document_symbols(params).unwrap_or(Json::Null))
+            }
+            "textDocument/completion" => {
+                Ok(Json::Array(self.completion(params).unwrap_or_default()))
+            }
+            _ => Err(ResponseError {
+                code: METHOD_NOT_FOUND,
+                message: format!("unknown method `{}`", method),
+            }),
+        }
+    }
+
+    /// Handles a notification. Malformed notifications are ignored, as
+    /// there's no way to answer them.
+    fn handle_notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        let uri = match params
+            .get("textDocument")
+            .and_then(|doc| doc.get("uri"))
+            .and_then(Json::as_str)
+        {
+            Some(uri) => uri.to_owned(),
+            None => return Ok(()),
+        };
+        match method {
+            "textDocument/didOpen" => {
+                let text = match params
+                    .get("textDocument")
+                    .and_then(|doc| doc.get("text"))
+                    .and_then(Json::as_str)
+                {
+                    Some(text) => text,
+                    None => return Ok(()),
+                };
+                let file = SourceFile::new(FileName::Virtual(uri.clone()), text.to_owned());
+                let analysis = Analysis::new(&uri, text);
+                self.documents.insert(
+                    uri.clone(),
+                    Document {
+                        file,
+                        analysis,
+                        completion_items: vec![],
+                    },
+                );
+                self.update(&uri)
+            }
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges").and_then(Json::as_array);
+                match (self.documents.get_mut(&uri), changes) {
+                    (Some(doc), Some(changes)) => {
+                        for change in changes {
+                            apply_change(&mut doc.file, change);
+                        }
+                        doc.analysis = Analysis::new(&uri, &doc.file.src);
+                    }
+                    _ => return Ok(()),
+                }
+                self.update(&uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish_diagnostics(&uri, vec![])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Publishes the diagnostics of a document which was just checked.
+    fn update(&mut self, uri: &str) -> io::Result<()> {
+        let doc = self.documents.get_mut(uri).unwrap();
+        if let Some(ref module) = doc.analysis.module {
+            doc.completion_items = module.completion_items();
+        }
+        let file = doc.analysis.file.clone();
+        // Diagnostics of the units used by the document are left to be
+        // published when they're opened.
+        let diagnostics = doc
+            .analysis
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.diag.location() <= file.end_pos())
+            .map(|&Diagnostic { ref diag, level }| {
+                let pos = diag.location();
+                let severity = match level {
+                    Level::Error => 1,
+                    Level::Warning => 2,
+                    Level::Note => 3,
+                    Level::Help => 4,
+                };
+                Json::object(vec![
+                    (
+                        "range",
+                        lsp_range(
+                            &file,
+                            Span {
+                                start: pos,
+                                end: pos,
+                            },
+                        ),
+                    ),
+                    ("severity", Json::from(severity)),
+                    ("code", Json::from(diag.code())),
+                    ("source", Json::from("blaise")),
+                    ("message", Json::from(diag.to_string())),
+                ])
+            })
+            .collect();
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Json>) -> io::Result<()> {
+        self.send(Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("method", Json::from("textDocument/publishDiagnostics")),
+            (
+                "params",
+                Json::object(vec![
+                    ("uri", Json::from(uri)),
+                    ("diagnostics", Json::Array(diagnostics)),
+                ]),
+            ),
+        ]))
+    }
+
+    /// Returns the document and the position a request refers to.
+    fn document_position(&self, params: &Json) -> Option<(&Document, BytePos)> {
+        let doc = self.document(params)?;
+        let position = params.get("position")?;
+        let line = position.get("line")?.as_usize()?;
+        let character = position.get("character")?.as_usize()?;
+        let pos = doc.file.lookup_pos(line + 1, character, ColUnit::Utf16)?;
+        Some((doc, pos))
+    }
+
+    fn document(&self, params: &Json) -> Option<&Document> {
+        let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+        self.documents.get(uri)
+    }
+
+    fn hover(&self, params: &Json) -> Option<Json> {
+        let (doc, pos) = self.document_position(params)?;
+        let module = doc.analysis.module.as_ref()?;
+        let ident = module.ident_at(pos)?;
+        let symbol = module.resolve(ident)?;
+        Some(Json::object(vec![
+            (
+                "contents",
+                Json::object(vec![
+                    ("kind", Json::from("plaintext")),
+                    ("value", Json::from(module.describe(&symbol))),
+                ]),
+            ),
+            ("range", lsp_range(&doc.analysis.file.clone(), ident.span)),
+        ]))
+    }
+
+    fn definition(&self, params: &Json) -> Option<Json> {
+        let (doc, pos) = self.document_position(params)?;
+        let module = doc.analysis.module.as_ref()?;
+        let symbol = module.resolve(module.ident_at(pos)?)?;
+        let span = symbol.ident().span;
+        let file = doc.analysis.source_map.lookup_file(span.start)?;
+        let uri = if file.start_pos() == BytePos(0) {
+            params.get("textDocument")?.get("uri")?.as_str()?.to_owned()
+        } else {
+            match *file.name() {
+                FileName::Real(ref path) if path.is_absolute() => path_to_uri(path),
+                FileName::Real(ref path) => path_to_uri(&env::current_dir().ok()?.join(path)),
+                FileName::Virtual(_) => return None,
+            }
+        };
+        Some(Json::object(vec![
+            ("uri", Json::from(uri)),
+            ("range", lsp_range(&file, span)),
+        ]))
+    }
+
+    fn document_symbols(&self, params: &Json) -> Option<Json> {
+        let doc = self.document(params)?;
+        let module = doc.analysis.module.as_ref()?;
+        let file = doc.analysis.file.clone();
+        let symbol = |ident: &ast::Ident, kind: usize, detail: &str| {
+            let range = lsp_range(&file, ident.span);
+            Json::object(vec![
+                ("name", Json::from(module.ident_table.get(ident.name))),
+                ("detail", Json::from(detail)),
+                ("kind", Json::from(kind)),
+                ("range", range.clone()),
+                ("selectionRange", range),
+            ])
+        };
+
+        let mut children = vec![];
+        for decl in module.root.consts() {
+            children.push(symbol(&decl.ident, SYMBOL_CONSTANT, decl.ty.name()));
+        }
+        for decl in module.root.decls() {
+            children.push(symbol(&decl.ident, SYMBOL_VARIABLE, decl.ty.name()));
+        }
+        let name = module.root.name();
+        let detail = match module.root {
+            Root::Program(_) => "program",
+            Root::Unit(_) => "unit",
+        };
+        let whole_file = Span {
+            start: file.start_pos(),
+            end: file.end_pos(),
+        };
+        Some(Json::Array(vec![Json::object(vec![
+            ("name", Json::from(module.ident_table.get(name.name))),
+            ("detail", Json::from(detail)),
+            ("kind", Json::from(SYMBOL_MODULE)),
+            ("range", lsp_range(&file, whole_file)),
+            ("selectionRange", lsp_range(&file, name.span)),
+            ("children", Json::Array(children)),
+        ])]))
+    }
+
+    fn completion(&self, params: &Json) -> Option<Vec<Json>> {
+        let doc = self.document(params)?;
+        let mut items = KEYWORDS
+            .iter()
+            .map(|&(keyword, _)| completion_item(keyword, COMPLETION_KEYWORD, None))
+            .collect::<Vec<_>>();
+        items.extend(
+            BUILTINS
+                .iter()
+                .map(|builtin| completion_item(builtin.name(), COMPLETION_FUNCTION, None)),
+        );
+        items.extend(doc.completion_items.iter().cloned());
+        Some(items)
+    }
+
+    fn send(&mut self, message: Json) -> io::Result<()> {
+        write_message(&mut self.output, &message)
+    }
+
+    fn send_error(&mut self, id: Json, code: i64, message: &str) -> io::Result<()> {
+        self.send(Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("id", id),
+            (
+                "error",
+                Json::object(vec![
+                    ("code", Json::Number(code as f64)),
+                    ("message", Json::from(message)),
+                ]),
+            ),
+        ]))
+    }
+}
+
+/// Applies a change sent by the client to a document, either replacing a
+/// range of it or, if there's no range, the whole text.
+fn apply_change(file: &mut SourceFile, change: &Json) {
+    let text = match change.get("text").and_then(Json::as_str) {
+        Some(text) => text,
+        None => return,
+    };
+    let pos = |position: &Json| {
+        let line = position.get("line")?.as_usize()?;
+        let character = position.get("character")?.as_usize()?;
+        // Positions past the last line refer to the end of the document.
+        Some(
+            file.lookup_pos(line + 1, character, ColUnit::Utf16)
+                .unwrap_or_else(|| file.end_pos()),
+        )
+    };
+    let span = change.get("range").and_then(|range| {
+        Some(Span {
+            start: pos(range.get("start")?)?,
+            end: pos(range.get("end")?)?,
+        })
+    });
+    match span {
+        Some(span) => file.apply_edit(span, text),
+        None => *file = SourceFile::new(file.name().clone(), text.to_owned()),
+    }
+}
+
+/// Converts a position into a line and a character, counted in UTF-16 code
+/// units, as the protocol does.
+fn lsp_position(file: &SourceFile, pos: BytePos) -> Json {
+    let (line, character) = match file.lookup_source_location(pos) {
+        Some(loc) => (loc.line - 1, loc.col_utf16),
+        // The end of the file has no location of its own.
+        None => {
+            let text = &file.src[..(pos - file.start_pos()).0.min(file.src.len())];
+            let line_start = text.rfind('\n').map_or(0, |i| i + 1);
+            (
+                text.matches('\n').count(),
+                text[line_start..].encode_utf16().count(),
+            )
+        }
+    };
+    Json::object(vec![
+        ("line", Json::from(line)),
+        ("character", Json::from(character)),
+    ])
+}
+
+fn lsp_range(file: &SourceFile, span: Span) -> Json {
+    Json::object(vec![
+        ("start", lsp_position(file, span.start)),
+        ("end", lsp_position(file, span.end)),
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::{path_to_uri, read_message, run, uri_to_path};
+    use json::Json;
+    use std::io::Cursor;
+    use std::path::Path;
+
+    fn frame(messages: &[&str]) -> Vec<u8> {
+        messages
+            .iter()
+            .map(|content| format!("Content-Length: {}\r\n\r\n{}", content.len(), content))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    /// Runs a session with the given messages, returning whether it ended
+    /// properly and the messages sent by the server.
+    fn run_session(messages: &[&str]) -> (bool, Vec<Json>) {
+        let mut output = vec![];
+        let shutdown = run(Cursor::new(frame(messages)), &mut output).unwrap();
+        let mut output = Cursor::new(output);
+        let mut responses = vec![];
+        while let Some(content) = read_message(&mut output).unwrap() {
+            responses.push(Json::parse(&content).unwrap());
+        }
+        (shutdown, responses)
+    }
+
+    #[test]
+    fn test_read_message() {
+        let mut input = Cursor::new(
+            "Content-Type: x\r\ncontent-length: 4\r\n\r\n\"é\"Content-Length: 2\r\n\r\n{}"
+                .as_bytes(),
+        );
+        assert_eq!(Some("\"é\"".to_owned()), read_message(&mut input).unwrap());
+        assert_eq!(Some("{}".to_owned()), read_message(&mut input).unwrap());
+        assert_eq!(None, read_message(&mut input).unwrap());
+    }
+
+    #[test]
+    fn test_uri_conversion() {
+        let path = Path::new("/home/me/my progs/ação.bl");
+        let uri = path_to_uri(path);
+        assert_eq!("file:///home/me/my%20progs/a%C3%A7%C3%A3o.bl", uri);
+        assert_eq!(Some(path.to_path_buf()), uri_to_path(&uri));
+        assert_eq!(None, uri_to_path("untitled:1"));
+    }
+
+    #[test]
+    fn test_incremental_changes() {
+        let (shutdown, responses) = run_session(&[
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":
+                {"uri":"untitled:a","languageId":"blaise","version":1,
+                 "text":"program p;\nlet x: int;\nwrite(\"😀\"); x = 1.5;"}}}"#,
+            r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":
+                {"uri":"untitled:a","version":2},"contentChanges":[
+                {"range":{"start":{"line":1,"character":7},"end":{"line":1,"character":10}},
+                 "text":"float"}]}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{
+                "textDocument":{"uri":"untitled:a"},"position":{"line":2,"character":14}}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit"}"#,
+        ]);
+        assert!(shutdown);
+        assert_eq!(5, responses.len());
+
+        let diagnostics = |response: &Json| {
+            response
+                .get("params")
+                .and_then(|params| params.get("diagnostics"))
+                .and_then(Json::as_array)
+                .map(<[Json]>::len)
+        };
+        assert_eq!(Some(1), diagnostics(&responses[1]));
+        assert_eq!(Some(0), diagnostics(&responses[2]));
+
+        let hover = responses[3].get("result").unwrap();
+        assert_eq!(
+            Some("let x: float"),
+            hover
+                .get("contents")
+                .and_then(|contents| contents.get("value"))
+                .and_then(Json::as_str)
+        );
+        assert_eq!(
+            "{\"start\":{\"line\":2,\"character\":13},\"end\":{\"line\":2,\"character\":14}}",
+            hover.get("range").unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_request_errors() {
+        let (shutdown, responses) = run_session(&[
+            r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"nope"}"#,
+            r#"{"jsonrpc":"2.0","id":4,"#,
+        ]);
+        assert!(!shutdown);
+        let code = |response: &Json| {
+            response
+                .get("error")
+                .and_then(|error| error.get("code"))
+                .and_then(Json::as_f64)
+        };
+        assert_eq!(Some(-32002.0), code(&responses[0]));
+        assert_eq!(None, code(&responses[1]));
+        assert_eq!(Some(-32601.0), code(&responses[2]));
+        assert_eq!(Some(-32700.0), code(&responses[3]));
+        assert_eq!(Some(&Json::Null), responses[3].get("id"));
+    }
+}
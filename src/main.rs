@@ -1,36 +1,669 @@
+extern crate blaise;
+
 use std::env;
 use std::fs;
-use std::rc::Rc;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+use blaise::driver::{self, Compilation, Diagnostic, Emit, Input, Module};
+use blaise::error_codes::{self, ErrorCode};
+use blaise::errors::{Diag, Level, LevelConfig};
+use blaise::interp::Limits;
+use blaise::repl;
+use blaise::source_map::SourceMap;
+
+const USAGE: &str = "\
+Usage: blaise [command] [options] <file>
+
+Commands:
+    run <file>     checks and runs a program
+    check <file>   checks a program or unit for errors
+    build <file>   checks a program or unit, then writes what --emit asks for
+    tokens <file>  writes the words of a file, like build --emit=tokens
+    ast <file>     writes the syntax tree of a file, like build --emit=ast,
+                   the default command
+    cst <file>     writes the concrete syntax tree of a file, comments and
+                   whitespace included, like build --emit=cst
+    fmt <files>    formats files in place
+    fix <files>    applies the fixes suggested for files in place
+    repl           starts an interactive session
+
+Options:
+    --emit=<kind>  what build writes: tokens, ast (the default), cst or ir,
+                   the lowered program; c and asm are out of scope, as blaise
+                   has no code generator
+    -o <path>      writes the output of build, tokens, ast or cst to a file
+    --check        makes fmt report unformatted files rather than format them
+    --error-format=<format>
+                   how diagnostics are written: human (the default) or json,
+                   one object per line
+    -D warnings    reports every warning as an error
+    -D <code>      reports the warnings with a code, e.g. B0012 or
+                   non-exhaustive-case, as errors
+    -A <code>      doesn't report the warnings with a code
+    --max-errors=<n>
+                   stops reporting errors after n of them, 50 by default
+    --max-steps=<n>
+                   makes run stop a program after n statements or times
+                   around loops
+    --max-depth=<n>
+                   makes run stop a program nesting blocks and calls deeper
+                   than n
+    --max-memory=<bytes>
+                   makes run stop a program whose variables, along with the
+                   string being built, hold more bytes of strings
+    --timeout=<seconds>
+                   makes run stop a program after so many seconds
+    --explain <code>
+                   explains the diagnostics with a code
+    -h, --help     shows this message
+    -V, --version  shows the version
+
+A <file> named `-` is read from the standard input.
+";
+
+/// The exit code of a command which failed because of errors in the code
+/// given to it, or of I/O errors.
+const EXIT_FAILURE: i32 = 1;
+/// The exit code of an invalid command line.
+const EXIT_USAGE: i32 = 2;
+
+/// The options setting limits on the programs run, which take a value.
+const LIMIT_OPTIONS: [&str; 4] = ["--max-steps", "--max-depth", "--max-memory", "--timeout"];
+
+/// How many errors are shown before the rest are dropped, unless
+/// `--max-errors` says otherwise.
+const DEFAULT_MAX_ERRORS: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Run,
+    Check,
+    Build,
+    Tokens,
+    Ast,
+    Cst,
+    Fmt,
+    Fix,
+    Repl,
+    Explain,
+    Help,
+    Version,
+}
+
+/// The commands named on the command line.
+const COMMANDS: [(&str, Command); 9] = [
+    ("run", Command::Run),
+    ("check", Command::Check),
+    ("build", Command::Build),
+    ("tokens", Command::Tokens),
+    ("ast", Command::Ast),
+    ("cst", Command::Cst),
+    ("fmt", Command::Fmt),
+    ("fix", Command::Fix),
+    ("repl", Command::Repl),
+];
 
-use parser::Parser;
-use scanner::{Scanner, WordStream};
-use source_map::{Loc, SourceFile};
+/// How diagnostics are written to the standard error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
 
-pub mod ast;
-pub mod errors;
-pub mod parser;
-pub mod scanner;
-pub mod source_map;
+struct Options {
+    command: Command,
+    emit: Emit,
+    output: Option<PathBuf>,
+    check: bool,
+    error_format: ErrorFormat,
+    levels: LevelConfig,
+    max_errors: usize,
+    limits: Limits,
+    explain: Option<&'static ErrorCode>,
+    files: Vec<String>,
+}
 
 fn main() {
-    let mut args = env::args();
-    args.next();
-    let path = args.next().unwrap();
-    let src = fs::read_to_string(path).unwrap();
-    let file = Rc::new(SourceFile::new("test".into(), src.into()));
-    let scanner = Scanner::new(file.clone());
-    let handler = errors::Handler::with_emitter(move |diag| {
-        let Loc { line, col } = file.lookup_source_location(diag.location()).unwrap();
-        println!("{}:{}: error: {}", line, col.0, diag);
-        true
-    });
-    let word_stream = WordStream::new(scanner, &handler);
-    let mut parser = Parser::new(word_stream);
-
-    match parser.parse_program() {
-        Ok(program) => println!("{:#?}", program),
-        Err(diag) => {
-            handler.report(diag);
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let code = match parse_args(&args) {
+        Ok(options) => execute(&options),
+        Err(message) => {
+            eprintln!("error: {}", message);
+            eprintln!("Run `blaise --help` to see the usage.");
+            EXIT_USAGE
+        }
+    };
+    process::exit(code);
+}
+
+/// Parses the command line, returning the reason it's invalid otherwise.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut command = None;
+    let mut emit = None;
+    let mut output = None;
+    let mut check = false;
+    let mut error_format = ErrorFormat::Human;
+    let mut levels = LevelConfig::default();
+    let mut max_errors = DEFAULT_MAX_ERRORS;
+    let mut limits = Limits::default();
+    let mut explain = None;
+    let mut files = vec![];
+    let mut is_default_command = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let limit_option = LIMIT_OPTIONS
+            .iter()
+            .find(|&&option| arg == option || arg.starts_with(&format!("{}=", option)));
+        if let Some(&option) = limit_option {
+            let value = if arg == option {
+                args.next()
+                    .ok_or_else(|| format!("`{}` needs a number", option))?
+            } else {
+                &arg[option.len() + 1..]
+            };
+            match option {
+                "--max-steps" => limits.max_steps = Some(parse_positive(option, value)?),
+                "--max-depth" => limits.max_depth = Some(parse_positive(option, value)?),
+                "--max-memory" => limits.max_memory = Some(parse_positive(option, value)?),
+                _ => limits.timeout = Some(parse_timeout(value)?),
+            }
+            continue;
+        }
+        match arg.as_str() {
+            "-h" | "--help" => command = Some(Command::Help),
+            "-V" | "--version" => command = Some(Command::Version),
+            "--check" => check = true,
+            "-o" => {
+                let path = args.next().ok_or("`-o` needs a path")?;
+                output = Some(PathBuf::from(path));
+            }
+            "--emit" => emit = Some(parse_emit(args.next().ok_or("`--emit` needs a kind")?)?),
+            _ if arg.starts_with("--emit=") => emit = Some(parse_emit(&arg["--emit=".len()..])?),
+            "--error-format" => {
+                error_format =
+                    parse_error_format(args.next().ok_or("`--error-format` needs a format")?)?
+            }
+            _ if arg.starts_with("--error-format=") => {
+                error_format = parse_error_format(&arg["--error-format=".len()..])?
+            }
+            "-D" => match args
+                .next()
+                .ok_or("`-D` needs a code or `warnings`")?
+                .as_str()
+            {
+                "warnings" => levels.deny_warnings = true,
+                code => levels.denied.push(parse_code(code)?.code),
+            },
+            "-A" => {
+                let error_code = parse_code(args.next().ok_or("`-A` needs a code")?)?;
+                if error_code.level != Level::Warning {
+                    return Err(format!(
+                        "`{}` isn't a warning, so it can't be allowed",
+                        error_code.code
+                    ));
+                }
+                levels.allowed.push(error_code.code);
+            }
+            "--max-errors" => {
+                let count = args.next().ok_or("`--max-errors` needs a number")?;
+                max_errors = parse_positive("--max-errors", count)?
+            }
+            _ if arg.starts_with("--max-errors=") => {
+                max_errors = parse_positive("--max-errors", &arg["--max-errors=".len()..])?
+            }
+            "--explain" => {
+                explain = Some(parse_code(args.next().ok_or("`--explain` needs a code")?)?);
+                command = Some(Command::Explain);
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option `{}`", arg))
+            }
+            _ if command.is_none() && files.is_empty() => {
+                command = Some(match COMMANDS.iter().find(|&&(name, _)| name == arg) {
+                    Some(&(_, command)) => command,
+                    None => {
+                        // `blaise <file>` dumps the syntax tree of the file.
+                        files.push(arg.clone());
+                        is_default_command = true;
+                        Command::Ast
+                    }
+                });
+            }
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    let command = command.ok_or("no file given")?;
+    match command {
+        Command::Explain | Command::Help | Command::Version => {}
+        Command::Repl if !files.is_empty() => return Err("`repl` takes no files".to_owned()),
+        Command::Repl => {}
+        Command::Fmt | Command::Fix if files.is_empty() => return Err("no file given".to_owned()),
+        Command::Fmt | Command::Fix => {}
+        _ if files.is_empty() => return Err("no file given".to_owned()),
+        // `blaise bulid p.bl` is more likely a misspelled command than two
+        // files.
+        _ if is_default_command && files.len() > 1 => {
+            let names = COMMANDS
+                .iter()
+                .map(|&(name, _)| format!("`{}`", name))
+                .collect::<Vec<_>>();
+            return Err(format!(
+                "unknown command `{}`, expected one of {}",
+                files[0],
+                names.join(", ")
+            ));
+        }
+        _ if files.len() > 1 => return Err(format!("unexpected argument `{}`", files[1])),
+        _ => {}
+    }
+    if emit.is_some() && command != Command::Build {
+        return Err("`--emit` only applies to `build`".to_owned());
+    }
+    let emits_output = matches!(
+        command,
+        Command::Build | Command::Tokens | Command::Ast | Command::Cst
+    );
+    if output.is_some() && !emits_output {
+        return Err("`-o` only applies to `build`, `tokens`, `ast` and `cst`".to_owned());
+    }
+    if error_format != ErrorFormat::Human && command == Command::Repl {
+        return Err("`--error-format` doesn't apply to `repl`".to_owned());
+    }
+    if check && command != Command::Fmt {
+        return Err("`--check` only applies to `fmt`".to_owned());
+    }
+    if limits != Limits::default() && command != Command::Run {
+        return Err(
+            "`--max-steps`, `--max-depth`, `--max-memory` and `--timeout` only apply to `run`"
+                .to_owned(),
+        );
+    }
+
+    let emit = match command {
+        Command::Tokens => Emit::Tokens,
+        Command::Cst => Emit::Cst,
+        _ => emit.unwrap_or(Emit::Ast),
+    };
+    Ok(Options {
+        command,
+        emit,
+        output,
+        check,
+        error_format,
+        levels,
+        max_errors,
+        limits,
+        explain,
+        files,
+    })
+}
+
+fn parse_emit(kind: &str) -> Result<Emit, String> {
+    match kind {
+        "tokens" => Ok(Emit::Tokens),
+        "ast" => Ok(Emit::Ast),
+        "cst" => Ok(Emit::Cst),
+        "ir" => Ok(Emit::Ir),
+        "c" | "asm" => Err(format!(
+            "`--emit={}` is out of scope, as blaise has no code generator",
+            kind
+        )),
+        _ => Err(format!(
+            "unknown `--emit` kind `{}`, expected `tokens`, `ast`, `cst` or `ir`",
+            kind
+        )),
+    }
+}
+
+fn parse_error_format(format: &str) -> Result<ErrorFormat, String> {
+    match format {
+        "human" => Ok(ErrorFormat::Human),
+        "json" => Ok(ErrorFormat::Json),
+        _ => Err(format!(
+            "unknown `--error-format` `{}`, expected `human` or `json`",
+            format
+        )),
+    }
+}
+
+/// Parses the value of an option which has to be a positive integer.
+fn parse_positive<T: FromStr + Default + PartialOrd>(
+    option: &str,
+    value: &str,
+) -> Result<T, String> {
+    match value.parse() {
+        Ok(value) if value > T::default() => Ok(value),
+        _ => Err(format!(
+            "invalid `{}` `{}`, expected a positive number",
+            option, value
+        )),
+    }
+}
+
+/// Parses the seconds given to `--timeout`, which may have a fraction.
+fn parse_timeout(secs: &str) -> Result<Duration, String> {
+    match secs.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!(
+            "invalid `--timeout` `{}`, expected a positive number of seconds",
+            secs
+        )),
+    }
+}
+
+/// Finds a diagnostic code, given either as a code or by its name.
+fn parse_code(code: &str) -> Result<&'static ErrorCode, String> {
+    error_codes::lookup(code).ok_or_else(|| format!("unknown diagnostic code `{}`", code))
+}
+
+/// Runs a command, returning its exit code.
+fn execute(options: &Options) -> i32 {
+    match options.command {
+        Command::Help => {
+            print!("{}", USAGE);
+            0
+        }
+        Command::Version => {
+            println!("blaise {}", env!("CARGO_PKG_VERSION"));
+            0
+        }
+        Command::Explain => {
+            let error_code = options.explain.unwrap();
+            print!(
+                "{} ({})\n\n{}",
+                error_code.code, error_code.name, error_code.explanation
+            );
+            0
+        }
+        Command::Repl => {
+            // Both the REPL and the code it runs read lines from stdin, so
+            // neither may buffer more than it reads.
+            let input = BufReader::with_capacity(1, io::stdin());
+            let program_input = BufReader::with_capacity(1, io::stdin());
+            match repl::run(input, program_input, io::stdout()) {
+                Ok(()) => 0,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    EXIT_FAILURE
+                }
+            }
+        }
+        Command::Fmt => format_files(options),
+        Command::Fix => fix_files(options),
+        Command::Run | Command::Check | Command::Build => {
+            let compilation = match compile(options) {
+                Some(compilation) => compilation,
+                None => return EXIT_FAILURE,
+            };
+            let module = match compilation.module {
+                Some(ref module) => module,
+                None => return EXIT_FAILURE,
+            };
+            match options.command {
+                Command::Run => run(options, &compilation, module),
+                Command::Check => 0,
+                _ => match driver::emit(&compilation, options.emit) {
+                    Some(text) => exit_code(write_output(options, &text)),
+                    None => EXIT_FAILURE,
+                },
+            }
+        }
+        _ => {
+            let driver_options = driver_options(options);
+            let output = match with_input(&options.files[0], |input| {
+                driver::dump(input, options.emit, &driver_options)
+            }) {
+                Some(output) => output,
+                None => return EXIT_FAILURE,
+            };
+            write_diagnostics(
+                options.error_format,
+                &output.diagnostics,
+                &output.source_map,
+            );
+            match output.text {
+                Some(ref text) => exit_code(write_output(options, text) && !output.has_errors()),
+                None => EXIT_FAILURE,
+            }
+        }
+    }
+}
+
+/// The options of the driver, in which units are looked for next to the
+/// file compiled, then in the directories listed by `BLAISE_PATH`.
+fn driver_options(options: &Options) -> driver::Options {
+    let mut driver_options = driver::Options {
+        levels: options.levels.clone(),
+        max_errors: Some(options.max_errors),
+        ..driver::Options::default()
+    };
+    if let Some(paths) = env::var_os("BLAISE_PATH") {
+        driver_options.search_path.extend(env::split_paths(&paths));
+    }
+    driver_options
+}
+
+/// Gives the file at a path, or the standard input for `-`, to the driver,
+/// writing why it couldn't be read otherwise.
+fn with_input<T, F>(path: &str, f: F) -> Option<T>
+where
+    F: FnOnce(Input) -> io::Result<T>,
+{
+    let result = if path == "-" {
+        read_stdin().and_then(|src| {
+            f(Input::Str {
+                name: "stdin",
+                src: &src,
+            })
+        })
+    } else {
+        f(Input::File(Path::new(path)))
+    };
+    result.map_err(|err| report_read_error(path, &err)).ok()
+}
+
+/// Compiles the file given, or the standard input for `-`, writing its
+/// diagnostics. Returns `None` if the file couldn't be read.
+fn compile(options: &Options) -> Option<Compilation> {
+    let path = &options.files[0];
+    let mut driver_options = driver_options(options);
+    if path == "-" {
+        driver_options.search_path.insert(0, PathBuf::from("."));
+    }
+    let compilation = with_input(path, |input| match input {
+        Input::File(path) => driver::check_file(path, &driver_options),
+        Input::Str { name, src } => Ok(driver::compile_str(name, src, &driver_options)),
+    })?;
+    write_diagnostics(
+        options.error_format,
+        &compilation.diagnostics,
+        &compilation.source_map,
+    );
+    Some(compilation)
+}
+
+/// Runs a compiled program on the standard input and output.
+fn run(options: &Options, compilation: &Compilation, module: &Module) -> i32 {
+    if module.program().is_none() {
+        eprintln!(
+            "error: `{}` holds a unit, which can't be run",
+            compilation.file.name()
+        );
+        return EXIT_FAILURE;
+    }
+    let stdin = io::stdin();
+    match driver::run_program_with_limits(module, options.limits, stdin.lock(), io::stdout()) {
+        Ok(()) => 0,
+        Err(err) => {
+            write_diag(
+                options.error_format,
+                &err.into(),
+                Level::Error,
+                &compilation.source_map,
+            );
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Writes diagnostics to the standard error, in the format asked for.
+fn write_diagnostics(
+    error_format: ErrorFormat,
+    diagnostics: &[Diagnostic],
+    source_map: &SourceMap,
+) {
+    for diagnostic in diagnostics {
+        write_diag(error_format, &diagnostic.diag, diagnostic.level, source_map);
+    }
+}
+
+/// Writes a diagnostic to the standard error, in the format asked for.
+fn write_diag(error_format: ErrorFormat, diag: &Diag, level: Level, source_map: &SourceMap) {
+    match error_format {
+        ErrorFormat::Human => eprintln!("{}", diag.render(level, source_map)),
+        ErrorFormat::Json => eprintln!("{}", diag.to_json(level, source_map)),
+    }
+}
+
+/// Writes the output of `build`, `tokens`, `ast` or `cst` to the path given
+/// by `-o`, or the standard output. Returns whether it could be written.
+fn write_output(options: &Options, text: &str) -> bool {
+    let result = match options.output {
+        Some(ref path) => fs::write(path, text).map_err(|err| {
+            let err = describe_io_error(&err);
+            eprintln!("error: couldn't write `{}`: {}", path.display(), err);
+        }),
+        None => io::stdout().write_all(text.as_bytes()).map_err(|err| {
+            let err = describe_io_error(&err);
+            eprintln!("error: couldn't write the standard output: {}", err);
+        }),
+    };
+    result.is_ok()
+}
+
+/// Writes text to a file in place of its code. Returns whether it could be
+/// written.
+fn write_file(path: &str, text: &str) -> bool {
+    match fs::write(path, text) {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!(
+                "error: couldn't write `{}`: {}",
+                path,
+                describe_io_error(&err)
+            );
+            false
+        }
+    }
+}
+
+fn read_stdin() -> io::Result<String> {
+    let mut src = String::new();
+    io::stdin().read_to_string(&mut src).map(|_| src)
+}
+
+/// Writes why a file, or the standard input for `-`, couldn't be read.
+fn report_read_error(path: &str, err: &io::Error) {
+    let err = describe_io_error(err);
+    if path == "-" {
+        eprintln!("error: couldn't read the standard input: {}", err);
+    } else {
+        eprintln!("error: couldn't read `{}`: {}", path, err);
+    }
+}
+
+fn exit_code(success: bool) -> i32 {
+    if success {
+        0
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+/// Describes an I/O error without the OS error code.
+fn describe_io_error(err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::NotFound => "no such file or directory".to_owned(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_owned(),
+        io::ErrorKind::IsADirectory => "it's a directory".to_owned(),
+        io::ErrorKind::InvalidData => "it isn't valid UTF-8 text".to_owned(),
+        _ => err.to_string(),
+    }
+}
+
+/// Formats the given files in place, or with `--check`, reports which aren't
+/// formatted. A file named `-` is read from the standard input and written
+/// formatted to the standard output.
+fn format_files(options: &Options) -> i32 {
+    let driver_options = driver_options(options);
+    let mut all_formatted = true;
+    for path in &options.files {
+        let output = match with_input(path, |input| driver::format(input, &driver_options)) {
+            Some(output) => output,
+            None => {
+                all_formatted = false;
+                continue;
+            }
+        };
+        write_diagnostics(
+            options.error_format,
+            &output.diagnostics,
+            &output.source_map,
+        );
+        let formatted = match output.text {
+            Some(ref formatted) if !output.has_errors() => formatted,
+            _ => {
+                all_formatted = false;
+                continue;
+            }
+        };
+        if options.check {
+            if *formatted != *output.file.src {
+                println!("{}: not formatted", output.file.name());
+                all_formatted = false;
+            }
+        } else if path == "-" {
+            print!("{}", formatted);
+        } else if *formatted != *output.file.src {
+            all_formatted &= write_file(path, formatted);
+        }
+    }
+    exit_code(all_formatted)
+}
+
+/// Applies the fixes suggested for the given files in place, then reports
+/// the errors left. A file named `-` is read from the standard input and
+/// written fixed to the standard output.
+fn fix_files(options: &Options) -> i32 {
+    let driver_options = driver_options(options);
+    let mut all_fixed = true;
+    for path in &options.files {
+        let output = match with_input(path, |input| driver::fix(input, &driver_options)) {
+            Some(output) => output,
+            None => {
+                all_fixed = false;
+                continue;
+            }
+        };
+        let fixed = output.text.as_ref().unwrap();
+        if path == "-" {
+            print!("{}", fixed);
+        } else if *fixed != *output.file.src && !write_file(path, fixed) {
+            all_fixed = false;
+            continue;
         }
+        write_diagnostics(
+            options.error_format,
+            &output.diagnostics,
+            &output.source_map,
+        );
+        all_fixed &= !output.has_errors();
     }
+    exit_code(all_fixed)
 }
@@ -1,50 +1,104 @@
 use ast;
-use errors::Diag;
-use scanner::{Category, Word, WordStream};
+use errors::{Applicability, Diag, Suggestion};
+use scanner::{Category, Word, WordStream, KEYWORDS};
 use source_map::Span;
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::result;
 
 type Result<T> = result::Result<T, Diag>;
 
+/// The keywords which may start a declaration or statement, any of which an
+/// identifier starting one may be a misspelling of.
+const STMT_KEYWORDS: &[Category] = &[
+    Category::Let,
+    Category::Const,
+    Category::If,
+    Category::Case,
+    Category::While,
+    Category::For,
+    Category::Repeat,
+    Category::Break,
+    Category::Continue,
+];
+
 pub struct Parser<'a> {
     word_stream: WordStream<'a>,
     peek_word: Word,
-    ident_table: HashMap<String, ast::Name>,
-    last_name_id: u64,
+    /// The last word consumed.
+    prev_word: Option<Word>,
+    ident_table: ast::IdentTable,
+    /// How many loops enclose the statement being parsed.
+    loop_depth: usize,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(mut word_stream: WordStream<'a>) -> Parser {
-        let peek_word = word_stream.next();
+    pub fn new(word_stream: WordStream<'a>) -> Parser<'a> {
+        Parser::with_ident_table(word_stream, ast::IdentTable::default())
+    }
+
+    /// Creates a parser which interns identifiers into an existing table,
+    /// so that names are shared by every file of a program.
+    pub fn with_ident_table(
+        mut word_stream: WordStream<'a>,
+        ident_table: ast::IdentTable,
+    ) -> Parser<'a> {
+        let peek_word = next_word(&mut word_stream);
         Parser {
             word_stream,
             peek_word,
-            ident_table: HashMap::new(),
-            last_name_id: 0,
+            prev_word: None,
+            ident_table,
+            loop_depth: 0,
         }
     }
 
     fn is_start_of_statement(&self) -> bool {
-        match self.peek_word.category {
-            Category::Ident | Category::If | Category::While | Category::OpenCurly => true,
-            _ => false,
-        }
+        matches!(
+            self.peek_word.category,
+            Category::Ident
+                | Category::If
+                | Category::Case
+                | Category::While
+                | Category::For
+                | Category::Repeat
+                | Category::Break
+                | Category::Continue
+                | Category::OpenCurly
+        )
     }
 
     fn parse_block_stmt(&mut self) -> Result<ast::Stmt> {
         assert_eq!(Category::OpenCurly, self.peek_word.category);
         let block = self.parse_block()?;
-        Ok(ast::Stmt::BlockStmt(Box::new(block)))
+        let span = block.span;
+        Ok(ast::Stmt {
+            kind: ast::StmtKind::BlockStmt(Box::new(block)),
+            span,
+        })
     }
 
     fn parse_command(&mut self) -> Result<ast::Stmt> {
         let stmt = match self.peek_word.category {
-            Category::Ident => self.parse_assignment()?,
+            // Telling a call from an assignment takes a word after the name.
+            Category::Ident => {
+                if self.peek_nth(1).category == Category::OpenParen {
+                    self.parse_call()?
+                } else {
+                    self.parse_assignment()?
+                }
+            }
             Category::If => self.parse_selection()?,
+            Category::Case => self.parse_case()?,
             Category::While => self.parse_repetition()?,
+            Category::For => self.parse_for()?,
+            Category::Repeat => self.parse_repeat()?,
+            Category::Break | Category::Continue => self.parse_loop_control()?,
             Category::OpenCurly => self.parse_block_stmt()?,
-            _ => panic!("has to be the start of an statement!"),
+            _ => {
+                return Err(Diag::ExpectedStmt {
+                    got: self.peek_word,
+                })
+            }
         };
         Ok(stmt)
     }
@@ -53,25 +107,104 @@ impl<'a> Parser<'a> {
         self.expect_and_consume(Category::Program)?;
         let prog_name = self.parse_ident()?;
         self.expect_and_consume(Category::Semi)?;
+        let uses = self.parse_uses()?;
+        let (consts, decls) = self.parse_decls()?;
 
-        let mut decls = vec![];
         let mut stmts = vec![];
 
-        while self.peek_word.category == Category::Let {
-            decls.push(self.parse_decl()?);
-        }
-
         while self.is_start_of_statement() {
             stmts.push(self.parse_command()?);
         }
+        self.expect_and_consume(Category::Eof)?;
 
         Ok(ast::Program {
             name: prog_name,
+            uses,
+            consts,
+            decls,
+            stmts,
+        })
+    }
+
+    /// Parses a unit, which has no statements, up to the end of its file.
+    pub fn parse_unit(&mut self) -> Result<ast::Unit> {
+        self.expect_and_consume(Category::Unit)?;
+        let name = self.parse_ident()?;
+        self.expect_and_consume(Category::Semi)?;
+        let uses = self.parse_uses()?;
+        let (consts, decls) = self.parse_decls()?;
+        self.expect_and_consume(Category::Eof)?;
+        Ok(ast::Unit {
+            name,
+            uses,
+            consts,
+            decls,
+        })
+    }
+
+    /// Parses declarations followed by statements up to the end of the
+    /// file, as typed into the REPL.
+    pub fn parse_items(&mut self) -> Result<ast::Items> {
+        let (consts, decls) = self.parse_decls()?;
+        let mut stmts = vec![];
+        while self.is_start_of_statement() {
+            stmts.push(self.parse_command()?);
+        }
+        self.expect_and_consume(Category::Eof)?;
+        Ok(ast::Items {
+            consts,
             decls,
             stmts,
         })
     }
 
+    /// Parses an expression taking up the whole file, as typed into the
+    /// REPL.
+    pub fn parse_standalone_expr(&mut self) -> Result<ast::Expr> {
+        let expr = self.parse_expr()?;
+        self.expect_and_consume(Category::Eof)?;
+        Ok(expr)
+    }
+
+    fn parse_uses(&mut self) -> Result<Vec<ast::Ident>> {
+        let mut units = vec![];
+        if self.peek_word.category == Category::Uses {
+            self.consume();
+            units.push(self.parse_ident()?);
+            while self.peek_word.category == Category::Comma {
+                self.consume();
+                units.push(self.parse_ident()?);
+            }
+            self.expect_and_consume(Category::Semi)?;
+        }
+        Ok(units)
+    }
+
+    /// Parses the constant and variable declarations, which may come in any
+    /// order.
+    fn parse_decls(&mut self) -> Result<(Vec<ast::ConstDecl>, Vec<ast::Decl>)> {
+        let mut consts = vec![];
+        let mut decls = vec![];
+        loop {
+            match self.peek_word.category {
+                Category::Let => decls.push(self.parse_decl()?),
+                Category::Const => consts.push(self.parse_const_decl()?),
+                _ => return Ok((consts, decls)),
+            }
+        }
+    }
+
+    fn parse_const_decl(&mut self) -> Result<ast::ConstDecl> {
+        self.expect_and_consume(Category::Const)?;
+        let ident = self.parse_ident()?;
+        self.expect_and_consume(Category::Colon)?;
+        let ty = self.parse_ty()?;
+        self.expect_and_consume(Category::Eq)?;
+        let expr = self.parse_expr()?;
+        self.expect_and_consume(Category::Semi)?;
+        Ok(ast::ConstDecl { ident, ty, expr })
+    }
+
     fn parse_decl(&mut self) -> Result<ast::Decl> {
         assert_eq!(Category::Let, self.peek_word.category);
         self.expect_and_consume(Category::Let)?;
@@ -102,214 +235,554 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_block(&mut self) -> Result<ast::Block> {
-        self.expect_and_consume(Category::OpenCurly)?;
-        let mut commands = vec![self.parse_command()?];
+        let open_word = self.expect_and_consume(Category::OpenCurly)?;
+        let mut commands = vec![];
         while self.is_start_of_statement() {
             commands.push(self.parse_command()?);
         }
-        self.expect_and_consume(Category::CloseCurly)?;
-        Ok(ast::Block { stmts: commands })
+        let close_word = self.expect_and_consume(Category::CloseCurly)?;
+        Ok(ast::Block {
+            stmts: commands,
+            span: Span {
+                start: open_word.lexeme.start,
+                end: close_word.lexeme.end,
+            },
+        })
     }
 
-    fn parse_call(&mut self, func_id: ast::Ident) -> Result<ast::Stmt> {
+    fn parse_call(&mut self) -> Result<ast::Stmt> {
+        let func_id = self.parse_ident()?;
+        let (args, _) = self.parse_call_args()?;
+        let semi_word = self.expect_and_consume(Category::Semi)?;
+        let span = Span {
+            start: func_id.span.start,
+            end: semi_word.lexeme.end,
+        };
+        Ok(ast::Stmt {
+            kind: ast::StmtKind::Call(func_id, args),
+            span,
+        })
+    }
+
+    /// Parses the parenthesized arguments of a call, returning them along
+    /// with the closing parenthesis.
+    fn parse_call_args(&mut self) -> Result<(Vec<ast::Expr>, Word)> {
         self.expect_and_consume(Category::OpenParen)?;
         let mut args = vec![];
         while self.peek_word.category != Category::CloseParen {
-            args.push(self.parse_expr()?);
+            args.push(self.parse_call_arg()?);
             if self.peek_word.category == Category::CloseParen {
                 break;
             }
             self.expect_and_consume(Category::Comma)?;
         }
-        self.expect_and_consume(Category::CloseParen)?;
-        self.expect_and_consume(Category::Semi)?;
-        Ok(ast::Stmt::Call(func_id, args))
+        let close_word = self.expect_and_consume(Category::CloseParen)?;
+        Ok((args, close_word))
+    }
+
+    fn parse_call_arg(&mut self) -> Result<ast::Expr> {
+        let expr = self.parse_expr()?;
+        if self.peek_word.category != Category::Colon {
+            return Ok(expr);
+        }
+        self.consume();
+        let width = self.parse_expr()?;
+        let mut end = width.span.end;
+        let prec = if self.peek_word.category == Category::Colon {
+            self.consume();
+            let prec = self.parse_expr()?;
+            end = prec.span.end;
+            Some(Box::new(prec))
+        } else {
+            None
+        };
+        let span = Span {
+            start: expr.span.start,
+            end,
+        };
+        Ok(ast::Expr {
+            kind: ast::ExprKind::Formatted(Box::new(expr), Box::new(width), prec),
+            span,
+        })
     }
 
     fn parse_assignment(&mut self) -> Result<ast::Stmt> {
         assert_eq!(Category::Ident, self.peek_word.category);
         let ident = self.parse_ident()?;
-        if self.peek_word.category == Category::OpenParen {
-            return self.parse_call(ident);
+        if self.peek_word.category != Category::Eq {
+            // A statement starting with a misspelled keyword looks like an
+            // assignment to the misspelling.
+            return Err(Diag::ExpectedWord {
+                expected: Category::Eq,
+                got: self.peek_word,
+                suggestions: self.keyword_suggestions(ident.span, STMT_KEYWORDS),
+            });
         }
-        self.expect_and_consume(Category::Eq)?;
+        self.consume();
         let expr = self.parse_expr()?;
-        self.expect_and_consume(Category::Semi)?;
-        Ok(ast::Stmt::Assign(ident, expr))
+        let semi_word = self.expect_and_consume(Category::Semi)?;
+        let span = Span {
+            start: ident.span.start,
+            end: semi_word.lexeme.end,
+        };
+        Ok(ast::Stmt {
+            kind: ast::StmtKind::Assign(ident, expr),
+            span,
+        })
     }
 
     fn parse_selection(&mut self) -> Result<ast::Stmt> {
         assert_eq!(Category::If, self.peek_word.category);
-        self.consume();
+        let if_word = self.consume();
         let cond_expr = self.parse_expr()?;
         let then_block = self.parse_block()?;
         let else_block = if self.peek_word.category == Category::Else {
             self.consume();
-            let else_block = self.parse_block()?;
+            // `else if` is sugar for an `else` block holding the chained `if`.
+            let else_block = if self.peek_word.category == Category::If {
+                let stmt = self.parse_selection()?;
+                let span = stmt.span;
+                ast::Block {
+                    stmts: vec![stmt],
+                    span,
+                }
+            } else {
+                self.parse_block()?
+            };
             Some(Box::new(else_block))
         } else {
             None
         };
 
-        Ok(ast::Stmt::If(cond_expr, Box::new(then_block), else_block))
+        let span = Span {
+            start: if_word.lexeme.start,
+            end: else_block
+                .as_ref()
+                .map_or(then_block.span.end, |block| block.span.end),
+        };
+        Ok(ast::Stmt {
+            kind: ast::StmtKind::If(cond_expr, Box::new(then_block), else_block),
+            span,
+        })
     }
 
-    fn parse_repetition(&mut self) -> Result<ast::Stmt> {
-        assert_eq!(Category::While, self.peek_word.category);
-        self.consume();
-        let cond_expr = self.parse_expr()?;
-        let block = self.parse_block()?;
-        Ok(ast::Stmt::While(cond_expr, Box::new(block)))
-    }
+    fn parse_case(&mut self) -> Result<ast::Stmt> {
+        assert_eq!(Category::Case, self.peek_word.category);
+        let case_span = self.consume().lexeme;
+        let expr = self.parse_expr()?;
+        self.expect_and_consume(Category::Of)?;
 
-    fn parse_expr(&mut self) -> Result<ast::Expr> {
-        use ast::Expr;
-        let lhs_expr = self.parse_term()?;
-        match self.peek_word.category {
-            Category::Plus | Category::Minus => {
-                let expr_cat = self.peek_word.category;
+        let mut arms = vec![];
+
+        loop {
+            let mut pats = vec![];
+            loop {
+                pats.push(self.parse_case_pattern()?);
+                if self.peek_word.category != Category::Comma {
+                    break;
+                }
                 self.consume();
-                let rhs_expr = self.parse_expr()?;
-                let expr_op = match expr_cat {
-                    Category::Plus => ast::BinOp::Add,
-                    Category::Minus => ast::BinOp::Sub,
-                    _ => panic!("has to be an additive operator!"),
-                };
-                Ok(Expr::BinaryOp(
-                    expr_op,
-                    Box::new(lhs_expr),
-                    Box::new(rhs_expr),
-                ))
             }
-            _ => Ok(lhs_expr),
+            self.expect_and_consume(Category::Colon)?;
+            let block = self.parse_block()?;
+            arms.push(ast::CaseArm {
+                pats,
+                block: Box::new(block),
+            });
+            if !self.is_start_of_case_pattern() {
+                break;
+            }
         }
-    }
 
-    fn parse_term(&mut self) -> Result<ast::Expr> {
-        use ast::Expr;
-        let lhs_expr = self.parse_equality_expr()?;
-        match self.peek_word.category {
-            Category::Star | Category::Slash => {
-                let term_cat = self.peek_word.category;
-                self.consume();
-                let rhs_expr = self.parse_term()?;
-                let term_op = match term_cat {
-                    Category::Star => ast::BinOp::Mult,
-                    Category::Slash => ast::BinOp::Div,
-                    _ => panic!("has to be a multiplicative operator!"),
-                };
-                Ok(Expr::BinaryOp(
-                    term_op,
-                    Box::new(lhs_expr),
-                    Box::new(rhs_expr),
-                ))
+        let else_block = if self.peek_word.category == Category::Else {
+            self.consume();
+            Some(Box::new(self.parse_block()?))
+        } else {
+            None
+        };
+
+        let mut seen_pats: Vec<&ast::Pat> = vec![];
+        for pat in arms.iter().flat_map(|arm| arm.pats.iter()) {
+            let prev = seen_pats
+                .iter()
+                .find(|&&seen_pat| case_patterns_overlap(seen_pat, pat));
+            if let Some(prev) = prev {
+                return Err(Diag::DuplicateCasePattern {
+                    pat_span: pat.span,
+                    prev_span: prev.span,
+                });
             }
-            _ => Ok(lhs_expr),
+            seen_pats.push(pat);
         }
+
+        if else_block.is_none() {
+            let covered_bools = seen_pats
+                .iter()
+                .filter_map(|pat| match pat.kind {
+                    ast::PatKind::Lit(ast::Lit::BoolLit(value)) => Some(value),
+                    _ => None,
+                })
+                .collect::<HashSet<_>>();
+            if covered_bools.len() == 1 {
+                let covered = *covered_bools.iter().next().unwrap();
+                self.word_stream.handler().report(Diag::NonExhaustiveCase {
+                    case_span,
+                    missing: !covered,
+                });
+            }
+        }
+
+        let end = match else_block {
+            Some(ref block) => block.span.end,
+            None => arms.last().unwrap().block.span.end,
+        };
+        let span = Span {
+            start: case_span.start,
+            end,
+        };
+        Ok(ast::Stmt {
+            kind: ast::StmtKind::Case(expr, arms, else_block),
+            span,
+        })
     }
 
-    fn parse_equality_expr(&mut self) -> Result<ast::Expr> {
-        use ast::Expr;
-        let lhs_expr = self.parse_relational_expr()?;
-        match self.peek_word.category {
-            Category::EqEq | Category::Ne => {
-                let eq_cat = self.peek_word.category;
-                self.consume();
-                let rhs_expr = self.parse_equality_expr()?;
-                let eq_op = match eq_cat {
-                    Category::EqEq => ast::BinOp::Eq,
-                    Category::Ne => ast::BinOp::Ne,
-                    _ => panic!("has to be an equality operator!"),
-                };
-                Ok(Expr::BinaryOp(
-                    eq_op,
-                    Box::new(lhs_expr),
-                    Box::new(rhs_expr),
-                ))
+    fn is_start_of_case_pattern(&self) -> bool {
+        matches!(
+            self.peek_word.category,
+            Category::NumConst { is_float: false }
+                | Category::Minus
+                | Category::StrLit
+                | Category::True
+                | Category::False
+        )
+    }
+
+    fn parse_case_pattern(&mut self) -> Result<ast::Pat> {
+        if !self.is_start_of_case_pattern() {
+            return Err(self.expected_one_of(&[
+                Category::NumConst { is_float: false },
+                Category::StrLit,
+                Category::True,
+                Category::False,
+            ]));
+        }
+
+        let (kind, span) = match self.peek_word.category {
+            Category::NumConst { .. } | Category::Minus => {
+                let (low, mut span) = self.parse_case_int()?;
+                if self.peek_word.category == Category::DotDot {
+                    self.consume();
+                    let (high, high_span) = self.parse_case_int()?;
+                    span.end = high_span.end;
+                    if low > high {
+                        return Err(Diag::ReversedCaseRange { span });
+                    }
+                    (ast::PatKind::Range(low, high), span)
+                } else {
+                    (ast::PatKind::Int(low), span)
+                }
             }
-            _ => Ok(lhs_expr),
+            _ => {
+                let span = self.peek_word.lexeme;
+                (ast::PatKind::Lit(self.parse_lit()?), span)
+            }
+        };
+        Ok(ast::Pat { kind, span })
+    }
+
+    /// Parses an integer of a `case` pattern, which may be negated, and has
+    /// to fit in an `int`.
+    fn parse_case_int(&mut self) -> Result<(i64, Span)> {
+        let mut span = self.peek_word.lexeme;
+        let is_negative = self.peek_word.category == Category::Minus;
+        if is_negative {
+            self.consume();
         }
+        let word = self.expect_and_consume(Category::NumConst { is_float: false })?;
+        span.end = word.lexeme.end;
+        let value = parse_int_lexeme(self.get_lexeme(word.lexeme)).and_then(|magnitude| {
+            if is_negative {
+                0i64.checked_sub_unsigned(magnitude)
+            } else {
+                (magnitude <= i64::MAX as u64).then_some(magnitude as i64)
+            }
+        });
+        let value = value.ok_or(Diag::IntLiteralTooLarge { span: word.lexeme })?;
+        Ok((value, span))
     }
 
-    fn parse_relational_expr(&mut self) -> Result<ast::Expr> {
-        use ast::Expr;
-        let lhs_expr = self.parse_factor()?;
-        match self.peek_word.category {
-            Category::Lt | Category::Le | Category::Gt | Category::Ge => {
-                let rel_cat = self.peek_word.category;
-                self.consume();
-                let rhs_expr = self.parse_relational_expr()?;
-                let rel_op = match rel_cat {
-                    Category::Lt => ast::BinOp::Lt,
-                    Category::Le => ast::BinOp::Le,
-                    Category::Gt => ast::BinOp::Gt,
-                    Category::Ge => ast::BinOp::Ge,
-                    _ => panic!("has to be a relational operator!"),
-                };
-                Ok(Expr::BinaryOp(
-                    rel_op,
-                    Box::new(lhs_expr),
-                    Box::new(rhs_expr),
-                ))
+    fn parse_repetition(&mut self) -> Result<ast::Stmt> {
+        assert_eq!(Category::While, self.peek_word.category);
+        let while_word = self.consume();
+        let cond_expr = self.parse_expr()?;
+        let block = self.parse_loop_body()?;
+        let span = Span {
+            start: while_word.lexeme.start,
+            end: block.span.end,
+        };
+        Ok(ast::Stmt {
+            kind: ast::StmtKind::While(cond_expr, Box::new(block)),
+            span,
+        })
+    }
+
+    fn parse_for(&mut self) -> Result<ast::Stmt> {
+        assert_eq!(Category::For, self.peek_word.category);
+        let for_word = self.consume();
+        let ident = self.parse_ident()?;
+        self.expect_and_consume(Category::Eq)?;
+        let start_expr = self.parse_expr()?;
+        let dir_word = self.expect_one_of_and_consume(&[Category::To, Category::Downto])?;
+        let dir = match dir_word.category {
+            Category::To => ast::ForDir::To,
+            Category::Downto => ast::ForDir::Downto,
+            _ => panic!("has to be a for loop direction!"),
+        };
+        let end_expr = self.parse_expr()?;
+        let block = self.parse_loop_body()?;
+        let span = Span {
+            start: for_word.lexeme.start,
+            end: block.span.end,
+        };
+        Ok(ast::Stmt {
+            kind: ast::StmtKind::For(ident, start_expr, dir, end_expr, Box::new(block)),
+            span,
+        })
+    }
+
+    fn parse_repeat(&mut self) -> Result<ast::Stmt> {
+        assert_eq!(Category::Repeat, self.peek_word.category);
+        let repeat_word = self.consume();
+        let block = self.parse_loop_body()?;
+        self.expect_and_consume(Category::Until)?;
+        let cond_expr = self.parse_expr()?;
+        let semi_word = self.expect_and_consume(Category::Semi)?;
+        let span = Span {
+            start: repeat_word.lexeme.start,
+            end: semi_word.lexeme.end,
+        };
+        Ok(ast::Stmt {
+            kind: ast::StmtKind::Repeat(Box::new(block), cond_expr),
+            span,
+        })
+    }
+
+    fn parse_loop_control(&mut self) -> Result<ast::Stmt> {
+        let keyword = self.expect_one_of_and_consume(&[Category::Break, Category::Continue])?;
+        if self.loop_depth == 0 {
+            return Err(Diag::LoopControlOutsideLoop { keyword });
+        }
+        let semi_word = self.expect_and_consume(Category::Semi)?;
+        let kind = match keyword.category {
+            Category::Break => ast::StmtKind::Break,
+            Category::Continue => ast::StmtKind::Continue,
+            _ => panic!("has to be a loop control statement!"),
+        };
+        let span = Span {
+            start: keyword.lexeme.start,
+            end: semi_word.lexeme.end,
+        };
+        Ok(ast::Stmt { kind, span })
+    }
+
+    /// Parses the block of a loop, in which `break` and `continue` are
+    /// allowed.
+    fn parse_loop_body(&mut self) -> Result<ast::Block> {
+        self.loop_depth += 1;
+        let block = self.parse_block();
+        self.loop_depth -= 1;
+        block
+    }
+
+    fn parse_expr(&mut self) -> Result<ast::Expr> {
+        self.parse_binary_expr(0)
+    }
+
+    /// Parses a chain of binary operators by precedence climbing, where
+    /// only operators binding at least as tight as `min_prec` are consumed.
+    fn parse_binary_expr(&mut self, min_prec: u32) -> Result<ast::Expr> {
+        let mut lhs_expr = self.parse_unary_expr()?;
+        while let Some(op) = binary_op_of(self.peek_word.category) {
+            let prec = op.precedence();
+            if prec < min_prec {
+                break;
             }
-            _ => Ok(lhs_expr),
+            self.consume();
+            let rhs_min_prec = if op.is_right_assoc() { prec } else { prec + 1 };
+            let rhs_expr = self.parse_binary_expr(rhs_min_prec)?;
+            let span = Span {
+                start: lhs_expr.span.start,
+                end: rhs_expr.span.end,
+            };
+            let kind = ast::ExprKind::BinaryOp(op, Box::new(lhs_expr), Box::new(rhs_expr));
+            lhs_expr = ast::Expr { kind, span };
         }
+        Ok(lhs_expr)
+    }
+
+    fn parse_unary_expr(&mut self) -> Result<ast::Expr> {
+        let op = match self.peek_word.category {
+            Category::Minus => ast::UnOp::Neg,
+            Category::Not => ast::UnOp::Not,
+            _ => return self.parse_factor(),
+        };
+        let op_word = self.consume();
+        // Operators binding tighter than unary ones (i.e. `**`) are part of
+        // the operand, so `-2 ** 2` is `-(2 ** 2)`.
+        let operand = self.parse_binary_expr(op.precedence() + 1)?;
+        let span = Span {
+            start: op_word.lexeme.start,
+            end: operand.span.end,
+        };
+        Ok(ast::Expr {
+            kind: ast::ExprKind::UnaryOp(op, Box::new(operand)),
+            span,
+        })
     }
 
     fn parse_factor(&mut self) -> Result<ast::Expr> {
-        use ast::{Expr, Lit};
-        match self.peek_word.category {
+        use ast::ExprKind;
+        let start_span = self.peek_word.lexeme;
+        let kind = match self.peek_word.category {
             Category::OpenParen => {
                 self.consume();
                 let expr = self.parse_expr()?;
-                self.expect_and_consume(Category::CloseParen)?;
-                Ok(Expr::Paren(Box::new(expr)))
+                let close_word = self.expect_and_consume(Category::CloseParen)?;
+                return Ok(ast::Expr {
+                    kind: ExprKind::Paren(Box::new(expr)),
+                    span: Span {
+                        start: start_span.start,
+                        end: close_word.lexeme.end,
+                    },
+                });
             }
-            Category::StrLit => {
-                let str_data = self.get_peek_lexeme().trim_matches('"').to_owned();
-                self.consume();
-                Ok(Expr::Lit(Lit::StrLit(str_data)))
+            Category::Int | Category::Float | Category::Str => {
+                let ty = self.parse_ty()?;
+                self.expect_and_consume(Category::OpenParen)?;
+                let expr = self.parse_expr()?;
+                let close_word = self.expect_and_consume(Category::CloseParen)?;
+                return Ok(ast::Expr {
+                    kind: ExprKind::Cast(ty, Box::new(expr)),
+                    span: Span {
+                        start: start_span.start,
+                        end: close_word.lexeme.end,
+                    },
+                });
             }
-            Category::NumConst { is_float: false } => {
-                let value = self
-                    .get_peek_lexeme()
-                    .chars()
-                    .flat_map(|c| c.to_digit(10))
-                    .fold(0u64, |acc, val| acc * 10 + val as u64);
-                self.consume();
-                Ok(Expr::Lit(Lit::IntLit(value)))
+            Category::StrLit | Category::NumConst { .. } | Category::True | Category::False => {
+                ExprKind::Lit(self.parse_lit()?)
             }
-            Category::NumConst { is_float: true } => {
-                let value = self.get_peek_lexeme().parse::<f64>().unwrap();
-                self.consume();
-                Ok(Expr::Lit(Lit::FloatLit(value)))
+            Category::Ident => {
+                let ident = self.parse_ident()?;
+                if self.peek_word.category != Category::OpenParen {
+                    ExprKind::Ident(ident)
+                } else {
+                    let (args, close_word) = self.parse_call_args()?;
+                    return Ok(ast::Expr {
+                        kind: ExprKind::Call(ident, args),
+                        span: Span {
+                            start: start_span.start,
+                            end: close_word.lexeme.end,
+                        },
+                    });
+                }
             }
-            Category::Ident => Ok(Expr::Ident(self.parse_ident()?)),
-            _ => unimplemented!(),
-        }
+            _ => {
+                return Err(Diag::ExpectedExpr {
+                    got: self.peek_word,
+                })
+            }
+        };
+        Ok(ast::Expr {
+            kind,
+            span: start_span,
+        })
     }
 
-    fn register_name(&mut self, ident: String) -> ast::Name {
-        let mut last_name_id = self.last_name_id;
-        let name = *self.ident_table.entry(ident).or_insert_with(|| {
-            let name = ast::Name(last_name_id);
-            last_name_id += 1;
-            name
-        });
-        self.last_name_id = last_name_id;
-        name
+    fn parse_lit(&mut self) -> Result<ast::Lit> {
+        use ast::Lit;
+        let lit = match self.peek_word.category {
+            Category::StrLit => Lit::StrLit(self.get_peek_lexeme().trim_matches('"').to_owned()),
+            Category::NumConst { is_float: false } => {
+                match parse_int_lexeme(self.get_peek_lexeme()) {
+                    Some(value) => Lit::IntLit(value),
+                    None => {
+                        return Err(Diag::IntLiteralTooLarge {
+                            span: self.peek_word.lexeme,
+                        })
+                    }
+                }
+            }
+            Category::NumConst { is_float: true } => {
+                Lit::FloatLit(self.get_peek_lexeme().parse::<f64>().unwrap())
+            }
+            Category::True => Lit::BoolLit(true),
+            Category::False => Lit::BoolLit(false),
+            _ => panic!("has to be a literal!"),
+        };
+        self.consume();
+        Ok(lit)
     }
 
     fn parse_ident(&mut self) -> Result<ast::Ident> {
         let ident = self.expect_and_consume(Category::Ident)?;
-        let id_lexeme = self.get_lexeme(ident.lexeme).to_owned();
-        let name = self.register_name(id_lexeme);
-        Ok(ast::Ident { name })
+        let source_file = &self.word_stream.scanner.source_file;
+        let name = self
+            .ident_table
+            .intern(source_file.span_to_snippet(ident.lexeme));
+        Ok(ast::Ident {
+            name,
+            span: ident.lexeme,
+        })
+    }
+
+    /// Whether the source file holds a unit rather than a program.
+    pub fn is_unit(&self) -> bool {
+        self.peek_word.category == Category::Unit
+    }
+
+    /// The spans of the comments found so far, in order.
+    pub fn comments(&self) -> &[Span] {
+        self.word_stream.scanner.comments()
+    }
+
+    /// The table of every identifier parsed so far.
+    pub fn ident_table(&self) -> &ast::IdentTable {
+        &self.ident_table
+    }
+
+    pub fn into_ident_table(self) -> ast::IdentTable {
+        self.ident_table
+    }
+
+    /// Looks at the word `n` words ahead, the peeked word being the 0th,
+    /// skipping text the scanner rejected. A run of rejected text reaching
+    /// past the lookahead of the word stream gives its last error word.
+    fn peek_nth(&mut self, n: usize) -> Word {
+        if n == 0 {
+            return self.peek_word;
+        }
+        let mut n = n - 1;
+        let mut i = 0;
+        loop {
+            let word = self.word_stream.peek_nth(i);
+            if i + 1 == self.word_stream.lookahead() {
+                return word;
+            }
+            if word.category != Category::Error {
+                if n == 0 {
+                    return word;
+                }
+                n -= 1;
+            }
+            i += 1;
+        }
     }
 
     fn consume(&mut self) -> Word {
         let ate_word = self.peek_word;
-        self.peek_word = self.word_stream.next();
+        self.peek_word = next_word(&mut self.word_stream);
+        self.prev_word = Some(ate_word);
         ate_word
     }
 
@@ -317,9 +790,20 @@ impl<'a> Parser<'a> {
         if self.peek_word.category == category {
             Ok(self.consume())
         } else {
+            let mut suggestions = self.keyword_suggestions(self.peek_word.lexeme, &[category]);
+            if let (Category::Semi, Some(prev_word)) = (category, self.prev_word) {
+                let end = prev_word.lexeme.end;
+                suggestions.push(Suggestion {
+                    message: "add `;` here".to_owned(),
+                    span: Span { start: end, end },
+                    replacement: ";".to_owned(),
+                    applicability: Applicability::MachineApplicable,
+                });
+            }
             Err(Diag::ExpectedWord {
                 expected: category,
                 got: self.peek_word,
+                suggestions,
             })
         }
     }
@@ -328,11 +812,39 @@ impl<'a> Parser<'a> {
         if categories.contains(&self.peek_word.category) {
             Ok(self.consume())
         } else {
-            Err(Diag::ExpectedOneOf {
-                expected: categories.to_owned(),
-                got: self.peek_word,
-            })
+            Err(self.expected_one_of(categories))
+        }
+    }
+
+    fn expected_one_of(&self, categories: &[Category]) -> Diag {
+        Diag::ExpectedOneOf {
+            expected: categories.to_owned(),
+            got: self.peek_word,
+            suggestions: self.keyword_suggestions(self.peek_word.lexeme, categories),
+        }
+    }
+
+    /// Suggests replacing an identifier by the keyword among the given
+    /// categories it's most likely a misspelling of, if any.
+    fn keyword_suggestions(&self, span: Span, categories: &[Category]) -> Vec<Suggestion> {
+        let text = self.get_lexeme(span);
+        if span.start == span.end || !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return vec![];
         }
+        KEYWORDS
+            .iter()
+            .filter(|&&(_, category)| categories.contains(&category))
+            .map(|&(keyword, _)| (edit_distance(text, keyword), keyword))
+            .filter(|&(distance, keyword)| distance <= (keyword.len() / 3).max(1))
+            .min()
+            .map(|(_, keyword)| Suggestion {
+                message: format!("did you mean `{}`?", keyword),
+                span,
+                replacement: keyword.to_owned(),
+                applicability: Applicability::MaybeIncorrect,
+            })
+            .into_iter()
+            .collect()
     }
 
     fn get_lexeme(&self, sp: Span) -> &str {
@@ -344,13 +856,101 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Takes the next word the parser cares about. Text the scanner rejected
+/// has already been reported, so parsing goes on as if it weren't there.
+fn next_word(word_stream: &mut WordStream) -> Word {
+    loop {
+        let word = word_stream.next_word();
+        if word.category != Category::Error {
+            return word;
+        }
+    }
+}
+
+/// Counts the insertions, deletions, substitutions and transpositions of
+/// adjacent characters it takes to turn a string into another.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // The distances between prefixes of `a` and `b`, one row per length of
+    // the prefix of `a`.
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// The value of an integer literal, unless it's too large for a `u64`.
+fn parse_int_lexeme(lexeme: &str) -> Option<u64> {
+    lexeme
+        .chars()
+        .flat_map(|c| c.to_digit(10))
+        .try_fold(0u64, |acc, val| {
+            acc.checked_mul(10)
+                .and_then(|acc| acc.checked_add(u64::from(val)))
+        })
+}
+
+fn binary_op_of(category: Category) -> Option<ast::BinOp> {
+    use ast::BinOp;
+    let op = match category {
+        Category::Plus => BinOp::Add,
+        Category::Minus => BinOp::Sub,
+        Category::Star => BinOp::Mult,
+        Category::Slash => BinOp::Div,
+        Category::Percent | Category::Mod => BinOp::Mod,
+        Category::Div => BinOp::IntDiv,
+        Category::StarStar => BinOp::Pow,
+        Category::Amp => BinOp::BitAnd,
+        Category::Pipe => BinOp::BitOr,
+        Category::Caret => BinOp::BitXor,
+        Category::Shl => BinOp::Shl,
+        Category::Shr => BinOp::Shr,
+        Category::EqEq => BinOp::Eq,
+        Category::Ne => BinOp::Ne,
+        Category::Lt => BinOp::Lt,
+        Category::Le => BinOp::Le,
+        Category::Gt => BinOp::Gt,
+        Category::Ge => BinOp::Ge,
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Checks whether some value could be matched by both case patterns.
+fn case_patterns_overlap(a: &ast::Pat, b: &ast::Pat) -> bool {
+    use ast::PatKind;
+    match (&a.kind, &b.kind) {
+        (PatKind::Range(low, high), PatKind::Int(value))
+        | (PatKind::Int(value), PatKind::Range(low, high)) => low <= value && value <= high,
+        (PatKind::Range(a_low, a_high), PatKind::Range(b_low, b_high)) => {
+            a_low <= b_high && b_low <= a_high
+        }
+        (PatKind::Int(a), PatKind::Int(b)) => a == b,
+        (PatKind::Lit(a), PatKind::Lit(b)) => a == b,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::Parser;
+    use super::{edit_distance, Parser};
     use ast;
-    use errors;
+    use errors::{self, Applicability, Diag, Suggestion};
     use scanner::{Category, Scanner, Word, WordStream};
     use source_map::{BytePos, SourceFile, Span};
+    use std::cell::RefCell;
     use std::rc::Rc;
 
     fn create_parser<'a>(src: &str, handler: &'a errors::Handler) -> Parser<'a> {
@@ -360,8 +960,70 @@ mod test {
         Parser::new(word_stream)
     }
 
-    fn mk_int(v: u64) -> ast::Expr {
-        ast::Expr::Lit(ast::Lit::IntLit(v))
+    fn sp(start: usize, end: usize) -> Span {
+        Span {
+            start: BytePos(start),
+            end: BytePos(end),
+        }
+    }
+
+    fn mk_int(v: u64, start: usize) -> ast::Expr {
+        ast::Expr {
+            kind: ast::ExprKind::Lit(ast::Lit::IntLit(v)),
+            span: sp(start, start + v.to_string().len()),
+        }
+    }
+
+    fn mk_ident(name: u64, start: usize, end: usize) -> ast::Ident {
+        ast::Ident {
+            name: ast::Name(name),
+            span: sp(start, end),
+        }
+    }
+
+    fn mk_ident_expr(name: u64, start: usize, end: usize) -> ast::Expr {
+        ast::Expr {
+            kind: ast::ExprKind::Ident(mk_ident(name, start, end)),
+            span: sp(start, end),
+        }
+    }
+
+    fn mk_binary(op: ast::BinOp, lhs: ast::Expr, rhs: ast::Expr) -> ast::Expr {
+        let span = sp(lhs.span.start.0, rhs.span.end.0);
+        ast::Expr {
+            kind: ast::ExprKind::BinaryOp(op, Box::new(lhs), Box::new(rhs)),
+            span,
+        }
+    }
+
+    /// Creates an assignment, whose `;` follows its expression right away.
+    fn mk_assign(ident: ast::Ident, expr: ast::Expr) -> ast::Stmt {
+        let span = sp(ident.span.start.0, expr.span.end.0 + 1);
+        ast::Stmt {
+            kind: ast::StmtKind::Assign(ident, expr),
+            span,
+        }
+    }
+
+    fn mk_stmt(kind: ast::StmtKind, start: usize, end: usize) -> ast::Stmt {
+        ast::Stmt {
+            kind,
+            span: sp(start, end),
+        }
+    }
+
+    fn mk_pat(kind: ast::PatKind, start: usize, end: usize) -> ast::Pat {
+        ast::Pat {
+            kind,
+            span: sp(start, end),
+        }
+    }
+
+    fn mk_block(stmts: Vec<ast::Stmt>, start: usize, end: usize) -> Box<ast::Block> {
+        Box::new(ast::Block {
+            stmts,
+            span: sp(start, end),
+        })
     }
 
     #[test]
@@ -369,7 +1031,7 @@ mod test {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("", &handler);
 
-        assert_eq!(Word::eof(), parser.consume());
+        assert_eq!(Word::eof(BytePos(0)), parser.consume());
     }
 
     #[test]
@@ -377,26 +1039,33 @@ mod test {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("\"abc 123!\"", &handler);
         assert_eq!(
-            Ok(ast::Expr::Lit(ast::Lit::StrLit("abc 123!".into()))),
+            Ok(ast::Expr {
+                kind: ast::ExprKind::Lit(ast::Lit::StrLit("abc 123!".into())),
+                span: sp(0, 10),
+            }),
             parser.parse_expr()
         );
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_parse_num_const() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("0 123 3.14 42e3", &handler);
-        assert_eq!(Ok(ast::Expr::Lit(ast::Lit::IntLit(0))), parser.parse_expr());
-        assert_eq!(
-            Ok(ast::Expr::Lit(ast::Lit::IntLit(123))),
-            parser.parse_expr()
-        );
+        assert_eq!(Ok(mk_int(0, 0)), parser.parse_expr());
+        assert_eq!(Ok(mk_int(123, 2)), parser.parse_expr());
         assert_eq!(
-            Ok(ast::Expr::Lit(ast::Lit::FloatLit(3.14))),
+            Ok(ast::Expr {
+                kind: ast::ExprKind::Lit(ast::Lit::FloatLit(3.14)),
+                span: sp(6, 10),
+            }),
             parser.parse_expr()
         );
         assert_eq!(
-            Ok(ast::Expr::Lit(ast::Lit::FloatLit(42000.0))),
+            Ok(ast::Expr {
+                kind: ast::ExprKind::Lit(ast::Lit::FloatLit(42000.0)),
+                span: sp(11, 15),
+            }),
             parser.parse_expr()
         );
     }
@@ -405,44 +1074,26 @@ mod test {
     fn test_parse_ident() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("aaa bbb aaa ccc bbb aaa a", &handler);
-        assert_eq!(
-            Ok(ast::Expr::Ident(ast::Ident { name: ast::Name(0) })),
-            parser.parse_expr()
-        );
-        assert_eq!(
-            Ok(ast::Expr::Ident(ast::Ident { name: ast::Name(1) })),
-            parser.parse_expr()
-        );
-        assert_eq!(
-            Ok(ast::Expr::Ident(ast::Ident { name: ast::Name(0) })),
-            parser.parse_expr()
-        );
-        assert_eq!(
-            Ok(ast::Expr::Ident(ast::Ident { name: ast::Name(2) })),
-            parser.parse_expr()
-        );
-        assert_eq!(
-            Ok(ast::Expr::Ident(ast::Ident { name: ast::Name(1) })),
-            parser.parse_expr()
-        );
-        assert_eq!(
-            Ok(ast::Expr::Ident(ast::Ident { name: ast::Name(0) })),
-            parser.parse_expr()
-        );
-        assert_eq!(
-            Ok(ast::Expr::Ident(ast::Ident { name: ast::Name(3) })),
-            parser.parse_expr()
-        );
+        assert_eq!(Ok(mk_ident_expr(0, 0, 3)), parser.parse_expr());
+        assert_eq!(Ok(mk_ident_expr(1, 4, 7)), parser.parse_expr());
+        assert_eq!(Ok(mk_ident_expr(0, 8, 11)), parser.parse_expr());
+        assert_eq!(Ok(mk_ident_expr(2, 12, 15)), parser.parse_expr());
+        assert_eq!(Ok(mk_ident_expr(1, 16, 19)), parser.parse_expr());
+        assert_eq!(Ok(mk_ident_expr(0, 20, 23)), parser.parse_expr());
+        assert_eq!(Ok(mk_ident_expr(3, 24, 25)), parser.parse_expr());
     }
 
     #[test]
     fn test_parse_paren() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("(((0)))", &handler);
-        let lit = ast::Expr::Lit(ast::Lit::IntLit(0));
-        let paren1 = ast::Expr::Paren(Box::new(lit));
-        let paren2 = ast::Expr::Paren(Box::new(paren1));
-        let paren3 = ast::Expr::Paren(Box::new(paren2));
+        let mk_paren = |expr, start, end| ast::Expr {
+            kind: ast::ExprKind::Paren(Box::new(expr)),
+            span: sp(start, end),
+        };
+        let paren1 = mk_paren(mk_int(0, 3), 2, 5);
+        let paren2 = mk_paren(paren1, 1, 6);
+        let paren3 = mk_paren(paren2, 0, 7);
 
         assert_eq!(Ok(paren3), parser.parse_expr());
     }
@@ -453,7 +1104,21 @@ mod test {
         let mut parser = create_parser("(((0))", &handler);
         let diag = errors::Diag::ExpectedWord {
             expected: Category::CloseParen,
-            got: Word::eof(),
+            got: Word::eof(BytePos(6)),
+            suggestions: vec![],
+        };
+        assert_eq!(Err(diag), parser.parse_expr());
+    }
+
+    #[test]
+    fn test_parse_missing_expr() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("0 + ;", &handler);
+        let diag = errors::Diag::ExpectedExpr {
+            got: Word {
+                category: Category::Semi,
+                lexeme: sp(4, 5),
+            },
         };
         assert_eq!(Err(diag), parser.parse_expr());
     }
@@ -462,7 +1127,7 @@ mod test {
     fn test_parse_relational_expr() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("0 < 0", &handler);
-        let expr = ast::Expr::BinaryOp(ast::BinOp::Lt, Box::new(mk_int(0)), Box::new(mk_int(0)));
+        let expr = mk_binary(ast::BinOp::Lt, mk_int(0, 0), mk_int(0, 4));
         assert_eq!(Ok(expr), parser.parse_expr());
     }
 
@@ -470,7 +1135,7 @@ mod test {
     fn test_parse_equality_expr() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("0 == 0", &handler);
-        let expr = ast::Expr::BinaryOp(ast::BinOp::Eq, Box::new(mk_int(0)), Box::new(mk_int(0)));
+        let expr = mk_binary(ast::BinOp::Eq, mk_int(0, 0), mk_int(0, 5));
         assert_eq!(Ok(expr), parser.parse_expr());
     }
 
@@ -478,7 +1143,7 @@ mod test {
     fn test_parse_term() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("0 * 0", &handler);
-        let expr = ast::Expr::BinaryOp(ast::BinOp::Mult, Box::new(mk_int(0)), Box::new(mk_int(0)));
+        let expr = mk_binary(ast::BinOp::Mult, mk_int(0, 0), mk_int(0, 4));
         assert_eq!(Ok(expr), parser.parse_expr());
     }
 
@@ -486,29 +1151,141 @@ mod test {
     fn test_parse_additive() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("0 + 0", &handler);
-        let expr = ast::Expr::BinaryOp(ast::BinOp::Add, Box::new(mk_int(0)), Box::new(mk_int(0)));
+        let expr = mk_binary(ast::BinOp::Add, mk_int(0, 0), mk_int(0, 4));
+        assert_eq!(Ok(expr), parser.parse_expr());
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("0 + 1 * 2 < 3 == 4 | 5", &handler);
+        let mult = mk_binary(ast::BinOp::Mult, mk_int(1, 4), mk_int(2, 8));
+        let add = mk_binary(ast::BinOp::Add, mk_int(0, 0), mult);
+        let lt = mk_binary(ast::BinOp::Lt, add, mk_int(3, 12));
+        let eq = mk_binary(ast::BinOp::Eq, lt, mk_int(4, 17));
+        let expr = mk_binary(ast::BinOp::BitOr, eq, mk_int(5, 21));
+        assert_eq!(Ok(expr), parser.parse_expr());
+    }
+
+    #[test]
+    fn test_parse_left_assoc() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("0 - 1 mod 2 - 3", &handler);
+        let modulo = mk_binary(ast::BinOp::Mod, mk_int(1, 4), mk_int(2, 10));
+        let sub = mk_binary(ast::BinOp::Sub, mk_int(0, 0), modulo);
+        let expr = mk_binary(ast::BinOp::Sub, sub, mk_int(3, 14));
+        assert_eq!(Ok(expr), parser.parse_expr());
+    }
+
+    #[test]
+    fn test_parse_pow() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("-0 ** 1 ** 2", &handler);
+        let inner = mk_binary(ast::BinOp::Pow, mk_int(1, 6), mk_int(2, 11));
+        let outer = mk_binary(ast::BinOp::Pow, mk_int(0, 1), inner);
+        let expr = ast::Expr {
+            kind: ast::ExprKind::UnaryOp(ast::UnOp::Neg, Box::new(outer)),
+            span: sp(0, 12),
+        };
+        assert_eq!(Ok(expr), parser.parse_expr());
+    }
+
+    #[test]
+    fn test_parse_bitwise_and_shifts() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("0 & 1 << 2 ^ 3 div 4", &handler);
+        let shl = mk_binary(ast::BinOp::Shl, mk_int(1, 4), mk_int(2, 9));
+        let bit_and = mk_binary(ast::BinOp::BitAnd, mk_int(0, 0), shl);
+        let int_div = mk_binary(ast::BinOp::IntDiv, mk_int(3, 13), mk_int(4, 19));
+        let expr = mk_binary(ast::BinOp::BitXor, bit_and, int_div);
         assert_eq!(Ok(expr), parser.parse_expr());
     }
 
+    #[test]
+    fn test_parse_cast() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("float(x) + int(\"42\")", &handler);
+        let to_float = ast::Expr {
+            kind: ast::ExprKind::Cast(ast::Ty::FloatTy, Box::new(mk_ident_expr(0, 6, 7))),
+            span: sp(0, 8),
+        };
+        let str_lit = ast::Expr {
+            kind: ast::ExprKind::Lit(ast::Lit::StrLit("42".into())),
+            span: sp(15, 19),
+        };
+        let to_int = ast::Expr {
+            kind: ast::ExprKind::Cast(ast::Ty::IntTy, Box::new(str_lit)),
+            span: sp(11, 20),
+        };
+        let expr = mk_binary(ast::BinOp::Add, to_float, to_int);
+        assert_eq!(Ok(expr), parser.parse_expr());
+    }
+
+    #[test]
+    fn test_parse_call_expr() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("find(s, \"a\") + len()", &handler);
+        let str_lit = ast::Expr {
+            kind: ast::ExprKind::Lit(ast::Lit::StrLit("a".into())),
+            span: sp(8, 11),
+        };
+        let find_call = ast::Expr {
+            kind: ast::ExprKind::Call(mk_ident(0, 0, 4), vec![mk_ident_expr(1, 5, 6), str_lit]),
+            span: sp(0, 12),
+        };
+        let len_call = ast::Expr {
+            kind: ast::ExprKind::Call(mk_ident(2, 15, 18), vec![]),
+            span: sp(15, 20),
+        };
+        let expr = mk_binary(ast::BinOp::Add, find_call, len_call);
+        assert_eq!(Ok(expr), parser.parse_expr());
+    }
+
+    #[test]
+    fn test_parse_formatted_args() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("write(x:8:2, y:n);", &handler);
+        let x_arg = ast::Expr {
+            kind: ast::ExprKind::Formatted(
+                Box::new(mk_ident_expr(1, 6, 7)),
+                Box::new(mk_int(8, 8)),
+                Some(Box::new(mk_int(2, 10))),
+            ),
+            span: sp(6, 11),
+        };
+        let y_arg = ast::Expr {
+            kind: ast::ExprKind::Formatted(
+                Box::new(mk_ident_expr(2, 13, 14)),
+                Box::new(mk_ident_expr(3, 15, 16)),
+                None,
+            ),
+            span: sp(13, 16),
+        };
+        let stmt = mk_stmt(
+            ast::StmtKind::Call(mk_ident(0, 0, 5), vec![x_arg, y_arg]),
+            0,
+            18,
+        );
+        assert_eq!(Ok(stmt), parser.parse_command());
+    }
+
     #[test]
     fn test_parse_selection() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("if 1 { x = 0; } else { x = 1; }", &handler);
 
-        let stmt = ast::Stmt::If(
-            mk_int(1),
-            Box::new(ast::Block {
-                stmts: vec![ast::Stmt::Assign(
-                    ast::Ident { name: ast::Name(0) },
-                    mk_int(0),
-                )],
-            }),
-            Some(Box::new(ast::Block {
-                stmts: vec![ast::Stmt::Assign(
-                    ast::Ident { name: ast::Name(0) },
-                    mk_int(1),
-                )],
-            })),
+        let stmt = mk_stmt(
+            ast::StmtKind::If(
+                mk_int(1, 3),
+                mk_block(vec![mk_assign(mk_ident(0, 7, 8), mk_int(0, 11))], 5, 15),
+                Some(mk_block(
+                    vec![mk_assign(mk_ident(0, 23, 24), mk_int(1, 27))],
+                    21,
+                    31,
+                )),
+            ),
+            0,
+            31,
         );
 
         assert_eq!(Ok(stmt), parser.parse_selection());
@@ -519,61 +1296,428 @@ mod test {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("if 1 { x = 0; }", &handler);
 
-        let stmt = ast::Stmt::If(
-            mk_int(1),
-            Box::new(ast::Block {
-                stmts: vec![ast::Stmt::Assign(
-                    ast::Ident { name: ast::Name(0) },
-                    mk_int(0),
-                )],
-            }),
-            None,
+        let stmt = mk_stmt(
+            ast::StmtKind::If(
+                mk_int(1, 3),
+                mk_block(vec![mk_assign(mk_ident(0, 7, 8), mk_int(0, 11))], 5, 15),
+                None,
+            ),
+            0,
+            15,
+        );
+
+        assert_eq!(Ok(stmt), parser.parse_selection());
+    }
+
+    #[test]
+    fn test_parse_selection_else_if() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser(
+            "if 1 { x = 0; } else if 2 { x = 1; } else { x = 2; }",
+            &handler,
+        );
+
+        let else_if = mk_stmt(
+            ast::StmtKind::If(
+                mk_int(2, 24),
+                mk_block(vec![mk_assign(mk_ident(0, 28, 29), mk_int(1, 32))], 26, 36),
+                Some(mk_block(
+                    vec![mk_assign(mk_ident(0, 44, 45), mk_int(2, 48))],
+                    42,
+                    52,
+                )),
+            ),
+            21,
+            52,
+        );
+        let stmt = mk_stmt(
+            ast::StmtKind::If(
+                mk_int(1, 3),
+                mk_block(vec![mk_assign(mk_ident(0, 7, 8), mk_int(0, 11))], 5, 15),
+                // The `else` block of an `else if` spans the chained `if`.
+                Some(mk_block(vec![else_if], 21, 52)),
+            ),
+            0,
+            52,
         );
 
         assert_eq!(Ok(stmt), parser.parse_selection());
     }
 
+    #[test]
+    fn test_parse_case() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser(
+            "case x of 1, 2: { x = 0; } 3..5: { x = 1; } else { x = 2; }",
+            &handler,
+        );
+
+        let stmt = mk_stmt(
+            ast::StmtKind::Case(
+                mk_ident_expr(0, 5, 6),
+                vec![
+                    ast::CaseArm {
+                        pats: vec![
+                            mk_pat(ast::PatKind::Int(1), 10, 11),
+                            mk_pat(ast::PatKind::Int(2), 13, 14),
+                        ],
+                        block: mk_block(
+                            vec![mk_assign(mk_ident(0, 18, 19), mk_int(0, 22))],
+                            16,
+                            26,
+                        ),
+                    },
+                    ast::CaseArm {
+                        pats: vec![mk_pat(ast::PatKind::Range(3, 5), 27, 31)],
+                        block: mk_block(
+                            vec![mk_assign(mk_ident(0, 35, 36), mk_int(1, 39))],
+                            33,
+                            43,
+                        ),
+                    },
+                ],
+                Some(mk_block(
+                    vec![mk_assign(mk_ident(0, 51, 52), mk_int(2, 55))],
+                    49,
+                    59,
+                )),
+            ),
+            0,
+            59,
+        );
+
+        assert_eq!(Ok(stmt), parser.parse_case());
+    }
+
+    #[test]
+    fn test_parse_case_str_patterns() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("case s of \"a\": { x = 0; } \"b\": { x = 1; }", &handler);
+
+        let stmt = mk_stmt(
+            ast::StmtKind::Case(
+                mk_ident_expr(0, 5, 6),
+                vec![
+                    ast::CaseArm {
+                        pats: vec![mk_pat(
+                            ast::PatKind::Lit(ast::Lit::StrLit("a".into())),
+                            10,
+                            13,
+                        )],
+                        block: mk_block(
+                            vec![mk_assign(mk_ident(1, 17, 18), mk_int(0, 21))],
+                            15,
+                            25,
+                        ),
+                    },
+                    ast::CaseArm {
+                        pats: vec![mk_pat(
+                            ast::PatKind::Lit(ast::Lit::StrLit("b".into())),
+                            26,
+                            29,
+                        )],
+                        block: mk_block(
+                            vec![mk_assign(mk_ident(1, 33, 34), mk_int(1, 37))],
+                            31,
+                            41,
+                        ),
+                    },
+                ],
+                None,
+            ),
+            0,
+            41,
+        );
+
+        assert_eq!(Ok(stmt), parser.parse_case());
+    }
+
+    #[test]
+    fn test_parse_case_duplicate_pattern() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("case x of 1..5: { x = 0; } 4: { x = 1; }", &handler);
+
+        let diag = errors::Diag::DuplicateCasePattern {
+            pat_span: Span {
+                start: BytePos(27),
+                end: BytePos(28),
+            },
+            prev_span: Span {
+                start: BytePos(10),
+                end: BytePos(14),
+            },
+        };
+
+        assert_eq!(Err(diag), parser.parse_case());
+    }
+
+    #[test]
+    fn test_parse_case_negative_patterns() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser(
+            "-1 -3..-2 -5..5 -9223372036854775808 -9223372036854775809",
+            &handler,
+        );
+
+        assert_eq!(
+            Ok(mk_pat(ast::PatKind::Int(-1), 0, 2)),
+            parser.parse_case_pattern()
+        );
+        assert_eq!(
+            Ok(mk_pat(ast::PatKind::Range(-3, -2), 3, 9)),
+            parser.parse_case_pattern()
+        );
+        assert_eq!(
+            Ok(mk_pat(ast::PatKind::Range(-5, 5), 10, 15)),
+            parser.parse_case_pattern()
+        );
+        assert_eq!(
+            Ok(mk_pat(ast::PatKind::Int(i64::MIN), 16, 36)),
+            parser.parse_case_pattern()
+        );
+        assert_eq!(
+            Err(Diag::IntLiteralTooLarge { span: sp(38, 57) }),
+            parser.parse_case_pattern()
+        );
+    }
+
+    #[test]
+    fn test_parse_case_reversed_range() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("case x of 5..3: { x = 0; }", &handler);
+        assert_eq!(
+            Err(Diag::ReversedCaseRange { span: sp(10, 14) }),
+            parser.parse_case()
+        );
+
+        let mut parser = create_parser("-2..-3 3..3", &handler);
+        assert_eq!(
+            Err(Diag::ReversedCaseRange { span: sp(0, 6) }),
+            parser.parse_case_pattern()
+        );
+        assert_eq!(
+            Ok(mk_pat(ast::PatKind::Range(3, 3), 7, 11)),
+            parser.parse_case_pattern()
+        );
+    }
+
+    #[test]
+    fn test_parse_case_non_exhaustive_bool() {
+        let diags = Rc::new(RefCell::new(vec![]));
+        let reported = diags.clone();
+        let handler =
+            errors::Handler::with_emitter(move |diag, _| reported.borrow_mut().push(diag));
+        let mut parser = create_parser("case b of true: { x = 0; }", &handler);
+
+        assert!(parser.parse_case().is_ok());
+        handler.flush();
+        assert_eq!(
+            vec![errors::Diag::NonExhaustiveCase {
+                case_span: Span {
+                    start: BytePos(0),
+                    end: BytePos(4),
+                },
+                missing: false,
+            }],
+            *diags.borrow()
+        );
+    }
+
     #[test]
     fn test_parse_repetition() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("while 1 { x = 0; }", &handler);
 
-        let stmt = ast::Stmt::While(
-            mk_int(1),
-            Box::new(ast::Block {
-                stmts: vec![ast::Stmt::Assign(
-                    ast::Ident { name: ast::Name(0) },
-                    mk_int(0),
-                )],
-            }),
+        let stmt = mk_stmt(
+            ast::StmtKind::While(
+                mk_int(1, 6),
+                mk_block(vec![mk_assign(mk_ident(0, 10, 11), mk_int(0, 14))], 8, 18),
+            ),
+            0,
+            18,
         );
 
         assert_eq!(Ok(stmt), parser.parse_repetition());
     }
 
+    #[test]
+    fn test_parse_for() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("for i = 0 to 9 { x = i; }", &handler);
+
+        let stmt = mk_stmt(
+            ast::StmtKind::For(
+                mk_ident(0, 4, 5),
+                mk_int(0, 8),
+                ast::ForDir::To,
+                mk_int(9, 13),
+                mk_block(
+                    vec![mk_assign(mk_ident(1, 17, 18), mk_ident_expr(0, 21, 22))],
+                    15,
+                    25,
+                ),
+            ),
+            0,
+            25,
+        );
+
+        assert_eq!(Ok(stmt), parser.parse_for());
+    }
+
+    #[test]
+    fn test_parse_for_downto() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("for i = 9 downto 0 { break; }", &handler);
+
+        let stmt = mk_stmt(
+            ast::StmtKind::For(
+                mk_ident(0, 4, 5),
+                mk_int(9, 8),
+                ast::ForDir::Downto,
+                mk_int(0, 17),
+                mk_block(vec![mk_stmt(ast::StmtKind::Break, 21, 27)], 19, 29),
+            ),
+            0,
+            29,
+        );
+
+        assert_eq!(Ok(stmt), parser.parse_for());
+    }
+
+    #[test]
+    fn test_parse_repeat() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("repeat { x = 0; continue; } until 1;", &handler);
+
+        let stmt = mk_stmt(
+            ast::StmtKind::Repeat(
+                mk_block(
+                    vec![
+                        mk_assign(mk_ident(0, 9, 10), mk_int(0, 13)),
+                        mk_stmt(ast::StmtKind::Continue, 16, 25),
+                    ],
+                    7,
+                    27,
+                ),
+                mk_int(1, 34),
+            ),
+            0,
+            36,
+        );
+
+        assert_eq!(Ok(stmt), parser.parse_repeat());
+    }
+
+    #[test]
+    fn test_parse_loop_control_in_nested_block() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("while 1 { if 1 { break; } }", &handler);
+
+        let if_stmt = mk_stmt(
+            ast::StmtKind::If(
+                mk_int(1, 13),
+                mk_block(vec![mk_stmt(ast::StmtKind::Break, 17, 23)], 15, 25),
+                None,
+            ),
+            10,
+            25,
+        );
+        let stmt = mk_stmt(
+            ast::StmtKind::While(mk_int(1, 6), mk_block(vec![if_stmt], 8, 27)),
+            0,
+            27,
+        );
+
+        assert_eq!(Ok(stmt), parser.parse_repetition());
+    }
+
+    #[test]
+    fn test_parse_loop_control_outside_loop() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("{ x = 0; continue; }", &handler);
+
+        let diag = errors::Diag::LoopControlOutsideLoop {
+            keyword: Word {
+                category: Category::Continue,
+                lexeme: Span {
+                    start: BytePos(9),
+                    end: BytePos(17),
+                },
+            },
+        };
+
+        assert_eq!(Err(diag), parser.parse_block_stmt());
+    }
+
     #[test]
     fn test_parse_block() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("{ x = 0; y = 1; x = 2; }", &handler);
 
-        let stmt = ast::Stmt::BlockStmt(Box::new(ast::Block {
-            stmts: vec![
-                ast::Stmt::Assign(ast::Ident { name: ast::Name(0) }, mk_int(0)),
-                ast::Stmt::Assign(ast::Ident { name: ast::Name(1) }, mk_int(1)),
-                ast::Stmt::Assign(ast::Ident { name: ast::Name(0) }, mk_int(2)),
-            ],
-        }));
+        let stmt = mk_stmt(
+            ast::StmtKind::BlockStmt(mk_block(
+                vec![
+                    mk_assign(mk_ident(0, 2, 3), mk_int(0, 6)),
+                    mk_assign(mk_ident(1, 9, 10), mk_int(1, 13)),
+                    mk_assign(mk_ident(0, 16, 17), mk_int(2, 20)),
+                ],
+                0,
+                24,
+            )),
+            0,
+            24,
+        );
+
+        assert_eq!(Ok(stmt), parser.parse_block_stmt());
+    }
 
+    #[test]
+    fn test_parse_empty_block() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("{ }", &handler);
+        let stmt = mk_stmt(ast::StmtKind::BlockStmt(mk_block(vec![], 0, 3)), 0, 3);
         assert_eq!(Ok(stmt), parser.parse_block_stmt());
     }
 
+    #[test]
+    fn test_parse_int_literal_too_large() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("18446744073709551615 18446744073709551616", &handler);
+        assert_eq!(
+            Ok(ast::Expr {
+                kind: ast::ExprKind::Lit(ast::Lit::IntLit(u64::MAX)),
+                span: sp(0, 20),
+            }),
+            parser.parse_expr()
+        );
+        assert_eq!(
+            Err(Diag::IntLiteralTooLarge { span: sp(21, 41) }),
+            parser.parse_expr()
+        );
+
+        let mut parser = create_parser("9223372036854775807 9223372036854775808", &handler);
+        assert_eq!(
+            Ok(mk_pat(ast::PatKind::Int(i64::MAX), 0, 19)),
+            parser.parse_case_pattern()
+        );
+        assert_eq!(
+            Err(Diag::IntLiteralTooLarge { span: sp(20, 39) }),
+            parser.parse_case_pattern()
+        );
+        let mut parser = create_parser("1..9223372036854775808", &handler);
+        assert_eq!(
+            Err(Diag::IntLiteralTooLarge { span: sp(3, 22) }),
+            parser.parse_case_pattern()
+        );
+    }
+
     #[test]
     fn test_parse_decl() {
         let handler = errors::Handler::with_ignoring_emitter();
         let mut parser = create_parser("let i: int;", &handler);
 
         let decl = ast::Decl {
-            ident: ast::Ident { name: ast::Name(0) },
+            ident: mk_ident(0, 4, 5),
             ty: ast::Ty::IntTy,
         };
 
@@ -586,18 +1730,119 @@ mod test {
         let mut parser = create_parser("program a; let i: int; i = 42;", &handler);
 
         let prog = ast::Program {
-            name: ast::Ident { name: ast::Name(0) },
+            name: mk_ident(0, 8, 9),
+            uses: vec![],
+            consts: vec![],
             decls: vec![ast::Decl {
-                ident: ast::Ident { name: ast::Name(1) },
+                ident: mk_ident(1, 15, 16),
                 ty: ast::Ty::IntTy,
             }],
-            stmts: vec![ast::Stmt::Assign(
-                ast::Ident { name: ast::Name(1) },
-                mk_int(42),
+            stmts: vec![mk_assign(mk_ident(1, 23, 24), mk_int(42, 27))],
+        };
+
+        assert_eq!(Ok(prog), parser.parse_program());
+    }
+
+    #[test]
+    fn test_parse_program_skips_rejected_text() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("program a; let i: int; i = 42 $$;", &handler);
+
+        let prog = ast::Program {
+            name: mk_ident(0, 8, 9),
+            uses: vec![],
+            consts: vec![],
+            decls: vec![ast::Decl {
+                ident: mk_ident(1, 15, 16),
+                ty: ast::Ty::IntTy,
+            }],
+            stmts: vec![mk_stmt(
+                ast::StmtKind::Assign(mk_ident(1, 23, 24), mk_int(42, 27)),
+                23,
+                33,
             )],
         };
 
         assert_eq!(Ok(prog), parser.parse_program());
+        assert!(handler.has_errors());
+    }
+
+    #[test]
+    fn test_parse_program_uses() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("program a; uses b, c; x = 1;", &handler);
+
+        let prog = ast::Program {
+            name: mk_ident(0, 8, 9),
+            uses: vec![mk_ident(1, 16, 17), mk_ident(2, 19, 20)],
+            consts: vec![],
+            decls: vec![],
+            stmts: vec![mk_assign(mk_ident(3, 22, 23), mk_int(1, 26))],
+        };
+
+        assert_eq!(Ok(prog), parser.parse_program());
+    }
+
+    #[test]
+    fn test_parse_unit() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("unit u; let x: int; const k: int = 2 * 3;", &handler);
+
+        let unit = ast::Unit {
+            name: mk_ident(0, 5, 6),
+            uses: vec![],
+            consts: vec![ast::ConstDecl {
+                ident: mk_ident(2, 26, 27),
+                ty: ast::Ty::IntTy,
+                expr: mk_binary(ast::BinOp::Mult, mk_int(2, 35), mk_int(3, 39)),
+            }],
+            decls: vec![ast::Decl {
+                ident: mk_ident(1, 12, 13),
+                ty: ast::Ty::IntTy,
+            }],
+        };
+
+        assert_eq!(Ok(unit), parser.parse_unit());
+    }
+
+    #[test]
+    fn test_parse_unit_with_statements() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("unit u; x = 1;", &handler);
+
+        let diag = errors::Diag::ExpectedWord {
+            expected: Category::Eof,
+            got: Word {
+                category: Category::Ident,
+                lexeme: Span {
+                    start: BytePos(8),
+                    end: BytePos(9),
+                },
+            },
+            suggestions: vec![],
+        };
+
+        assert_eq!(Err(diag), parser.parse_unit());
+    }
+
+    #[test]
+    fn test_parse_program_trailing_words() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let mut parser = create_parser("program a; i = 42; let j: int;", &handler);
+
+        let diag = errors::Diag::ExpectedWord {
+            expected: Category::Eof,
+            got: Word {
+                category: Category::Let,
+                lexeme: Span {
+                    start: BytePos(19),
+                    end: BytePos(22),
+                },
+            },
+            suggestions: vec![],
+        };
+
+        assert_eq!(Err(diag), parser.parse_program());
     }
 
     #[test]
@@ -614,8 +1859,53 @@ mod test {
                     end: BytePos(13),
                 },
             },
+            suggestions: vec![Suggestion {
+                message: "add `;` here".to_owned(),
+                span: sp(9, 9),
+                replacement: ";".to_owned(),
+                applicability: Applicability::MachineApplicable,
+            }],
         };
 
         assert_eq!(Err(diag), parser.parse_program());
     }
+
+    #[test]
+    fn test_parse_misspelled_keywords() {
+        let handler = errors::Handler::with_ignoring_emitter();
+        let suggestion = |start, end, keyword: &str| Suggestion {
+            message: format!("did you mean `{}`?", keyword),
+            span: sp(start, end),
+            replacement: keyword.to_owned(),
+            applicability: Applicability::MaybeIncorrect,
+        };
+
+        let mut parser = create_parser("program p; whlie x { x = 1; }", &handler);
+        let result = parser.parse_program();
+        assert_eq!(
+            vec![suggestion(11, 16, "while")],
+            result.unwrap_err().suggestions()
+        );
+
+        let mut parser = create_parser("program p; let x: itn;", &handler);
+        let result = parser.parse_program();
+        assert_eq!(
+            vec![suggestion(18, 21, "int")],
+            result.unwrap_err().suggestions()
+        );
+
+        let mut parser = create_parser("program p; let x: bar;", &handler);
+        let result = parser.parse_program();
+        assert!(result.unwrap_err().suggestions().is_empty());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(0, edit_distance("while", "while"));
+        assert_eq!(1, edit_distance("whlie", "while"));
+        assert_eq!(1, edit_distance("progam", "program"));
+        assert_eq!(1, edit_distance("wihle", "while"));
+        assert_eq!(5, edit_distance("abc", "while"));
+        assert_eq!(3, edit_distance("", "int"));
+    }
 }
@@ -0,0 +1,427 @@
+use ast;
+use builtins::BUILTINS;
+use errors::{self, Diag};
+use interp::{Interpreter, Value};
+use parser::Parser;
+use scanner::{Category, Scanner, WordStream};
+use source_map::{SourceFile, SourceMap};
+use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::rc::Rc;
+use typeck::TypeChecker;
+
+const PROMPT: &str = ">> ";
+/// The prompt for the following lines of an input with an open block.
+const CONTINUATION_PROMPT: &str = ".. ";
+
+const HELP: &str = "\
+Type declarations and statements to run them, or an expression to see its value.
+:type <expr>  shows the type of an expression
+:ast <code>   shows the syntax tree of some code
+:reset        forgets every declaration
+:help         shows this message
+:quit         leaves the REPL
+";
+
+/// Runs an interactive session, which reads code from `input` and writes
+/// its results to `output`. The code itself reads from `program_input`.
+///
+/// Declarations and statements have to end with a `;` or `}`, and are run
+/// on top of the ones given before. Anything else is an expression, whose
+/// value is shown along with its type. Inputs go on for more lines while
+/// they have an open block.
+pub fn run<R, P, W>(mut input: R, program_input: P, output: W) -> io::Result<()>
+where
+    R: BufRead,
+    P: BufRead,
+    W: Write,
+{
+    let diags = Rc::new(RefCell::new(vec![]));
+    let handler = {
+        let diags = diags.clone();
+        errors::Handler::with_emitter(move |diag, _| diags.borrow_mut().push(diag))
+    };
+    let output = SharedOutput(Rc::new(RefCell::new(output)));
+    let mut session = Session::new(&handler, diags, program_input, output.clone());
+    let mut output = output;
+
+    loop {
+        let src = match read_input(&mut input, &mut output)? {
+            Some(src) => src,
+            None => return Ok(()),
+        };
+        if !session.eval(&src)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Reads the lines of an input, returning `None` at the end of `input`.
+/// An input whose block is still open there is dropped with an error.
+fn read_input<R, W>(input: &mut R, output: &mut W) -> io::Result<Option<String>>
+where
+    R: BufRead,
+    W: Write,
+{
+    write!(output, "{}", PROMPT)?;
+    output.flush()?;
+    let mut src = String::new();
+    if input.read_line(&mut src)? == 0 {
+        // Ends the prompt's line.
+        writeln!(output)?;
+        return Ok(None);
+    }
+    while has_open_block(&src) {
+        write!(output, "{}", CONTINUATION_PROMPT)?;
+        output.flush()?;
+        if input.read_line(&mut src)? == 0 {
+            writeln!(output)?;
+            writeln!(output, "error: unexpected end of input")?;
+            return Ok(None);
+        }
+    }
+    Ok(Some(src))
+}
+
+/// The categories of the words of some code, or `None` if it has invalid
+/// words, whose errors are left to the parser.
+fn scan_categories(src: &str) -> Option<Vec<Category>> {
+    let file = Rc::new(SourceFile::new("repl".into(), src.to_owned()));
+    let mut scanner = Scanner::new(file);
+    let mut categories = vec![];
+    loop {
+        let word = scanner.next_word().ok()?;
+        if word.category == Category::Eof {
+            return Some(categories);
+        }
+        categories.push(word.category);
+    }
+}
+
+/// Whether some code has more `{` than `}`, so it goes on in the next
+/// lines.
+fn has_open_block(src: &str) -> bool {
+    scan_categories(src).is_some_and(|categories| {
+        let opened = categories
+            .iter()
+            .filter(|&&category| category == Category::OpenCurly)
+            .count();
+        let closed = categories
+            .iter()
+            .filter(|&&category| category == Category::CloseCurly)
+            .count();
+        opened > closed
+    })
+}
+
+/// Code typed into the REPL.
+#[derive(Debug)]
+enum Input {
+    Items(ast::Items),
+    Expr(ast::Expr),
+}
+
+/// Whether some code is an expression rather than declarations and
+/// statements, which end with a `;` or `}`. Code starting like a
+/// declaration or statement other than a call is never an expression.
+fn is_expr(src: &str) -> bool {
+    scan_categories(src).is_some_and(|categories| {
+        let starts_items = match categories.first() {
+            Some(&Category::Ident) => categories.get(1) == Some(&Category::Eq),
+            Some(&Category::Let) | Some(&Category::Const) => true,
+            Some(&category) => is_start_of_stmt(category),
+            None => false,
+        };
+        let ends_items = matches!(
+            categories.last(),
+            Some(&Category::Semi) | Some(&Category::CloseCurly)
+        );
+        !starts_items && !ends_items
+    })
+}
+
+fn is_start_of_stmt(category: Category) -> bool {
+    matches!(
+        category,
+        Category::If
+            | Category::Case
+            | Category::While
+            | Category::For
+            | Category::Repeat
+            | Category::Break
+            | Category::Continue
+            | Category::OpenCurly
+    )
+}
+
+/// An output shared by the REPL and the code it runs.
+struct SharedOutput<W>(Rc<RefCell<W>>);
+
+impl<W> Clone for SharedOutput<W> {
+    fn clone(&self) -> SharedOutput<W> {
+        SharedOutput(self.0.clone())
+    }
+}
+
+impl<W: Write> Write for SharedOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+/// The state kept between inputs.
+struct Session<'a, W: Write> {
+    handler: &'a errors::Handler,
+    diags: Rc<RefCell<Vec<Diag>>>,
+    ident_table: ast::IdentTable,
+    type_checker: TypeChecker<'a>,
+    interp: Interpreter<'a>,
+    output: SharedOutput<W>,
+}
+
+impl<'a, W: Write + 'a> Session<'a, W> {
+    fn new<P>(
+        handler: &'a errors::Handler,
+        diags: Rc<RefCell<Vec<Diag>>>,
+        program_input: P,
+        output: SharedOutput<W>,
+    ) -> Session<'a, W>
+    where
+        P: BufRead + 'a,
+    {
+        // Builtins are resolved once, so all of them are interned up front
+        // for later inputs to use.
+        let mut ident_table = ast::IdentTable::default();
+        for builtin in BUILTINS {
+            ident_table.intern(builtin.name());
+        }
+        Session {
+            handler,
+            diags,
+            type_checker: TypeChecker::new(handler, &ident_table),
+            interp: Interpreter::new(&ident_table, program_input, output.clone()),
+            ident_table,
+            output,
+        }
+    }
+
+    /// Evaluates an input, reporting its errors. Returns whether to go on
+    /// reading inputs.
+    fn eval(&mut self, src: &str) -> io::Result<bool> {
+        let source_map = SourceMap::new();
+        let trimmed = src.trim_start();
+        if let Some(command) = trimmed.strip_prefix(':') {
+            let name_len = command.find(char::is_whitespace).unwrap_or(command.len());
+            let (name, code) = command.split_at(name_len);
+            match name {
+                "type" => {
+                    let file = source_map.add_file("repl".into(), code.to_owned());
+                    let ty = self
+                        .parse(file, Parser::parse_standalone_expr)
+                        .and_then(|expr| self.type_checker.check_standalone_expr(&expr));
+                    if let Some(ty) = ty {
+                        writeln!(self.output, "{}", ty.name())?;
+                    }
+                }
+                "ast" => {
+                    let file = source_map.add_file("repl".into(), code.to_owned());
+                    if let Some(input) = self.parse_input(file) {
+                        writeln!(self.output, "{:#?}", input)?;
+                    }
+                }
+                "reset" => {
+                    self.type_checker = TypeChecker::new(self.handler, &self.ident_table);
+                    self.interp.clear_vars();
+                }
+                "help" => write!(self.output, "{}", HELP)?,
+                "quit" => return Ok(false),
+                _ => writeln!(
+                    self.output,
+                    "unknown command `:{}`, see `:help` for the commands",
+                    name
+                )?,
+            }
+        } else if !trimmed.is_empty() {
+            let file = source_map.add_file("repl".into(), src.to_owned());
+            match self.parse_input(file) {
+                Some(Input::Items(items)) => self.run_items(&items),
+                Some(Input::Expr(expr)) => self.eval_expr(&expr)?,
+                None => {}
+            }
+        }
+
+        self.handler.flush();
+        for diag in self.diags.borrow_mut().drain(..) {
+            writeln!(self.output, "{}", diag.render(diag.level(), &source_map))?;
+        }
+        Ok(true)
+    }
+
+    fn parse<T, F>(&mut self, file: Rc<SourceFile>, parse: F) -> Option<T>
+    where
+        F: FnOnce(&mut Parser<'a>) -> Result<T, Diag>,
+    {
+        let word_stream = WordStream::new(Scanner::new(file), self.handler);
+        let ident_table = mem::take(&mut self.ident_table);
+        let mut parser = Parser::with_ident_table(word_stream, ident_table);
+        let result = parse(&mut parser);
+        self.ident_table = parser.into_ident_table();
+        result.map_err(|diag| self.handler.report(diag)).ok()
+    }
+
+    fn parse_input(&mut self, file: Rc<SourceFile>) -> Option<Input> {
+        if is_expr(&file.src) {
+            self.parse(file, Parser::parse_standalone_expr)
+                .map(Input::Expr)
+        } else {
+            self.parse(file, Parser::parse_items).map(Input::Items)
+        }
+    }
+
+    /// Runs declarations and statements. Declarations are only kept if they
+    /// are well-typed and their values can be computed.
+    fn run_items(&mut self, items: &ast::Items) {
+        let checkpoint = self.type_checker.clone();
+        if !self.type_checker.check_items(items) {
+            self.type_checker = checkpoint;
+            return;
+        }
+        if let Err(err) = self.interp.declare(items) {
+            self.type_checker = checkpoint;
+            self.handler.report(err.into());
+            return;
+        }
+        if let Err(err) = self.interp.exec_stmts(&items.stmts) {
+            self.handler.report(err.into());
+        }
+    }
+
+    fn eval_expr(&mut self, expr: &ast::Expr) -> io::Result<()> {
+        let ty = match self.type_checker.check_standalone_expr(expr) {
+            Some(ty) => ty,
+            None => return Ok(()),
+        };
+        match self.interp.eval_expr(expr) {
+            Ok(Value::Str(value)) => writeln!(self.output, "{:?}: {}", value, ty.name()),
+            Ok(value) => writeln!(self.output, "{}: {}", value, ty.name()),
+            Err(err) => {
+                self.handler.report(err.into());
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::run;
+
+    /// Runs a session with the given inputs, returning its output without
+    /// prompts.
+    fn session(input: &str) -> String {
+        session_with_program_input(input, "")
+    }
+
+    fn session_with_program_input(input: &str, program_input: &str) -> String {
+        let mut output = vec![];
+        run(input.as_bytes(), program_input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .replace(">> ", "")
+            .replace(".. ", "")
+    }
+
+    #[test]
+    fn test_repl_exprs() {
+        assert_eq!(
+            "3: int\n2.5: float\n\"a\\tb\": str\ntrue: bool\n3: int\n\n",
+            session("1 + 2\n5 / 2\n\"a\tb\"\n1 < 2 & !false\nlen(\"abc\") // A comment.\n")
+        );
+    }
+
+    #[test]
+    fn test_repl_keeps_state() {
+        assert_eq!(
+            "42\n43: int\n\n",
+            session("let x: int;\nx = 42; writeln(x);\nx + 1\n")
+        );
+    }
+
+    #[test]
+    fn test_repl_multi_line_input() {
+        assert_eq!(
+            "1\n2\n3\n\n",
+            session("let i: int;\nfor i = 1 to 3 {\n  writeln(i);\n}\n")
+        );
+        assert_eq!(
+            "6: int\n\n",
+            session("let i: int;\nwhile i < 6 {\n  if i < 3 {\n    i = i + 1;\n  }\n  i = i * 2;\n}\ni\n")
+        );
+    }
+
+    #[test]
+    fn test_repl_empty_blocks() {
+        assert_eq!(
+            "1: int\n\n",
+            session("let x: int;\nwhile false { }\nif true { } else { x = 2; }\nx + 1\n")
+        );
+    }
+
+    #[test]
+    fn test_repl_unfinished_input() {
+        assert_eq!(
+            "\nerror: unexpected end of input\n",
+            session("let x: int;\n{\n")
+        );
+        assert_eq!(
+            "2: int\n\nerror: unexpected end of input\n",
+            session("1 + 1\nwhile true {\n  writeln(1);\n")
+        );
+    }
+
+    #[test]
+    fn test_repl_errors() {
+        assert_eq!(
+            "<repl>:1:1: error[B0016]: use of undeclared variable\n\
+             <repl>:2:1: error[B0007]: expected `;`, but got `<end of file>`\n\
+             <repl>:1:11: help: add `;` here\n\
+             <repl>:1:1: error[B0022]: division by zero\n\
+             <repl>:1:16: error[B0022]: division by zero\n\
+             <repl>:1:1: error[B0016]: use of undeclared variable\n\
+             <repl>:1:26: error[B0017]: mismatched types: expected `float`, but found `str`\n\
+             1: int\n\n",
+            session(
+                "x + 1\nlet x: int\n1 div 0\nconst k: int = 1 div 0;\nk\n\
+                 let y: float; y = 1; y = \"a\";\nlet y: int; y = 1;\ny\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_repl_commands() {
+        let output =
+            session(":type 1 + 2.0\n:type \"a\" < \"b\"\n:ast x = 1;\n:nope\n:help\n:quit\n1\n");
+        assert!(output.starts_with("float\nbool\nItems(\n"));
+        assert!(output.contains("kind: Assign("));
+        assert!(output.contains("unknown command `:nope`"));
+        assert!(output.ends_with(":quit         leaves the REPL\n"));
+
+        assert_eq!(
+            "<repl>:1:1: error[B0016]: use of undeclared variable\n\n",
+            session("let x: int;\n:reset\nx\n")
+        );
+    }
+
+    #[test]
+    fn test_repl_program_input() {
+        assert_eq!(
+            "hello!\n\n",
+            session_with_program_input("let s: str;\nreadln(s);\nwriteln(s, \"!\");\n", "hello\n")
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use errors::{self, Diag};
-use source_map::{BytePos, Pos, SourceFile, Span, DUMMY_SPAN};
+use source_map::{BytePos, Pos, SourceFile, Span};
+use std::collections::VecDeque;
 use std::fmt;
 use std::rc::Rc;
 
@@ -18,7 +19,14 @@ pub enum Category {
     Le,
     Lt,
     Star,
+    StarStar,
     Slash,
+    Percent,
+    Amp,
+    Pipe,
+    Caret,
+    Shl,
+    Shr,
     Plus,
     Minus,
     Not,
@@ -26,7 +34,10 @@ pub enum Category {
     Colon,
     Semi,
     Program,
+    Unit,
+    Uses,
     Let,
+    Const,
     Int,
     Bool,
     Float,
@@ -34,9 +45,27 @@ pub enum Category {
     If,
     Else,
     While,
+    For,
+    To,
+    Downto,
+    Repeat,
+    Until,
+    Break,
+    Continue,
+    Mod,
+    Div,
+    Case,
+    Of,
+    True,
+    False,
+    DotDot,
     Ident,
-    NumConst { is_float: bool },
+    NumConst {
+        is_float: bool,
+    },
     StrLit,
+    /// Text the scanner rejected, which has already been reported.
+    Error,
     Eof,
 }
 
@@ -58,7 +87,14 @@ impl fmt::Display for Category {
                 Category::Le => "`<=`",
                 Category::Lt => "`<`",
                 Category::Star => "`*`",
+                Category::StarStar => "`**`",
                 Category::Slash => "`/`",
+                Category::Percent => "`%`",
+                Category::Amp => "`&`",
+                Category::Pipe => "`|`",
+                Category::Caret => "`^`",
+                Category::Shl => "`<<`",
+                Category::Shr => "`>>`",
                 Category::Plus => "`+`",
                 Category::Minus => "`-`",
                 Category::Not => "`!`",
@@ -66,7 +102,10 @@ impl fmt::Display for Category {
                 Category::Colon => "`:`",
                 Category::Semi => "`;`",
                 Category::Program => "`program`",
+                Category::Unit => "`unit`",
+                Category::Uses => "`uses`",
                 Category::Let => "`let`",
+                Category::Const => "`const`",
                 Category::Int => "`int`",
                 Category::Bool => "`bool`",
                 Category::Float => "`float`",
@@ -74,16 +113,60 @@ impl fmt::Display for Category {
                 Category::If => "`if`",
                 Category::Else => "`else`",
                 Category::While => "`while`",
+                Category::For => "`for`",
+                Category::To => "`to`",
+                Category::Downto => "`downto`",
+                Category::Repeat => "`repeat`",
+                Category::Until => "`until`",
+                Category::Break => "`break`",
+                Category::Continue => "`continue`",
+                Category::Mod => "`mod`",
+                Category::Div => "`div`",
+                Category::Case => "`case`",
+                Category::Of => "`of`",
+                Category::True => "`true`",
+                Category::False => "`false`",
+                Category::DotDot => "`..`",
                 Category::Ident => "identifier",
                 Category::NumConst { is_float: false } => "numeric integer constant",
                 Category::NumConst { is_float: true } => "numeric floating point constant",
                 Category::StrLit => "string literal",
+                Category::Error => "invalid word",
                 Category::Eof => "`<end of file>`",
             }
         )
     }
 }
 
+/// The keywords of the language, along with their categories.
+pub const KEYWORDS: &[(&str, Category)] = &[
+    ("program", Category::Program),
+    ("unit", Category::Unit),
+    ("uses", Category::Uses),
+    ("let", Category::Let),
+    ("const", Category::Const),
+    ("int", Category::Int),
+    ("bool", Category::Bool),
+    ("float", Category::Float),
+    ("str", Category::Str),
+    ("if", Category::If),
+    ("else", Category::Else),
+    ("while", Category::While),
+    ("for", Category::For),
+    ("to", Category::To),
+    ("downto", Category::Downto),
+    ("repeat", Category::Repeat),
+    ("until", Category::Until),
+    ("break", Category::Break),
+    ("continue", Category::Continue),
+    ("mod", Category::Mod),
+    ("div", Category::Div),
+    ("case", Category::Case),
+    ("of", Category::Of),
+    ("true", Category::True),
+    ("false", Category::False),
+];
+
 /// A word and its lexeme information given by a span.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Word {
@@ -94,10 +177,15 @@ pub struct Word {
 }
 
 impl Word {
-    pub fn eof() -> Word {
+    /// The end of a file, as an empty word at its end position, so that
+    /// errors about it point past the last line's text.
+    pub fn eof(end_pos: BytePos) -> Word {
         Word {
             category: Category::Eof,
-            lexeme: DUMMY_SPAN,
+            lexeme: Span {
+                start: end_pos,
+                end: end_pos,
+            },
         }
     }
 }
@@ -113,18 +201,25 @@ pub struct Scanner {
     peek_ch: Option<char>,
     pos: BytePos,
     next_pos: BytePos,
+    /// Where the last word scanned, or the text rejected, starts.
+    word_start_pos: BytePos,
+    /// The spans of the comments skipped so far.
+    comments: Vec<Span>,
 }
 
 impl Scanner {
     /// Creates a scanner for a source file.
     pub fn new(source_file: Rc<SourceFile>) -> Scanner {
         let src = source_file.src.clone();
+        let start_pos = source_file.start_pos();
         let mut sc = Scanner {
             source_file,
             src,
             peek_ch: Some('\n'),
-            pos: BytePos(0),
-            next_pos: BytePos(0),
+            pos: start_pos,
+            next_pos: start_pos,
+            word_start_pos: start_pos,
+            comments: vec![],
         };
 
         sc.bump();
@@ -135,13 +230,29 @@ impl Scanner {
         self.peek_ch == Some(c)
     }
 
+    /// Checks whether the character following the peeked one is `c`.
+    fn next_ch_is(&self, c: char) -> bool {
+        self.src[self.src_index(self.next_pos)..].starts_with(c)
+    }
+
+    /// Converts a position into an index of the source file's text buffer.
+    fn src_index(&self, pos: BytePos) -> usize {
+        (pos - self.source_file.start_pos()).to_usize()
+    }
+
+    /// Checks whether the peeked character ends a line, which is either a
+    /// `\n` or the `\r` of a `\r\n`.
+    fn is_eol(&self) -> bool {
+        self.ch_is('\n') || (self.ch_is('\r') && self.next_ch_is('\n'))
+    }
+
     fn is_eof(&self) -> bool {
         self.peek_ch.is_none()
     }
 
     /// Advances the Scanner by one character.
     fn bump(&mut self) {
-        let next_pos_idx = self.next_pos.to_usize();
+        let next_pos_idx = self.src_index(self.next_pos);
 
         if next_pos_idx < self.src.len() {
             let next_ch = self.src[next_pos_idx..].chars().next().unwrap();
@@ -164,17 +275,83 @@ impl Scanner {
     /// successfully scanned word, or a parsing error, which can be
     /// reported by a diagnostic handler.
     pub fn next_word(&mut self) -> Result<Word, Diag> {
-        while is_whitespace(self.peek_ch) {
-            self.bump();
+        loop {
+            while is_whitespace(self.peek_ch) {
+                self.bump();
+            }
+            if self.ch_is('/') && self.next_ch_is('/') {
+                self.skip_comment();
+            } else {
+                break;
+            }
         }
 
+        self.word_start_pos = self.pos;
         if self.is_eof() {
-            Ok(Word::eof())
+            Ok(Word::eof(self.source_file.end_pos()))
         } else {
             self.scan_word()
         }
     }
 
+    /// Skips a comment, which goes from `//` up to the end of the line.
+    fn skip_comment(&mut self) {
+        let comment_start_pos = self.pos;
+        while !self.is_eof() && !self.is_eol() {
+            self.bump();
+        }
+        self.comments.push(Span {
+            start: comment_start_pos,
+            end: self.pos,
+        });
+    }
+
+    /// The spans of the comments skipped so far, in order.
+    pub fn comments(&self) -> &[Span] {
+        &self.comments
+    }
+
+    /// The span of the text last scanned by `next_word`, be it a word or
+    /// text rejected with an error.
+    pub fn last_span(&self) -> Span {
+        Span {
+            start: self.word_start_pos,
+            end: self.pos,
+        }
+    }
+
+    /// Checks whether the peeked character can't start any word. A single
+    /// `.` can't, while two make a `..`.
+    fn is_unknown_char(&self) -> bool {
+        match self.peek_ch {
+            None => false,
+            Some('.') => !self.next_ch_is('.'),
+            Some(c) => {
+                !(c.is_whitespace()
+                    || is_ident_body(Some(c))
+                    || "(){}!=<>*/%&|^+-,:;\"".contains(c))
+            }
+        }
+    }
+
+    /// Skips a run of unknown characters, the first of which starts at
+    /// `start_pos` and has already been bumped, so that the whole run is
+    /// reported at once.
+    fn skip_unknown_chars(&mut self, start_pos: BytePos) -> Diag {
+        let mut count = 1;
+        while self.is_unknown_char() {
+            self.bump();
+            count += 1;
+        }
+        Diag::UnknownCharacter {
+            span: Span {
+                start: start_pos,
+                end: self.pos,
+            },
+            count,
+        }
+    }
+
     fn scan_ident(&mut self) -> Result<Word, Diag> {
         let id_start_pos = self.pos;
         self.bump();
@@ -188,18 +365,11 @@ impl Scanner {
             end: self.pos,
         };
 
-        let category = match self.source_file.span_to_snippet(lexeme) {
-            "program" => Category::Program,
-            "let" => Category::Let,
-            "int" => Category::Int,
-            "bool" => Category::Bool,
-            "float" => Category::Float,
-            "str" => Category::Str,
-            "if" => Category::If,
-            "else" => Category::Else,
-            "while" => Category::While,
-            _ => Category::Ident,
-        };
+        let snippet = self.source_file.span_to_snippet(lexeme);
+        let category = KEYWORDS
+            .iter()
+            .find(|&&(keyword, _)| keyword == snippet)
+            .map_or(Category::Ident, |&(_, category)| category);
 
         Ok(Word { category, lexeme })
     }
@@ -209,10 +379,7 @@ impl Scanner {
         self.bump();
 
         fn is_dec_digit(c: Option<char>) -> bool {
-            match c {
-                Some(c) => '0' <= c && c <= '9',
-                _ => false,
-            }
+            matches!(c, Some('0'..='9'))
         }
 
         while is_dec_digit(self.peek_ch) {
@@ -221,7 +388,8 @@ impl Scanner {
 
         let mut is_float = false;
 
-        if self.ch_is('.') {
+        // A second dot makes a range (e.g. `1..5`), not a decimal point.
+        if self.ch_is('.') && !self.next_ch_is('.') {
             is_float = true;
             self.bump();
         }
@@ -251,10 +419,7 @@ impl Scanner {
         }
 
         fn is_ident(c: Option<char>) -> bool {
-            c.map_or(false, |c| match c {
-                'a'..='z' | 'A'..='Z' | '_' => true,
-                _ => false,
-            })
+            matches!(c, Some('a'..='z' | 'A'..='Z' | '_'))
         }
 
         if is_ident(self.peek_ch) {
@@ -282,11 +447,11 @@ impl Scanner {
         let str_start_pos = self.pos;
         self.bump();
 
-        while !(self.ch_is('"') || self.ch_is('\n') || self.is_eof()) {
+        while !(self.ch_is('"') || self.is_eol() || self.is_eof()) {
             self.bump();
         }
 
-        if self.ch_is('\n') || self.is_eof() {
+        if self.is_eol() || self.is_eof() {
             return Err(Diag::MissingTerminatingStringMark {
                 str_start_pos,
                 eol_pos: self.pos,
@@ -349,6 +514,9 @@ impl Scanner {
                 if self.ch_is('=') {
                     self.bump();
                     Category::Ge
+                } else if self.ch_is('>') {
+                    self.bump();
+                    Category::Shr
                 } else {
                     Category::Gt
                 }
@@ -358,18 +526,42 @@ impl Scanner {
                 if self.ch_is('=') {
                     self.bump();
                     Category::Le
+                } else if self.ch_is('<') {
+                    self.bump();
+                    Category::Shl
                 } else {
                     Category::Lt
                 }
             }
             '*' => {
                 self.bump();
-                Category::Star
+                if self.ch_is('*') {
+                    self.bump();
+                    Category::StarStar
+                } else {
+                    Category::Star
+                }
             }
             '/' => {
                 self.bump();
                 Category::Slash
             }
+            '%' => {
+                self.bump();
+                Category::Percent
+            }
+            '&' => {
+                self.bump();
+                Category::Amp
+            }
+            '|' => {
+                self.bump();
+                Category::Pipe
+            }
+            '^' => {
+                self.bump();
+                Category::Caret
+            }
             '+' => {
                 self.bump();
                 Category::Plus
@@ -390,13 +582,23 @@ impl Scanner {
                 self.bump();
                 Category::Semi
             }
+            '.' => {
+                let pos = self.pos;
+                self.bump();
+                if self.ch_is('.') {
+                    self.bump();
+                    Category::DotDot
+                } else {
+                    return Err(self.skip_unknown_chars(pos));
+                }
+            }
             'a'..='z' | 'A'..='Z' | '_' => return self.scan_ident(),
             '0'..='9' => return self.scan_number(),
             '"' => return self.scan_string_literal(),
             _ => {
                 let pos = self.pos;
                 self.bump();
-                return Err(Diag::UnknownCharacter { pos });
+                return Err(self.skip_unknown_chars(pos));
             }
         };
 
@@ -411,39 +613,210 @@ impl Scanner {
 }
 
 fn is_ident_body(c: Option<char>) -> bool {
-    c.map_or(false, |c| match c {
-        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => true,
-        _ => false,
-    })
+    matches!(c, Some('a'..='z' | 'A'..='Z' | '0'..='9' | '_'))
 }
 
 fn is_whitespace(c: Option<char>) -> bool {
-    c.map_or(false, |c| c.is_whitespace())
+    c.is_some_and(|c| c.is_whitespace())
 }
 
+/// How many words a `WordStream` looks ahead by default.
+pub const DEFAULT_LOOKAHEAD: usize = 16;
+
+/// A stream of the words of a file, which reports scanning errors as it
+/// goes and stands the rejected text for `Category::Error` words.
+///
+/// The stream can look up to a configured number of words ahead with
+/// `peek_nth`, and be rewound to a `Checkpoint` taken earlier. It only
+/// keeps the words it has looked ahead at, along with the words consumed
+/// since the oldest checkpoint which is neither rewound to nor released, so
+/// its memory is bounded by the lookahead while no checkpoint is live.
 pub struct WordStream<'a> {
     pub scanner: Scanner,
     handler: &'a errors::Handler,
+    /// The words scanned and not yet released, ending with `Category::Eof`
+    /// once the end of the file is reached.
+    words: VecDeque<Word>,
+    /// The index in the stream of the first word of `words`.
+    first: usize,
+    /// The index in the stream of the next word.
+    cursor: usize,
+    /// How many words, the next one included, can be looked at.
+    lookahead: usize,
+    /// The indices in the stream of the live checkpoints.
+    checkpoints: Vec<usize>,
+    /// Whether the handler asked to stop, after which the stream ends.
+    stopped: bool,
 }
 
+/// A position in a word stream, to which it can be rewound. The words after
+/// it are kept until it's either rewound to or released.
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint(usize);
+
 impl<'a> WordStream<'a> {
-    pub fn new(scanner: Scanner, handler: &'a errors::Handler) -> WordStream {
-        WordStream { scanner, handler }
+    pub fn new(scanner: Scanner, handler: &'a errors::Handler) -> WordStream<'a> {
+        WordStream {
+            scanner,
+            handler,
+            words: VecDeque::with_capacity(DEFAULT_LOOKAHEAD),
+            first: 0,
+            cursor: 0,
+            lookahead: DEFAULT_LOOKAHEAD,
+            checkpoints: vec![],
+            stopped: false,
+        }
+    }
+
+    /// Sets how many words, the next one included, `peek_nth` can look at.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lookahead` is zero.
+    pub fn with_lookahead(mut self, lookahead: usize) -> WordStream<'a> {
+        assert!(lookahead > 0, "the lookahead has to include the next word");
+        self.lookahead = lookahead;
+        self.words.reserve(lookahead);
+        self
+    }
+
+    /// How many words, the next one included, `peek_nth` can look at.
+    pub fn lookahead(&self) -> usize {
+        self.lookahead
+    }
+
+    /// The handler to which scanning diagnostics are reported.
+    pub fn handler(&self) -> &'a errors::Handler {
+        self.handler
+    }
+
+    /// Consumes the next word. Once the end of the file is reached, every
+    /// word is `Category::Eof`.
+    pub fn next_word(&mut self) -> Word {
+        let word = self.peek_nth(0);
+        if word.category != Category::Eof {
+            self.cursor += 1;
+            self.release_words();
+        }
+        word
+    }
+
+    /// Looks at the word `n` words ahead without consuming any, the next
+    /// word being the 0th. The diagnostics of the words scanned meanwhile
+    /// are reported right away, and only once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` isn't less than the lookahead.
+    pub fn peek_nth(&mut self, n: usize) -> Word {
+        assert!(
+            n < self.lookahead,
+            "can't look {} words ahead with a lookahead of {}",
+            n,
+            self.lookahead
+        );
+        let index = self.cursor + n - self.first;
+        while self.words.len() <= index {
+            if self
+                .words
+                .back()
+                .is_some_and(|word| word.category == Category::Eof)
+            {
+                return Word::eof(self.scanner.source_file.end_pos());
+            }
+            let word = self.scan_word();
+            self.words.push_back(word);
+        }
+        self.words[index]
+    }
+
+    /// Takes a checkpoint at the next word. The words from there on are
+    /// kept until the checkpoint is rewound to or released.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints.push(self.cursor);
+        Checkpoint(self.cursor)
+    }
+
+    /// Goes back to a position taken earlier, so that the words after it
+    /// are consumed again.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        assert!(checkpoint.0 <= self.cursor, "can't rewind to a later word");
+        self.cursor = checkpoint.0;
+        self.release(checkpoint);
+    }
+
+    /// Gives up on going back to a checkpoint, so that the words before the
+    /// next one can be dropped.
+    pub fn release(&mut self, checkpoint: Checkpoint) {
+        let index = self
+            .checkpoints
+            .iter()
+            .position(|&pos| pos == checkpoint.0)
+            .expect("has to be a checkpoint of this stream!");
+        self.checkpoints.swap_remove(index);
+        self.release_words();
+    }
+
+    /// Drops the words consumed which no live checkpoint can go back to.
+    fn release_words(&mut self) {
+        let keep_from = self
+            .checkpoints
+            .iter()
+            .fold(self.cursor, |keep_from, &pos| keep_from.min(pos));
+        while self.first < keep_from && self.words.pop_front().is_some() {
+            self.first += 1;
+        }
     }
 
-    pub fn next(&mut self) -> Word {
+    fn scan_word(&mut self) -> Word {
+        if self.stopped {
+            return Word::eof(self.scanner.source_file.end_pos());
+        }
         match self.scanner.next_word() {
-            Ok(word) => return word,
-            Err(diag) => self.handler.report(diag),
-        };
-        self.next()
+            Ok(word) => word,
+            Err(diag) => {
+                if !self.handler.report(diag) {
+                    self.stopped = true;
+                    return Word::eof(self.scanner.source_file.end_pos());
+                }
+                Word {
+                    category: Category::Error,
+                    lexeme: self.scanner.last_span(),
+                }
+            }
+        }
     }
 }
 
+/// Yields the words of the stream up to the end of the file, without the
+/// final `Category::Eof` word.
+impl<'a> Iterator for WordStream<'a> {
+    type Item = Word;
+
+    fn next(&mut self) -> Option<Word> {
+        let word = self.next_word();
+        if word.category == Category::Eof {
+            None
+        } else {
+            Some(word)
+        }
+    }
+}
+
+/// Splits a file into words, leaving out the end of file. Text the scanner
+/// rejects makes `Category::Error` words, and isn't reported.
+pub fn tokenize(file: &SourceFile) -> Vec<Word> {
+    let handler = errors::Handler::with_ignoring_emitter();
+    let scanner = Scanner::new(Rc::new(file.clone()));
+    WordStream::new(scanner, &handler).collect()
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Category, Diag, Scanner, Word};
+    use super::{tokenize, Category, Diag, Scanner, Word, WordStream};
+    use errors::Handler;
     use source_map::{BytePos, SourceFile, Span};
+    use std::cell::RefCell;
     use std::rc::Rc;
 
     fn create_scanner(src: &str) -> (Scanner, Rc<SourceFile>) {
@@ -452,6 +825,23 @@ mod test {
         (scanner, file)
     }
 
+    #[test]
+    fn test_scan_comments() {
+        let (mut sc, file) = create_scanner("// first\r\nx / y // second\n//");
+
+        assert_eq!(Category::Ident, sc.next_word().unwrap().category);
+        assert_eq!(Category::Slash, sc.next_word().unwrap().category);
+        assert_eq!(Category::Ident, sc.next_word().unwrap().category);
+        assert_eq!(Category::Eof, sc.next_word().unwrap().category);
+
+        let comments = sc
+            .comments()
+            .iter()
+            .map(|&span| file.span_to_snippet(span))
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["// first", "// second", "//"], comments);
+    }
+
     #[test]
     fn test_scan_punctuators() {
         let (mut sc, _) = create_scanner("( ) { } != ! == = >= > <= < * / + - , : ;");
@@ -478,6 +868,23 @@ mod test {
         assert_eq!(Category::Eof, sc.next_word().unwrap().category);
     }
 
+    #[test]
+    fn test_scan_operator_punctuators() {
+        let (mut sc, _) = create_scanner("** % & | ^ << >> * < >");
+
+        assert_eq!(Category::StarStar, sc.next_word().unwrap().category);
+        assert_eq!(Category::Percent, sc.next_word().unwrap().category);
+        assert_eq!(Category::Amp, sc.next_word().unwrap().category);
+        assert_eq!(Category::Pipe, sc.next_word().unwrap().category);
+        assert_eq!(Category::Caret, sc.next_word().unwrap().category);
+        assert_eq!(Category::Shl, sc.next_word().unwrap().category);
+        assert_eq!(Category::Shr, sc.next_word().unwrap().category);
+        assert_eq!(Category::Star, sc.next_word().unwrap().category);
+        assert_eq!(Category::Lt, sc.next_word().unwrap().category);
+        assert_eq!(Category::Gt, sc.next_word().unwrap().category);
+        assert_eq!(Category::Eof, sc.next_word().unwrap().category);
+    }
+
     #[test]
     fn test_scan_identifiers() {
         let (mut sc, sf) = create_scanner("a abc abc123 123abc _a_");
@@ -560,6 +967,125 @@ mod test {
         assert_eq!(Category::Eof, category);
     }
 
+    #[test]
+    fn test_scan_loop_keywords() {
+        let (mut sc, sf) = create_scanner("for to downto repeat until break continue");
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::For, category);
+        assert_eq!("for", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::To, category);
+        assert_eq!("to", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Downto, category);
+        assert_eq!("downto", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Repeat, category);
+        assert_eq!("repeat", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Until, category);
+        assert_eq!("until", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Break, category);
+        assert_eq!("break", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Continue, category);
+        assert_eq!("continue", sf.span_to_snippet(lexeme));
+
+        let Word { category, .. } = sc.next_word().unwrap();
+        assert_eq!(Category::Eof, category);
+    }
+
+    #[test]
+    fn test_scan_operator_keywords() {
+        let (mut sc, sf) = create_scanner("mod div");
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Mod, category);
+        assert_eq!("mod", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Div, category);
+        assert_eq!("div", sf.span_to_snippet(lexeme));
+
+        let Word { category, .. } = sc.next_word().unwrap();
+        assert_eq!(Category::Eof, category);
+    }
+
+    #[test]
+    fn test_scan_unit_keywords() {
+        let (mut sc, sf) = create_scanner("unit uses const");
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Unit, category);
+        assert_eq!("unit", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Uses, category);
+        assert_eq!("uses", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Const, category);
+        assert_eq!("const", sf.span_to_snippet(lexeme));
+
+        let Word { category, .. } = sc.next_word().unwrap();
+        assert_eq!(Category::Eof, category);
+    }
+
+    #[test]
+    fn test_scan_case_keywords() {
+        let (mut sc, sf) = create_scanner("case of true false");
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Case, category);
+        assert_eq!("case", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::Of, category);
+        assert_eq!("of", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::True, category);
+        assert_eq!("true", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::False, category);
+        assert_eq!("false", sf.span_to_snippet(lexeme));
+
+        let Word { category, .. } = sc.next_word().unwrap();
+        assert_eq!(Category::Eof, category);
+    }
+
+    #[test]
+    fn test_scan_int_range() {
+        let (mut sc, sf) = create_scanner("3..5 3.5");
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::NumConst { is_float: false }, category);
+        assert_eq!("3", sf.span_to_snippet(lexeme));
+
+        let Word { category, .. } = sc.next_word().unwrap();
+        assert_eq!(Category::DotDot, category);
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::NumConst { is_float: false }, category);
+        assert_eq!("5", sf.span_to_snippet(lexeme));
+
+        let Word { category, lexeme } = sc.next_word().unwrap();
+        assert_eq!(Category::NumConst { is_float: true }, category);
+        assert_eq!("3.5", sf.span_to_snippet(lexeme));
+
+        let Word { category, .. } = sc.next_word().unwrap();
+        assert_eq!(Category::Eof, category);
+    }
+
     #[test]
     fn test_scan_string_literals() {
         let (mut sc, sf) = create_scanner("\"\" \"foo bar 123 !!!\"");
@@ -577,6 +1103,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::match_like_matches_macro)]
     fn test_nonterminating_string_literal() {
         let (mut sc, _) = create_scanner("\"abc");
 
@@ -594,6 +1121,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::match_like_matches_macro)]
     fn test_invalid_newline_in_string_literal() {
         let (mut sc, _) = create_scanner("\"abc\n\"");
 
@@ -616,6 +1144,23 @@ mod test {
         assert_eq!(Category::Eof, category);
     }
 
+    #[test]
+    fn test_invalid_crlf_in_string_literal() {
+        let (mut sc, _) = create_scanner("\"ab\r\n");
+
+        let word = sc.next_word();
+        assert!(matches!(
+            word,
+            Err(Diag::MissingTerminatingStringMark {
+                str_start_pos: BytePos(0),
+                eol_pos: BytePos(3),
+            })
+        ));
+
+        let Word { category, .. } = sc.next_word().unwrap();
+        assert_eq!(Category::Eof, category);
+    }
+
     #[test]
     fn test_scan_numbers() {
         let (mut sc, sf) = create_scanner("0 0123 3.14 3.14e42 0e0 0E0 0e+0 0e-0 0E+0 0E-0");
@@ -665,6 +1210,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::match_like_matches_macro)]
     fn test_missing_exponent_digits() {
         let (mut sc, _) = create_scanner("0e");
 
@@ -679,4 +1225,188 @@ mod test {
         let Word { category, .. } = sc.next_word().unwrap();
         assert_eq!(Category::Eof, category);
     }
+
+    #[test]
+    fn test_scan_unknown_characters() {
+        let (mut sc, sf) = create_scanner("a $€$ . b..c");
+
+        assert_eq!(Category::Ident, sc.next_word().unwrap().category);
+        assert_eq!(
+            Err(Diag::UnknownCharacter {
+                span: Span {
+                    start: BytePos(2),
+                    end: BytePos(7),
+                },
+                count: 3,
+            }),
+            sc.next_word()
+        );
+        assert_eq!("$€$", sf.span_to_snippet(sc.last_span()));
+        assert_eq!(
+            Err(Diag::UnknownCharacter {
+                span: Span {
+                    start: BytePos(8),
+                    end: BytePos(9),
+                },
+                count: 1,
+            }),
+            sc.next_word()
+        );
+        assert_eq!(Category::Ident, sc.next_word().unwrap().category);
+        assert_eq!(Category::DotDot, sc.next_word().unwrap().category);
+        assert_eq!(Category::Ident, sc.next_word().unwrap().category);
+        assert_eq!(Category::Eof, sc.next_word().unwrap().category);
+    }
+
+    #[test]
+    fn test_word_stream_error_words() {
+        let (sc, sf) = create_scanner("x 1a \"y");
+        let handler = Handler::with_ignoring_emitter();
+        let mut word_stream = WordStream::new(sc, &handler);
+
+        assert_eq!(Category::Ident, word_stream.next_word().category);
+        let Word { category, lexeme } = word_stream.next_word();
+        assert_eq!(Category::Error, category);
+        assert_eq!("1a", sf.span_to_snippet(lexeme));
+        let Word { category, lexeme } = word_stream.next_word();
+        assert_eq!(Category::Error, category);
+        assert_eq!("\"y", sf.span_to_snippet(lexeme));
+        assert_eq!(Category::Eof, word_stream.next_word().category);
+        assert_eq!(2, handler.error_count());
+    }
+
+    #[test]
+    fn test_word_stream_stops_with_handler() {
+        let (sc, _) = create_scanner("x $ y $ z");
+        let handler = Handler::with_ignoring_emitter().with_max_errors(2);
+        let mut word_stream = WordStream::new(sc, &handler);
+
+        assert_eq!(Category::Ident, word_stream.next_word().category);
+        assert_eq!(Category::Error, word_stream.next_word().category);
+        assert_eq!(Category::Ident, word_stream.next_word().category);
+        assert_eq!(Category::Eof, word_stream.next_word().category);
+        assert_eq!(Category::Eof, word_stream.next_word().category);
+    }
+
+    #[test]
+    fn test_word_stream_many_errors() {
+        let (sc, _) = create_scanner(&"$ ".repeat(100_000));
+        let handler = Handler::with_ignoring_emitter();
+        let mut word_stream = WordStream::new(sc, &handler);
+
+        let mut errors = 0;
+        while word_stream.next_word().category == Category::Error {
+            errors += 1;
+        }
+        assert_eq!(100_000, errors);
+    }
+
+    #[test]
+    fn test_word_stream_peek_nth() {
+        let (sc, sf) = create_scanner("x = f(1);");
+        let handler = Handler::with_ignoring_emitter();
+        let mut word_stream = WordStream::new(sc, &handler);
+
+        assert_eq!(Category::Ident, word_stream.peek_nth(0).category);
+        let Word { category, lexeme } = word_stream.peek_nth(3);
+        assert_eq!(Category::OpenParen, category);
+        assert_eq!("(", sf.span_to_snippet(lexeme));
+        assert_eq!(Category::Eof, word_stream.peek_nth(10).category);
+
+        assert_eq!(Category::Ident, word_stream.next_word().category);
+        assert_eq!(Category::Eq, word_stream.next_word().category);
+        assert_eq!(Category::Semi, word_stream.peek_nth(4).category);
+    }
+
+    #[test]
+    fn test_word_stream_rewind() {
+        let (sc, _) = create_scanner("a b $ c");
+        let diags = Rc::new(RefCell::new(vec![]));
+        let handler = {
+            let diags = diags.clone();
+            Handler::with_emitter(move |diag, _| diags.borrow_mut().push(diag))
+        };
+        let mut word_stream = WordStream::new(sc, &handler);
+
+        assert_eq!(Category::Ident, word_stream.next_word().category);
+        let checkpoint = word_stream.checkpoint();
+        let categories = (&mut word_stream)
+            .map(|word| word.category)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![Category::Ident, Category::Error, Category::Ident],
+            categories
+        );
+        assert_eq!(Category::Eof, word_stream.next_word().category);
+
+        word_stream.rewind(checkpoint);
+        assert_eq!(3, word_stream.count());
+        handler.flush();
+        assert_eq!(1, diags.borrow().len());
+    }
+
+    #[test]
+    fn test_word_stream_releases_words() {
+        let (sc, _) = create_scanner(&"x ".repeat(1000));
+        let handler = Handler::with_ignoring_emitter();
+        let mut word_stream = WordStream::new(sc, &handler).with_lookahead(4);
+
+        assert_eq!(Category::Ident, word_stream.peek_nth(3).category);
+        assert_eq!(4, word_stream.words.len());
+        for _ in 0..500 {
+            word_stream.next_word();
+        }
+        assert!(word_stream.words.len() <= 4);
+
+        // The words after a live checkpoint are kept.
+        let checkpoint = word_stream.checkpoint();
+        for _ in 0..100 {
+            word_stream.next_word();
+        }
+        assert_eq!(100, word_stream.words.len());
+        word_stream.release(checkpoint);
+        assert_eq!(0, word_stream.words.len());
+
+        let checkpoint = word_stream.checkpoint();
+        for _ in 0..10 {
+            word_stream.next_word();
+        }
+        word_stream.rewind(checkpoint);
+        assert_eq!(10, word_stream.words.len());
+        assert_eq!(400, word_stream.count());
+    }
+
+    #[test]
+    #[should_panic(expected = "can't look 4 words ahead with a lookahead of 4")]
+    fn test_word_stream_lookahead_limit() {
+        let (sc, _) = create_scanner("a b c d e");
+        let handler = Handler::with_ignoring_emitter();
+        WordStream::new(sc, &handler).with_lookahead(4).peek_nth(4);
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let sf = SourceFile::new("test".into(), "if x { y = 1..2; } @".into());
+
+        let categories = tokenize(&sf)
+            .into_iter()
+            .map(|word| word.category)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                Category::If,
+                Category::Ident,
+                Category::OpenCurly,
+                Category::Ident,
+                Category::Eq,
+                Category::NumConst { is_float: false },
+                Category::DotDot,
+                Category::NumConst { is_float: false },
+                Category::Semi,
+                Category::CloseCurly,
+                Category::Error,
+            ],
+            categories
+        );
+    }
 }
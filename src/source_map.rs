@@ -1,15 +1,23 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io;
 use std::ops::{Add, Sub};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 /// A byte position or offset into a source file's text buffer. This is used to
 /// map ASTs to soure code by indicating the position from which an AST node
 /// was parsed.
-#[derive(Clone, Copy, PartialEq, Debug)]
+///
+/// Positions are global to a `SourceMap`: each of its files takes a range of
+/// positions which doesn't overlap with the others.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Hash, Debug)]
 pub struct BytePos(pub usize);
 
 /// A range (span) into a source file's text buffer, indicating a region of
 /// text.
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Span {
     pub start: BytePos,
     pub end: BytePos,
@@ -55,10 +63,50 @@ impl Sub for BytePos {
 }
 
 /// A source location containing line and column number. Useful for diagnostics.
+///
+/// Columns count from 0 and come in different units, since tools disagree on
+/// how to count non-ASCII characters.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Loc {
     pub line: usize,
+    /// Column in bytes.
     pub col: BytePos,
+    /// Column in characters (i.e. Unicode scalar values).
+    pub col_char: usize,
+    /// Column in UTF-16 code units, as used by the Language Server Protocol.
+    pub col_utf16: usize,
+}
+
+/// The name of a source file.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FileName {
+    /// A file read from the file system.
+    Real(PathBuf),
+    /// A file made from a string, such as a snippet typed by the user.
+    Virtual(String),
+}
+
+impl<'a> From<&'a str> for FileName {
+    fn from(name: &'a str) -> FileName {
+        FileName::Virtual(name.to_owned())
+    }
+}
+
+impl fmt::Display for FileName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FileName::Real(ref path) => write!(f, "{}", path.display()),
+            FileName::Virtual(ref name) => write!(f, "<{}>", name),
+        }
+    }
+}
+
+/// A unit in which columns are counted.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColUnit {
+    Byte,
+    Char,
+    Utf16,
 }
 
 /// This holds information of a given source file, such as the source name,
@@ -67,11 +115,14 @@ pub struct Loc {
 /// A `SourceFile` assists in reporting errors and mapping ASTs to source code,
 /// providing an interface for text information lookup, such as: line and
 /// column number for a given position; text snippets from spans etc.
+#[derive(Clone)]
 pub struct SourceFile {
     /// File's content.
     pub src: Rc<String>,
     /// Name of the loaded file.
-    name: String,
+    name: FileName,
+    /// Position of the file's first byte.
+    start_pos: BytePos,
     /// Byte positions following every new line.
     lines: Vec<BytePos>,
 }
@@ -80,57 +131,257 @@ impl SourceFile {
     /// Constructs a new `SourceFile` from a string (the text buffer).
     ///
     /// Line positions are precomputed by this function.
-    pub fn new(name: String, src: String) -> SourceFile {
-        let mut lines = vec![BytePos(0)];
+    pub fn new(name: FileName, src: String) -> SourceFile {
+        SourceFile::with_start_pos(name, src, BytePos(0))
+    }
+
+    fn with_start_pos(name: FileName, src: String, start_pos: BytePos) -> SourceFile {
+        let mut lines = vec![start_pos];
 
         for (i, b) in src.bytes().enumerate() {
             if b == b'\n' {
-                lines.push(BytePos(i + 1));
+                lines.push(start_pos + BytePos(i + 1));
             }
         }
 
-        lines.push(BytePos(src.len()));
+        lines.push(start_pos + BytePos(src.len()));
 
         SourceFile {
             src: Rc::new(src),
             name,
+            start_pos,
             lines,
         }
     }
 
+    /// Returns the name of the loaded file.
+    pub fn name(&self) -> &FileName {
+        &self.name
+    }
+
+    /// Returns the position of the file's first byte.
+    pub fn start_pos(&self) -> BytePos {
+        self.start_pos
+    }
+
+    /// Returns the position right after the file's last byte.
+    pub fn end_pos(&self) -> BytePos {
+        self.start_pos + BytePos(self.src.len())
+    }
+
     /// Returns a string slice represented by a `Span`.
     pub fn span_to_snippet(&self, s: Span) -> &str {
-        &self.src[s.start.0..s.end.0]
+        let start = s.start - self.start_pos;
+        let end = s.end - self.start_pos;
+        &self.src[start.0..end.0]
     }
 
     /// Returns the line number for a `BytePos` if such is valid.
     pub fn lookup_line_index(&self, pos: BytePos) -> Option<usize> {
-        let pos_index = pos.to_usize();
-        for (i, line_pos) in self.lines.iter().enumerate() {
-            let line_pos_index = line_pos.to_usize();
-            if pos_index < line_pos_index {
-                return Some(i - 1);
+        // The number of lines starting at or before `pos`, where the last
+        // "line" is the end of the file, which is no valid position.
+        let line_count = self.lines.partition_point(|&line_pos| line_pos <= pos);
+        if line_count == 0 || line_count == self.lines.len() {
+            None
+        } else {
+            Some(line_count - 1)
+        }
+    }
+
+    /// Like `lookup_line_index`, but the end of the file belongs to the last
+    /// line, so that errors at the end of the input can be located.
+    fn lookup_located_line_index(&self, pos: BytePos) -> Option<usize> {
+        if pos == self.end_pos() {
+            Some(self.lines.len() - 2)
+        } else {
+            self.lookup_line_index(pos)
+        }
+    }
+
+    /// Returns the `BytePos` of a line (counting from 1, as in `Loc`) and a
+    /// column (counting from 0) in the given unit, if the line exists.
+    /// Columns past the end of the line are clamped to its end, and columns
+    /// in the middle of a character are moved past it.
+    pub fn lookup_pos(&self, line: usize, col: usize, unit: ColUnit) -> Option<BytePos> {
+        let line_index = line.checked_sub(1)?;
+        let text = self.line_text(line_index)?;
+        let mut offset = 0;
+        let mut units = 0;
+        for c in text.chars() {
+            if units >= col {
+                break;
             }
+            units += match unit {
+                ColUnit::Byte => c.len_utf8(),
+                ColUnit::Char => 1,
+                ColUnit::Utf16 => c.len_utf16(),
+            };
+            offset += c.len_utf8();
         }
+        Some(self.lines[line_index] + BytePos(offset))
+    }
 
-        None
+    /// Replaces the text of a span, updating the line table without
+    /// rescanning the whole file.
+    ///
+    /// Positions following the span are shifted, so this is meant for files
+    /// standing on their own, such as a document opened in an editor, rather
+    /// than for files followed by others in a `SourceMap`.
+    pub fn apply_edit(&mut self, span: Span, text: &str) {
+        let start = span.start - self.start_pos;
+        let end = span.end - self.start_pos;
+        Rc::make_mut(&mut self.src).replace_range(start.0..end.0, text);
+
+        // Lines starting inside the span lose their line breaks, lines
+        // following it are shifted, and the text may break new lines.
+        self.lines.pop();
+        let first_removed = self.lines.partition_point(|&pos| pos <= span.start);
+        let first_shifted = self.lines.partition_point(|&pos| pos <= span.end);
+        let removed_len = end.0 - start.0;
+        let shifted = self.lines[first_shifted..]
+            .iter()
+            .map(|&pos| BytePos(pos.0 + text.len() - removed_len))
+            .collect::<Vec<_>>();
+        self.lines.truncate(first_removed);
+        self.lines.extend(
+            text.match_indices('\n')
+                .map(|(i, _)| span.start + BytePos(i + 1)),
+        );
+        self.lines.extend(shifted);
+        let end_pos = self.end_pos();
+        self.lines.push(end_pos);
     }
 
     /// Returns the source information (line/column number etc) of a
     /// `BytePos` if such is valid.
     pub fn lookup_source_location(&self, pos: BytePos) -> Option<Loc> {
-        self.lookup_line_index(pos).map(|line_index| {
-            let line = line_index + 1;
-            let col = pos - self.lines[line_index];
-
-            Loc { line, col }
+        let line_index = self.lookup_located_line_index(pos)?;
+        let line_prefix = self.line_prefix(line_index, pos)?;
+
+        Some(Loc {
+            line: line_index + 1,
+            col: pos - self.lines[line_index],
+            col_char: line_prefix.chars().count(),
+            col_utf16: line_prefix.chars().map(char::len_utf16).sum(),
         })
     }
+
+    /// Returns the column at which a `BytePos` is displayed, where a tab
+    /// advances to the next multiple of `tab_width` columns.
+    pub fn lookup_display_col(&self, pos: BytePos, tab_width: usize) -> Option<usize> {
+        let line_index = self.lookup_located_line_index(pos)?;
+        let tab_width = tab_width.max(1);
+        let col = self
+            .line_prefix(line_index, pos)?
+            .chars()
+            .fold(0, |col, c| {
+                if c == '\t' {
+                    col + tab_width - col % tab_width
+                } else {
+                    col + 1
+                }
+            });
+        Some(col)
+    }
+
+    /// Returns the text of a line, counting from 0, without its line break
+    /// (either `\n` or `\r\n`).
+    pub fn line_text(&self, line_index: usize) -> Option<&str> {
+        let start = *self.lines.get(line_index)?;
+        let end = *self.lines.get(line_index + 1)?;
+        let text = self.span_to_snippet(Span { start, end });
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// Returns the text of a line up to a position in it, if the position
+    /// doesn't fall in the middle of a character.
+    fn line_prefix(&self, line_index: usize, pos: BytePos) -> Option<&str> {
+        let start = self.lines[line_index] - self.start_pos;
+        let end = pos - self.start_pos;
+        self.src.get(start.0..end.0)
+    }
+}
+
+/// The source map, which holds every source file of a program.
+///
+/// Files are given consecutive, non-overlapping ranges of positions as they
+/// are added, so any `BytePos` can be traced back to the file it came from.
+#[derive(Default)]
+pub struct SourceMap {
+    files: RefCell<Vec<Rc<SourceFile>>>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    /// Adds a file to the source map, placing it after every other file.
+    pub fn add_file(&self, name: FileName, src: String) -> Rc<SourceFile> {
+        let mut files = self.files.borrow_mut();
+        // Leaves a gap of one position between files, so that a file's end
+        // position isn't the start position of the next one.
+        let start_pos = files
+            .last()
+            .map_or(BytePos(0), |file| file.end_pos() + BytePos(1));
+        let file = Rc::new(SourceFile::with_start_pos(name, src, start_pos));
+        files.push(file.clone());
+        file
+    }
+
+    /// Reads a file from the file system and adds it to the source map.
+    pub fn load_file(&self, path: &Path) -> io::Result<Rc<SourceFile>> {
+        let src = fs::read_to_string(path)?;
+        Ok(self.add_file(FileName::Real(path.to_path_buf()), src))
+    }
+
+    /// Returns the file a position belongs to, if any.
+    pub fn lookup_file(&self, pos: BytePos) -> Option<Rc<SourceFile>> {
+        let files = self.files.borrow();
+        // Files are sorted by their positions, so the last file starting at
+        // or before `pos` is the only one which may contain it.
+        let file_count = files.partition_point(|file| file.start_pos() <= pos);
+        let file = &files[file_count.checked_sub(1)?];
+        if pos <= file.end_pos() {
+            Some(file.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the file of a position along with the position's line and
+    /// column in it.
+    pub fn lookup_source_location(&self, pos: BytePos) -> Option<(Rc<SourceFile>, Loc)> {
+        let file = self.lookup_file(pos)?;
+        let loc = file.lookup_source_location(pos)?;
+        Some((file, loc))
+    }
+
+    /// Returns the text a span covers.
+    pub fn span_to_snippet(&self, span: Span) -> Option<String> {
+        let file = self.lookup_file(span.start)?;
+        if span.end > file.end_pos() {
+            return None;
+        }
+        Some(file.span_to_snippet(span).to_owned())
+    }
+
+    /// Formats a position as `path:line:col`, with the column counted in
+    /// characters from 1, as most tools expect.
+    pub fn pos_to_string(&self, pos: BytePos) -> String {
+        match self.lookup_source_location(pos) {
+            Some((file, loc)) => format!("{}:{}:{}", file.name(), loc.line, loc.col_char + 1),
+            None => "<unknown>".to_owned(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{BytePos, Loc, SourceFile, Span};
+    use super::{BytePos, ColUnit, FileName, Loc, SourceFile, SourceMap, Span};
+    use std::env;
+    use std::fs;
 
     fn create_source_file() -> SourceFile {
         SourceFile::new(
@@ -185,6 +436,8 @@ mod tests {
             Some(Loc {
                 line: 1,
                 col: BytePos(0),
+                col_char: 0,
+                col_utf16: 0,
             }),
             source_file.lookup_source_location(BytePos(0))
         );
@@ -193,6 +446,8 @@ mod tests {
             Some(Loc {
                 line: 1,
                 col: BytePos(3),
+                col_char: 3,
+                col_utf16: 3,
             }),
             source_file.lookup_source_location(BytePos(3))
         );
@@ -201,6 +456,8 @@ mod tests {
             Some(Loc {
                 line: 2,
                 col: BytePos(0),
+                col_char: 0,
+                col_utf16: 0,
             }),
             source_file.lookup_source_location(BytePos(12))
         );
@@ -209,10 +466,273 @@ mod tests {
             Some(Loc {
                 line: 2,
                 col: BytePos(3),
+                col_char: 3,
+                col_utf16: 3,
             }),
             source_file.lookup_source_location(BytePos(15))
         );
 
-        assert_eq!(None, source_file.lookup_source_location(BytePos(37)));
+        assert_eq!(None, source_file.lookup_source_location(BytePos(38)));
+    }
+
+    #[test]
+    fn lookup_end_of_file_location_test() {
+        let source_file = create_source_file();
+        assert_eq!(
+            Some(Loc {
+                line: 4,
+                col: BytePos(0),
+                col_char: 0,
+                col_utf16: 0,
+            }),
+            source_file.lookup_source_location(BytePos(37))
+        );
+
+        let source_file = SourceFile::new("test".into(), "ab\ncd".into());
+        assert_eq!(None, source_file.lookup_line_index(BytePos(5)));
+        assert_eq!(
+            Some(Loc {
+                line: 2,
+                col: BytePos(2),
+                col_char: 2,
+                col_utf16: 2,
+            }),
+            source_file.lookup_source_location(BytePos(5))
+        );
+        assert_eq!(Some(2), source_file.lookup_display_col(BytePos(5), 4));
+
+        let source_file = SourceFile::new("test".into(), "".into());
+        assert_eq!(
+            Some(Loc {
+                line: 1,
+                col: BytePos(0),
+                col_char: 0,
+                col_utf16: 0,
+            }),
+            source_file.lookup_source_location(BytePos(0))
+        );
+    }
+
+    #[test]
+    fn source_map_positions_test() {
+        let source_map = SourceMap::new();
+        let first = source_map.add_file("first".into(), "ab\ncd".into());
+        let second = source_map.add_file("second".into(), "ef\ngh\n".into());
+
+        assert_eq!(BytePos(0), first.start_pos());
+        assert_eq!(BytePos(5), first.end_pos());
+        assert_eq!(BytePos(6), second.start_pos());
+        assert_eq!(BytePos(12), second.end_pos());
+
+        let name_of = |pos| source_map.lookup_file(pos).unwrap().name().to_string();
+        assert_eq!("<first>", name_of(BytePos(0)));
+        assert_eq!("<first>", name_of(BytePos(5)));
+        assert_eq!("<second>", name_of(BytePos(6)));
+        assert!(source_map.lookup_file(BytePos(13)).is_none());
+
+        let s = Span {
+            start: BytePos(9),
+            end: BytePos(11),
+        };
+        assert_eq!("gh", second.span_to_snippet(s));
+        assert_eq!(
+            Some(Loc {
+                line: 2,
+                col: BytePos(1),
+                col_char: 1,
+                col_utf16: 1,
+            }),
+            second.lookup_source_location(BytePos(10))
+        );
+    }
+
+    #[test]
+    fn source_map_locations_test() {
+        let source_map = SourceMap::new();
+        source_map.add_file("first".into(), "ab\ncd".into());
+        source_map.add_file("second".into(), "ef\ngh\n".into());
+
+        let s = Span {
+            start: BytePos(3),
+            end: BytePos(5),
+        };
+        assert_eq!(Some("cd".into()), source_map.span_to_snippet(s));
+        let s = Span {
+            start: BytePos(3),
+            end: BytePos(7),
+        };
+        assert_eq!(None, source_map.span_to_snippet(s));
+
+        assert_eq!("<first>:1:1", source_map.pos_to_string(BytePos(0)));
+        assert_eq!("<first>:2:2", source_map.pos_to_string(BytePos(4)));
+        assert_eq!("<second>:2:1", source_map.pos_to_string(BytePos(9)));
+        assert_eq!("<first>:2:3", source_map.pos_to_string(BytePos(5)));
+        assert_eq!("<unknown>", source_map.pos_to_string(BytePos(40)));
+    }
+
+    #[test]
+    fn source_map_load_file_test() {
+        let path = env::temp_dir().join("blaise-source-map-load-file.bl");
+        fs::write(&path, "program p;\n").unwrap();
+
+        let source_map = SourceMap::new();
+        let file = source_map.load_file(&path).unwrap();
+        assert_eq!(&FileName::Real(path.clone()), file.name());
+        assert_eq!(
+            format!("{}:1:9", path.display()),
+            source_map.pos_to_string(BytePos(8))
+        );
+
+        assert!(source_map
+            .load_file(&env::temp_dir().join("blaise-no-such-file.bl"))
+            .is_err());
+    }
+
+    #[test]
+    fn lookup_unicode_columns_test() {
+        let source_file = SourceFile::new("test".into(), "let s = \"é😀\"; x\n".into());
+
+        // The `x`, after a two byte `é` and a four byte emoji, which takes
+        // two UTF-16 code units.
+        assert_eq!(
+            Some(Loc {
+                line: 1,
+                col: BytePos(18),
+                col_char: 14,
+                col_utf16: 15,
+            }),
+            source_file.lookup_source_location(BytePos(18))
+        );
+
+        // Positions in the middle of a character have no column.
+        assert_eq!(None, source_file.lookup_source_location(BytePos(10)));
+    }
+
+    #[test]
+    fn lookup_display_columns_test() {
+        let source_file = SourceFile::new("test".into(), "\tab\tc\n  \tx".into());
+
+        assert_eq!(Some(4), source_file.lookup_display_col(BytePos(1), 4));
+        assert_eq!(Some(8), source_file.lookup_display_col(BytePos(4), 4));
+        assert_eq!(Some(4), source_file.lookup_display_col(BytePos(4), 1));
+        assert_eq!(Some(8), source_file.lookup_display_col(BytePos(9), 8));
+    }
+
+    #[test]
+    fn crlf_line_endings_test() {
+        let source_file = SourceFile::new("test".into(), "ab\r\ncd\r\n\r\ne".into());
+
+        assert_eq!(Some("ab"), source_file.line_text(0));
+        assert_eq!(Some("cd"), source_file.line_text(1));
+        assert_eq!(Some(""), source_file.line_text(2));
+        assert_eq!(Some("e"), source_file.line_text(3));
+        assert_eq!(None, source_file.line_text(4));
+
+        assert_eq!(
+            Some(Loc {
+                line: 2,
+                col: BytePos(1),
+                col_char: 1,
+                col_utf16: 1,
+            }),
+            source_file.lookup_source_location(BytePos(5))
+        );
+        assert_eq!(
+            Some(Loc {
+                line: 4,
+                col: BytePos(0),
+                col_char: 0,
+                col_utf16: 0,
+            }),
+            source_file.lookup_source_location(BytePos(10))
+        );
+    }
+
+    #[test]
+    fn lookup_pos_test() {
+        let source_file = SourceFile::new("test".into(), "ab\r\n\"é😀\" x\n".into());
+
+        assert_eq!(
+            Some(BytePos(1)),
+            source_file.lookup_pos(1, 1, ColUnit::Byte)
+        );
+        assert_eq!(
+            Some(BytePos(2)),
+            source_file.lookup_pos(1, 9, ColUnit::Char)
+        );
+        assert_eq!(
+            Some(BytePos(4)),
+            source_file.lookup_pos(2, 0, ColUnit::Utf16)
+        );
+        assert_eq!(
+            Some(BytePos(11)),
+            source_file.lookup_pos(2, 6, ColUnit::Byte)
+        );
+        assert_eq!(
+            Some(BytePos(13)),
+            source_file.lookup_pos(2, 5, ColUnit::Char)
+        );
+        assert_eq!(
+            Some(BytePos(13)),
+            source_file.lookup_pos(2, 6, ColUnit::Utf16)
+        );
+        // In the middle of the emoji.
+        assert_eq!(
+            Some(BytePos(11)),
+            source_file.lookup_pos(2, 3, ColUnit::Utf16)
+        );
+        assert_eq!(None, source_file.lookup_pos(0, 0, ColUnit::Byte));
+        assert_eq!(None, source_file.lookup_pos(4, 0, ColUnit::Byte));
+
+        for pos in &[0, 1, 4, 5, 7, 11, 13] {
+            let loc = source_file.lookup_source_location(BytePos(*pos)).unwrap();
+            assert_eq!(
+                Some(BytePos(*pos)),
+                source_file.lookup_pos(loc.line, loc.col_utf16, ColUnit::Utf16)
+            );
+        }
+    }
+
+    #[test]
+    fn apply_edit_test() {
+        let src = "first line.\nsecond line.\nthird line.\n";
+        let mut source_file = SourceFile::new("test".into(), src.into());
+        let mut expected_src = src.to_owned();
+
+        let edits: &[(usize, usize, &str)] = &[
+            (0, 0, "zeroth\n"),
+            (7, 13, "1st\r\nline"),
+            (30, 31, ""),
+            (15, 30, "a\nb\nc"),
+            (0, 0, ""),
+            (36, 36, "\n\n"),
+        ];
+        for &(start, end, text) in edits {
+            let span = Span {
+                start: BytePos(start),
+                end: BytePos(end),
+            };
+            source_file.apply_edit(span, text);
+            expected_src.replace_range(start..end, text);
+            assert_eq!(expected_src, *source_file.src);
+
+            let rescanned = SourceFile::new("test".into(), source_file.src.to_string());
+            assert_eq!(rescanned.lines, source_file.lines);
+        }
+    }
+
+    #[test]
+    fn apply_edit_keeps_shared_src_test() {
+        let mut source_file = SourceFile::new("test".into(), "ab\ncd".into());
+        let old_src = source_file.src.clone();
+        let span = Span {
+            start: BytePos(1),
+            end: BytePos(4),
+        };
+        source_file.apply_edit(span, "");
+
+        assert_eq!("ab\ncd", *old_src);
+        assert_eq!("ad", *source_file.src);
+        assert_eq!(vec![BytePos(0), BytePos(2)], source_file.lines);
     }
 }
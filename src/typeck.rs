@@ -0,0 +1,718 @@
+use ast::{self, Ty};
+use builtins::{self, Builtin, Native, Natives, Params};
+use errors::{self, Diag, Level};
+use source_map::Span;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// The type checker.
+///
+/// It assigns a type to every expression of a program and verifies that
+/// values are only used where their types are expected. Integers are
+/// implicitly promoted to floats, but the opposite conversion, which loses
+/// precision, has to be explicit (e.g. `int(x)`).
+#[derive(Clone)]
+pub struct TypeChecker<'a> {
+    handler: &'a errors::Handler,
+    builtins: HashMap<ast::Name, Builtin>,
+    natives: HashMap<ast::Name, Rc<Native>>,
+    var_tys: HashMap<ast::Name, Ty>,
+    consts: HashSet<ast::Name>,
+    /// The variables and constants used so far.
+    used: HashSet<ast::Name>,
+    /// Whether the value of a constant is being checked.
+    in_const_expr: bool,
+    has_errors: bool,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(handler: &'a errors::Handler, ident_table: &ast::IdentTable) -> TypeChecker<'a> {
+        TypeChecker::with_natives(handler, ident_table, &Natives::new())
+    }
+
+    /// Creates a type checker which knows the signatures of the functions
+    /// defined by the host.
+    pub fn with_natives(
+        handler: &'a errors::Handler,
+        ident_table: &ast::IdentTable,
+        natives: &Natives,
+    ) -> TypeChecker<'a> {
+        TypeChecker {
+            handler,
+            builtins: builtins::resolve(ident_table),
+            natives: natives.resolve(ident_table),
+            var_tys: HashMap::new(),
+            consts: HashSet::new(),
+            used: HashSet::new(),
+            in_const_expr: false,
+            has_errors: false,
+        }
+    }
+
+    /// Checks the declarations of a unit, making them available to the
+    /// units and program checked afterwards.
+    pub fn check_unit(&mut self, unit: &ast::Unit) {
+        self.check_decls(&unit.consts, &unit.decls);
+    }
+
+    /// Checks a whole program, reporting every type error found, as well
+    /// as the variables it declares but never uses. Returns whether the
+    /// program, as well as the units checked before it, is well-typed.
+    pub fn check_program(&mut self, program: &ast::Program) -> bool {
+        self.check_decls(&program.consts, &program.decls);
+
+        for stmt in &program.stmts {
+            self.check_stmt(stmt);
+        }
+
+        for decl in &program.decls {
+            if !self.used.contains(&decl.ident.name) {
+                self.report(Diag::UnusedVariable {
+                    span: decl.ident.span,
+                });
+            }
+        }
+
+        !self.has_errors
+    }
+
+    /// Checks code typed into the REPL on top of the declarations checked
+    /// before, which it may declare again with other types. Returns whether
+    /// the code is well-typed, regardless of errors found before.
+    pub fn check_items(&mut self, items: &ast::Items) -> bool {
+        self.has_errors = false;
+        for decl in &items.decls {
+            self.consts.remove(&decl.ident.name);
+        }
+        self.check_decls(&items.consts, &items.decls);
+
+        for stmt in &items.stmts {
+            self.check_stmt(stmt);
+        }
+
+        !self.has_errors
+    }
+
+    /// Computes the type of an expression typed into the REPL. Returns
+    /// `None` if it's ill-typed, regardless of errors found before.
+    pub fn check_standalone_expr(&mut self, expr: &ast::Expr) -> Option<Ty> {
+        self.has_errors = false;
+        let ty = self.check_expr(expr);
+        if self.has_errors {
+            None
+        } else {
+            ty
+        }
+    }
+
+    fn check_decls(&mut self, consts: &[ast::ConstDecl], decls: &[ast::Decl]) {
+        for decl in decls {
+            self.var_tys.insert(decl.ident.name, decl.ty);
+        }
+
+        for const_decl in consts {
+            self.in_const_expr = true;
+            self.expect_expr_ty(&const_decl.expr, const_decl.ty);
+            self.in_const_expr = false;
+            self.var_tys.insert(const_decl.ident.name, const_decl.ty);
+            self.consts.insert(const_decl.ident.name);
+        }
+    }
+
+    fn report(&mut self, diag: Diag) {
+        if diag.level() == Level::Error {
+            self.has_errors = true;
+        }
+        self.handler.report(diag);
+    }
+
+    fn check_block(&mut self, block: &ast::Block) {
+        for stmt in &block.stmts {
+            self.check_stmt(stmt);
+        }
+    }
+
+    fn check_stmt(&mut self, stmt: &ast::Stmt) {
+        use ast::StmtKind;
+        match stmt.kind {
+            StmtKind::While(ref cond_expr, ref block) => {
+                self.expect_expr_ty(cond_expr, Ty::BoolTy);
+                self.check_block(block);
+            }
+            StmtKind::For(ref ident, ref start_expr, _, ref end_expr, ref block) => {
+                self.check_assignable(ident);
+                if let Some(var_ty) = self.ident_ty(ident) {
+                    self.expect_ty(ident.span, var_ty, Ty::IntTy);
+                }
+                self.expect_expr_ty(start_expr, Ty::IntTy);
+                self.expect_expr_ty(end_expr, Ty::IntTy);
+                self.check_block(block);
+            }
+            StmtKind::Repeat(ref block, ref cond_expr) => {
+                self.check_block(block);
+                self.expect_expr_ty(cond_expr, Ty::BoolTy);
+            }
+            StmtKind::Break | StmtKind::Continue => {}
+            StmtKind::If(ref cond_expr, ref then_block, ref else_block) => {
+                self.expect_expr_ty(cond_expr, Ty::BoolTy);
+                self.check_block(then_block);
+                if let Some(ref else_block) = *else_block {
+                    self.check_block(else_block);
+                }
+            }
+            StmtKind::Case(ref expr, ref arms, ref else_block) => {
+                if let Some(expr_ty) = self.check_expr(expr) {
+                    for pat in arms.iter().flat_map(|arm| arm.pats.iter()) {
+                        if pat_ty(pat) != expr_ty {
+                            self.report(Diag::MismatchedTypes {
+                                span: pat.span,
+                                expected: expr_ty,
+                                found: pat_ty(pat),
+                            });
+                        }
+                    }
+                }
+                for arm in arms {
+                    self.check_block(&arm.block);
+                }
+                if let Some(ref else_block) = *else_block {
+                    self.check_block(else_block);
+                }
+            }
+            StmtKind::Assign(ref ident, ref expr) => {
+                self.check_assignable(ident);
+                if let Some(var_ty) = self.ident_ty(ident) {
+                    self.expect_expr_ty(expr, var_ty);
+                }
+            }
+            StmtKind::BlockStmt(ref block) => self.check_block(block),
+            StmtKind::Call(ref func, ref args) => {
+                self.check_call(func, args);
+            }
+        }
+    }
+
+    /// Checks the arguments of a call against the signature of the called
+    /// builtin or native. If it's defined, returns the type of the value it
+    /// returns, which is `None` for a procedure.
+    fn check_call(&mut self, func: &ast::Ident, args: &[ast::Expr]) -> Option<Option<Ty>> {
+        if let Some(native) = self.natives.get(&func.name).cloned() {
+            self.check_args(func, args, &native.params);
+            return Some(native.ret_ty);
+        }
+        let builtin = match self.builtins.get(&func.name) {
+            Some(&builtin) => builtin,
+            None => {
+                self.report(Diag::UndefinedFunction { span: func.span });
+                for arg in args {
+                    self.check_expr(arg);
+                }
+                return None;
+            }
+        };
+        match builtin.params() {
+            Params::Fixed(param_tys) => self.check_args(func, args, param_tys),
+            Params::Values => {
+                for arg in args {
+                    self.check_write_arg(arg);
+                }
+            }
+            Params::Vars => {
+                for arg in args {
+                    if let ast::ExprKind::Ident(ref ident) = arg.kind {
+                        self.check_assignable(ident);
+                        self.ident_ty(ident);
+                    } else {
+                        self.report(Diag::NotAssignable { span: arg.span });
+                        self.check_expr(arg);
+                    }
+                }
+            }
+        }
+        Some(builtin.ret_ty())
+    }
+
+    /// Checks the arguments of a call to a function which takes exactly
+    /// one argument of each parameter type.
+    fn check_args(&mut self, func: &ast::Ident, args: &[ast::Expr], param_tys: &[Ty]) {
+        if args.len() != param_tys.len() {
+            self.report(Diag::WrongArgCount {
+                span: func.span,
+                expected: param_tys.len(),
+                found: args.len(),
+            });
+            for arg in args {
+                self.check_expr(arg);
+            }
+        } else {
+            for (arg, &param_ty) in args.iter().zip(param_tys) {
+                self.expect_expr_ty(arg, param_ty);
+            }
+        }
+    }
+
+    /// Checks an argument of `write`, which may be formatted.
+    fn check_write_arg(&mut self, arg: &ast::Expr) {
+        if let ast::ExprKind::Formatted(ref expr, ref width, ref prec) = arg.kind {
+            let ty = self.check_expr(expr);
+            self.expect_expr_ty(width, Ty::IntTy);
+            if let Some(ref prec) = *prec {
+                self.expect_expr_ty(prec, Ty::IntTy);
+                match ty {
+                    Some(Ty::FloatTy) | None => {}
+                    Some(ty) => self.report(Diag::PrecisionOnNonFloat {
+                        span: prec.span,
+                        ty,
+                    }),
+                }
+            }
+        } else {
+            self.check_expr(arg);
+        }
+    }
+
+    fn check_assignable(&mut self, ident: &ast::Ident) {
+        if self.consts.contains(&ident.name) {
+            self.report(Diag::AssignToConstant { span: ident.span });
+        }
+    }
+
+    fn ident_ty(&mut self, ident: &ast::Ident) -> Option<Ty> {
+        let ty = self.var_tys.get(&ident.name).cloned();
+        self.used.insert(ident.name);
+        if ty.is_none() {
+            self.report(Diag::UndeclaredVariable { span: ident.span });
+        } else if self.in_const_expr && !self.consts.contains(&ident.name) {
+            self.report(Diag::NonConstantExpr { span: ident.span });
+        }
+        ty
+    }
+
+    /// Checks that an expression has the `expected` type, or one implicitly
+    /// convertible to it.
+    fn expect_expr_ty(&mut self, expr: &ast::Expr, expected: Ty) {
+        if let Some(found) = self.check_expr(expr) {
+            self.expect_ty(expr.span, found, expected);
+        }
+    }
+
+    fn expect_ty(&mut self, span: Span, found: Ty, expected: Ty) {
+        match (found, expected) {
+            _ if found == expected => {}
+            (Ty::IntTy, Ty::FloatTy) => {}
+            (Ty::FloatTy, Ty::IntTy) => self.report(Diag::LossyImplicitConversion {
+                span,
+                from: found,
+                to: expected,
+            }),
+            _ => self.report(Diag::MismatchedTypes {
+                span,
+                expected,
+                found,
+            }),
+        }
+    }
+
+    /// Computes the type of an expression. Returns `None` if the expression
+    /// is ill-typed, in which case an error has already been reported.
+    pub fn check_expr(&mut self, expr: &ast::Expr) -> Option<Ty> {
+        use ast::ExprKind;
+        match expr.kind {
+            ExprKind::BinaryOp(op, ref lhs_expr, ref rhs_expr) => {
+                let lhs = self.check_expr(lhs_expr);
+                let rhs = self.check_expr(rhs_expr);
+                let (lhs, rhs) = (lhs?, rhs?);
+                let ty = binary_op_ty(op, lhs, rhs);
+                if ty.is_none() {
+                    self.report(Diag::InvalidBinaryOperands {
+                        span: expr.span,
+                        op,
+                        lhs,
+                        rhs,
+                    });
+                }
+                ty
+            }
+            // The literal of `-9223372036854775808` doesn't fit in an `int`,
+            // but its negation does.
+            ExprKind::UnaryOp(ast::UnOp::Neg, ref operand)
+                if operand.kind == ExprKind::Lit(ast::Lit::IntLit(i64::MIN.unsigned_abs())) =>
+            {
+                Some(Ty::IntTy)
+            }
+            ExprKind::UnaryOp(op, ref operand) => {
+                let operand_ty = self.check_expr(operand)?;
+                let ty = match (op, operand_ty) {
+                    (ast::UnOp::Neg, Ty::IntTy) | (ast::UnOp::Neg, Ty::FloatTy) => Some(operand_ty),
+                    (ast::UnOp::Not, Ty::BoolTy) => Some(Ty::BoolTy),
+                    _ => None,
+                };
+                if ty.is_none() {
+                    self.report(Diag::InvalidUnaryOperand {
+                        span: expr.span,
+                        op,
+                        ty: operand_ty,
+                    });
+                }
+                ty
+            }
+            ExprKind::Lit(ref lit) => Some(match *lit {
+                ast::Lit::IntLit(value) => {
+                    if value > i64::MAX as u64 {
+                        self.report(Diag::IntLiteralTooLarge { span: expr.span });
+                    }
+                    Ty::IntTy
+                }
+                ast::Lit::FloatLit(_) => Ty::FloatTy,
+                ast::Lit::StrLit(_) => Ty::StrTy,
+                ast::Lit::BoolLit(_) => Ty::BoolTy,
+            }),
+            ExprKind::Ident(ref ident) => self.ident_ty(ident),
+            ExprKind::Paren(ref expr) => self.check_expr(expr),
+            ExprKind::Cast(to, ref operand) => {
+                let from = self.check_expr(operand)?;
+                let is_valid = match to {
+                    Ty::IntTy | Ty::FloatTy => from.is_numeric() || from == Ty::StrTy,
+                    Ty::StrTy => true,
+                    Ty::BoolTy => from == Ty::BoolTy,
+                };
+                if !is_valid {
+                    self.report(Diag::InvalidCast {
+                        span: expr.span,
+                        from,
+                        to,
+                    });
+                }
+                Some(to)
+            }
+            ExprKind::Call(ref func, ref args) => {
+                let ty = self.check_call(func, args)?;
+                if ty.is_none() {
+                    self.report(Diag::ProcedureInExpr { span: expr.span });
+                }
+                ty
+            }
+            ExprKind::Formatted(..) => {
+                self.report(Diag::MisplacedFormatSpec { span: expr.span });
+                None
+            }
+        }
+    }
+}
+
+/// The type of a binary operation's result, if the operator applies to
+/// operands of such types.
+fn binary_op_ty(op: ast::BinOp, lhs: Ty, rhs: Ty) -> Option<Ty> {
+    use ast::BinOp;
+    let both_int = lhs == Ty::IntTy && rhs == Ty::IntTy;
+    let both_numeric = lhs.is_numeric() && rhs.is_numeric();
+    let both_str = lhs == Ty::StrTy && rhs == Ty::StrTy;
+    match op {
+        BinOp::Add | BinOp::Sub | BinOp::Mult | BinOp::Pow if both_int => Some(Ty::IntTy),
+        BinOp::Add | BinOp::Sub | BinOp::Mult | BinOp::Pow if both_numeric => Some(Ty::FloatTy),
+        BinOp::Add if both_str => Some(Ty::StrTy),
+        BinOp::Div if both_numeric => Some(Ty::FloatTy),
+        BinOp::IntDiv | BinOp::Mod | BinOp::Shl | BinOp::Shr if both_int => Some(Ty::IntTy),
+        BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor if both_int => Some(Ty::IntTy),
+        BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor if lhs == Ty::BoolTy && rhs == Ty::BoolTy => {
+            Some(Ty::BoolTy)
+        }
+        BinOp::Eq | BinOp::Ne if lhs == rhs || both_numeric => Some(Ty::BoolTy),
+        BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge if both_numeric || both_str => {
+            Some(Ty::BoolTy)
+        }
+        _ => None,
+    }
+}
+
+fn pat_ty(pat: &ast::Pat) -> Ty {
+    match pat.kind {
+        ast::PatKind::Int(_) | ast::PatKind::Range(..) => Ty::IntTy,
+        ast::PatKind::Lit(ast::Lit::IntLit(_)) => Ty::IntTy,
+        ast::PatKind::Lit(ast::Lit::FloatLit(_)) => Ty::FloatTy,
+        ast::PatKind::Lit(ast::Lit::StrLit(_)) => Ty::StrTy,
+        ast::PatKind::Lit(ast::Lit::BoolLit(_)) => Ty::BoolTy,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TypeChecker;
+    use ast::{BinOp, Ty};
+    use errors::{self, Diag};
+    use parser::Parser;
+    use scanner::{Scanner, WordStream};
+    use source_map::{BytePos, SourceFile, Span};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Type checks a program, returning whether it's well-typed and the
+    /// diagnostics reported.
+    fn check(src: &str) -> (bool, Vec<Diag>) {
+        let diags = Rc::new(RefCell::new(vec![]));
+        let reported = diags.clone();
+        let handler =
+            errors::Handler::with_emitter(move |diag, _| reported.borrow_mut().push(diag));
+        let file = Rc::new(SourceFile::new("test".into(), src.into()));
+        let word_stream = WordStream::new(Scanner::new(file), &handler);
+        let mut parser = Parser::new(word_stream);
+        let program = parser.parse_program().unwrap();
+        let is_well_typed =
+            TypeChecker::new(&handler, parser.ident_table()).check_program(&program);
+        handler.flush();
+        let diags = diags.borrow_mut().drain(..).collect();
+        (is_well_typed, diags)
+    }
+
+    fn sp(start: usize, end: usize) -> Span {
+        Span {
+            start: BytePos(start),
+            end: BytePos(end),
+        }
+    }
+
+    #[test]
+    fn test_well_typed_program() {
+        let (is_well_typed, diags) = check(
+            "program p; let i: int; let f: float; let b: bool;
+             i = 7 div 2 + int(3.9); f = i / 2 + 1; b = f > i & !(i == 3);
+             while b { b = false; }",
+        );
+        assert!(is_well_typed);
+        assert_eq!(Vec::<Diag>::new(), diags);
+    }
+
+    #[test]
+    fn test_int_promotion() {
+        let (is_well_typed, _) = check("program p; let f: float; f = 1 + 2.5 * 2; f = 3;");
+        assert!(is_well_typed);
+    }
+
+    #[test]
+    fn test_lossy_implicit_conversion() {
+        let (is_well_typed, diags) = check("program p; let i: int; i = 1 + 2.5;");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![Diag::LossyImplicitConversion {
+                span: sp(27, 34),
+                from: Ty::FloatTy,
+                to: Ty::IntTy,
+            }],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_mismatched_types() {
+        let (is_well_typed, diags) = check("program p; let i: int; if i { i = \"a\"; }");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![
+                Diag::MismatchedTypes {
+                    span: sp(26, 27),
+                    expected: Ty::BoolTy,
+                    found: Ty::IntTy,
+                },
+                Diag::MismatchedTypes {
+                    span: sp(34, 37),
+                    expected: Ty::IntTy,
+                    found: Ty::StrTy,
+                },
+            ],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_invalid_operands() {
+        let (is_well_typed, diags) = check("program p; let f: float; f = 2.0 mod 1;");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![Diag::InvalidBinaryOperands {
+                span: sp(29, 38),
+                op: BinOp::Mod,
+                lhs: Ty::FloatTy,
+                rhs: Ty::IntTy,
+            }],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_undeclared_variable() {
+        let (is_well_typed, diags) = check("program p; let i: int; i = j + 1;");
+        assert!(!is_well_typed);
+        assert_eq!(vec![Diag::UndeclaredVariable { span: sp(27, 28) }], diags);
+    }
+
+    #[test]
+    fn test_int_literal_too_large() {
+        let (is_well_typed, _) = check("program p; let i: int; i = -9223372036854775808;");
+        assert!(is_well_typed);
+        let (is_well_typed, diags) = check("program p; let i: int; i = 9223372036854775808;");
+        assert!(!is_well_typed);
+        assert_eq!(vec![Diag::IntLiteralTooLarge { span: sp(27, 46) }], diags);
+        let (is_well_typed, diags) = check("program p; let i: int; i = -9223372036854775809;");
+        assert!(!is_well_typed);
+        assert_eq!(vec![Diag::IntLiteralTooLarge { span: sp(28, 47) }], diags);
+    }
+
+    #[test]
+    fn test_casts() {
+        let (is_well_typed, _) = check(
+            "program p; let i: int; let f: float; let s: str;
+             i = int(\"42\"); f = float(s); s = str(f) ; s = str(true);",
+        );
+        assert!(is_well_typed);
+
+        let (is_well_typed, diags) = check("program p; let i: int; i = int(true);");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![Diag::InvalidCast {
+                span: sp(27, 36),
+                from: Ty::BoolTy,
+                to: Ty::IntTy,
+            }],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_string_operations() {
+        let (is_well_typed, diags) = check(
+            "program p; let s: str; let b: bool; let i: int;
+             s = upper(s + \"!\"); b = s < \"b\" & s != lower(s);
+             i = len(s) + find(s, \"x\"); s = substr(s, 1, i) + char_at(s, 0);",
+        );
+        assert_eq!(Vec::<Diag>::new(), diags);
+        assert!(is_well_typed);
+
+        let (is_well_typed, diags) = check("program p; let s: str; s = s - \"a\";");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![Diag::InvalidBinaryOperands {
+                span: sp(27, 34),
+                op: BinOp::Sub,
+                lhs: Ty::StrTy,
+                rhs: Ty::StrTy,
+            }],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        let (is_well_typed, diags) = check("program p; let i: int; i = len(i);");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![Diag::MismatchedTypes {
+                span: sp(31, 32),
+                expected: Ty::StrTy,
+                found: Ty::IntTy,
+            }],
+            diags
+        );
+
+        let (is_well_typed, diags) = check("program p; let s: str; s = char_at(s);");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![Diag::WrongArgCount {
+                span: sp(27, 34),
+                expected: 2,
+                found: 1,
+            }],
+            diags
+        );
+
+        let (is_well_typed, diags) = check("program p; let i: int; i = size(\"a\");");
+        assert!(!is_well_typed);
+        assert_eq!(vec![Diag::UndefinedFunction { span: sp(27, 31) }], diags);
+    }
+
+    #[test]
+    fn test_io_calls() {
+        let (is_well_typed, diags) = check(
+            "program p; let i: int; let f: float; let s: str;
+             read(i, f); readln(s); write(\"a\", i:4, f:8:2); writeln(); writeln(s:i + 1);",
+        );
+        assert_eq!(Vec::<Diag>::new(), diags);
+        assert!(is_well_typed);
+
+        let (is_well_typed, diags) = check("program p; let i: int; read(i + 1); write(i:1:2);");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![
+                Diag::NotAssignable { span: sp(28, 33) },
+                Diag::PrecisionOnNonFloat {
+                    span: sp(46, 47),
+                    ty: Ty::IntTy,
+                },
+            ],
+            diags
+        );
+
+        let (is_well_typed, diags) = check("program p; let i: int; i = write(i); i = len(i:2);");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![
+                Diag::ProcedureInExpr { span: sp(27, 35) },
+                Diag::MisplacedFormatSpec { span: sp(45, 48) },
+            ],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_consts() {
+        let (is_well_typed, diags) = check(
+            "program p; const n: int = 2; const f: float = n * 1.5 + len(\"ab\"); let x: float;
+             x = f * n;",
+        );
+        assert_eq!(Vec::<Diag>::new(), diags);
+        assert!(is_well_typed);
+
+        let (is_well_typed, diags) =
+            check("program p; let x: int; const n: int = x + 1; n = 2; for n = 1 to 2 { x = n; }");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![
+                Diag::NonConstantExpr { span: sp(38, 39) },
+                Diag::AssignToConstant { span: sp(45, 46) },
+                Diag::AssignToConstant { span: sp(56, 57) },
+            ],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_case_pattern_types() {
+        let (is_well_typed, diags) =
+            check("program p; let i: int; case i of \"a\": { i = 0; } 1, true: { i = 1; } else {}");
+        assert!(!is_well_typed);
+        assert_eq!(
+            vec![
+                Diag::MismatchedTypes {
+                    span: sp(33, 36),
+                    expected: Ty::IntTy,
+                    found: Ty::StrTy,
+                },
+                Diag::MismatchedTypes {
+                    span: sp(52, 56),
+                    expected: Ty::IntTy,
+                    found: Ty::BoolTy,
+                },
+            ],
+            diags
+        );
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        let (is_well_typed, diags) = check(
+            "program p; let i: int; let j: int; let k: int; let s: str;
+             i = 1; for k = 1 to 2 { writeln(); } readln(s);",
+        );
+        assert!(is_well_typed);
+        assert_eq!(vec![Diag::UnusedVariable { span: sp(27, 28) }], diags);
+    }
+}
@@ -0,0 +1,443 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// The exit code, stdout and stderr of running `blaise` with some stdin.
+fn blaise(args: &[&str], stdin: &str) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_blaise"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // `blaise` may exit, e.g. on a usage error, before reading its stdin.
+    if let Err(err) = child.stdin.take().unwrap().write_all(stdin.as_bytes()) {
+        assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+    }
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_cli_run() {
+    let src = "program p; writeln(6 * 7);";
+    assert_eq!(
+        (0, "42\n".to_owned(), String::new()),
+        blaise(&["run", "-"], src)
+    );
+
+    let dir = temp_dir("blaise-cli-run");
+    let path = dir.join("p.bl");
+    fs::write(
+        &path,
+        "program p; let s: str; readln(s); writeln(s, \"!\");",
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+    assert_eq!(
+        (0, "hi!\n".to_owned(), String::new()),
+        blaise(&["run", path], "hi\n")
+    );
+}
+
+#[test]
+fn test_cli_check() {
+    assert_eq!(
+        (0, String::new(), String::new()),
+        blaise(&["check", "-"], "program p; let x: int; x = 1;")
+    );
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            "<stdin>:1:12: error[B0016]: use of undeclared variable\n".to_owned()
+        ),
+        blaise(&["check", "-"], "program p; x = 1;")
+    );
+    // Errors at the end of the input are located on the last line.
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            "<stdin>:3:6: error[B0007]: expected `;`, but got `<end of file>`\n\
+             <stdin>:3:6: help: add `;` here\n"
+                .to_owned()
+        ),
+        blaise(&["check", "-"], "program p;\nlet x: int;\nx = 1")
+    );
+    let (code, _, stderr) = blaise(&["run", "-"], "program p; writeln(1 div 0);");
+    assert_eq!(1, code);
+    assert!(stderr.contains("error[B0022]: division by zero"));
+}
+
+#[test]
+fn test_cli_usage_errors() {
+    for args in &[
+        &[][..],
+        &["check"],
+        &["check", "a.bl", "b.bl"],
+        &["--nope", "a.bl"],
+        &["build", "--emit=c", "a.bl"],
+        &["build", "--emit=wat", "a.bl"],
+        &["run", "-o", "out", "a.bl"],
+        &["check", "--check", "a.bl"],
+        &["repl", "a.bl"],
+        &["check", "--error-format=xml", "a.bl"],
+        &["repl", "--error-format=json"],
+        &["check", "-A", "B0016", "a.bl"],
+        &["check", "-A", "nope", "a.bl"],
+        &["--explain", "B9999"],
+        &["fix"],
+    ] {
+        let (code, stdout, stderr) = blaise(args, "");
+        assert_eq!(2, code, "exit code of {:?}", args);
+        assert_eq!("", stdout);
+        assert!(stderr.starts_with("error: "), "stderr of {:?}", args);
+    }
+
+    let (_, _, stderr) = blaise(&["build", "--emit=asm", "a.bl"], "");
+    assert!(stderr.starts_with("error: `--emit=asm` is out of scope"));
+    let (code, _, stderr) = blaise(&["bulid", "a.bl"], "");
+    assert_eq!(2, code);
+    assert!(stderr.starts_with(
+        "error: unknown command `bulid`, expected one of `run`, `check`, `build`, `tokens`, \
+         `ast`, `cst`, `fmt`, `fix`, `repl`\n"
+    ));
+
+    let (code, stdout, _) = blaise(&["--help"], "");
+    assert_eq!(0, code);
+    assert!(stdout.starts_with("Usage: blaise"));
+}
+
+#[test]
+fn test_cli_error_format_json() {
+    let src = "program p;\nlet x: int;\ncase x > 0 of true: { x = 1; }\n";
+    assert_eq!(
+        (
+            0,
+            String::new(),
+            "{\"severity\":\"warning\",\"code\":\"B0012\",\
+             \"message\":\"non-exhaustive case: `false` not covered\",\
+             \"spans\":[{\"file\":\"<stdin>\",\"byte_start\":23,\"byte_end\":27,\
+             \"line_start\":3,\"col_start\":1,\"line_end\":3,\"col_end\":5,\
+             \"is_primary\":true,\"label\":null}],\
+             \"notes\":[\"add an arm for `false` or an `else` block to cover it\"],\
+             \"fixes\":[]}\n"
+                .to_owned()
+        ),
+        blaise(&["check", "--error-format=json", "-"], src)
+    );
+
+    // The end of a file without a trailing line break is on its last line.
+    let (code, _, stderr) = blaise(
+        &["check", "--error-format=json", "-"],
+        "program p;\nlet x: int;\nx = 1",
+    );
+    assert_eq!(1, code);
+    assert!(stderr.contains(
+        "\"fixes\":[{\"message\":\"add `;` here\",\
+         \"span\":{\"file\":\"<stdin>\",\"byte_start\":28,\"byte_end\":28,\
+         \"line_start\":3,\"col_start\":6,\"line_end\":3,\"col_end\":6,\
+         \"is_primary\":false,\"label\":null},\
+         \"replacement\":\";\",\"applicability\":\"machine-applicable\"}]"
+    ));
+
+    // Secondary spans follow the primary one.
+    let src = "program p;\nlet x: int;\ncase true of true: { x = 1; } true: { x = 2; }\n";
+    let (code, _, stderr) = blaise(&["check", "--error-format", "json", "-"], src);
+    assert_eq!(1, code);
+    assert!(stderr.contains(
+        "\"spans\":[{\"file\":\"<stdin>\",\"byte_start\":53,\"byte_end\":57,\
+         \"line_start\":3,\"col_start\":31,\"line_end\":3,\"col_end\":35,\
+         \"is_primary\":true,\"label\":null},\
+         {\"file\":\"<stdin>\",\"byte_start\":36,\"byte_end\":40,\
+         \"line_start\":3,\"col_start\":14,\"line_end\":3,\"col_end\":18,\
+         \"is_primary\":false,\"label\":\"first covered here\"}]"
+    ));
+
+    // Runtime errors point at the expression which failed.
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            "{\"severity\":\"error\",\"code\":\"B0022\",\"message\":\"division by zero\",\
+             \"spans\":[{\"file\":\"<stdin>\",\"byte_start\":19,\"byte_end\":26,\
+             \"line_start\":1,\"col_start\":20,\"line_end\":1,\"col_end\":27,\
+             \"is_primary\":true,\"label\":null}],\"notes\":[],\"fixes\":[]}\n"
+                .to_owned()
+        ),
+        blaise(
+            &["run", "--error-format=json", "-"],
+            "program p; writeln(1 div 0);"
+        )
+    );
+}
+
+#[test]
+fn test_cli_levels() {
+    let src = "program p;\nlet x: int;\ncase x > 0 of true: { x = 1; }\n";
+    let message = "non-exhaustive case: `false` not covered\n\
+                   <stdin>:3:1: note: add an arm for `false` or an `else` block to cover it\n";
+    assert_eq!(
+        (
+            0,
+            String::new(),
+            format!("<stdin>:3:1: warning[B0012]: {}", message)
+        ),
+        blaise(&["check", "-"], src)
+    );
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            format!("<stdin>:3:1: error[B0012]: {}", message)
+        ),
+        blaise(&["check", "-D", "warnings", "-"], src)
+    );
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            format!("<stdin>:3:1: error[B0012]: {}", message)
+        ),
+        blaise(&["check", "-D", "non-exhaustive-case", "-"], src)
+    );
+    for code in &["B0012", "non-exhaustive-case"] {
+        assert_eq!(
+            (0, String::new(), String::new()),
+            blaise(&["check", "-D", "warnings", "-A", code, "-"], src)
+        );
+    }
+
+    let (code, stdout, _) = blaise(&["--explain", "B0012"], "");
+    assert_eq!(0, code);
+    assert!(stdout.starts_with("B0012 (non-exhaustive-case)\n\nA `case` statement"));
+
+    let src = "program p;\nlet x: int;\nwriteln(1);\n";
+    assert_eq!(
+        (
+            0,
+            "1\n".to_owned(),
+            "<stdin>:2:5: warning[B0043]: unused variable\n".to_owned()
+        ),
+        blaise(&["run", "-"], src)
+    );
+    assert_eq!(
+        (0, "1\n".to_owned(), String::new()),
+        blaise(&["run", "-A", "unused-variable", "-"], src)
+    );
+}
+
+#[test]
+fn test_cli_max_errors() {
+    let src = "program p;\nlet x: int;\nx = \"a\";\nx = \"b\";\nx = \"c\";\n";
+    let error = |line| {
+        format!(
+            "<stdin>:{}:5: error[B0017]: mismatched types: expected `int`, but found `str`\n",
+            line
+        )
+    };
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            format!("{}{}{}", error(3), error(4), error(5))
+        ),
+        blaise(&["check", "-"], src)
+    );
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            format!("{}{}error[B0001]: too many errors\n", error(3), error(4))
+        ),
+        blaise(&["check", "--max-errors=2", "-"], src)
+    );
+    let (code, _, stderr) = blaise(&["check", "--max-errors", "0", "-"], src);
+    assert_eq!(2, code);
+    assert!(stderr.starts_with("error: invalid `--max-errors` `0`"));
+}
+
+#[test]
+fn test_cli_limits() {
+    let src = "program p;\nlet i: int;\nwhile true {\n  i = i + 1;\n}\n";
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            "<stdin>:4:3: error[B0037]: execution stopped after 100 steps\n".to_owned()
+        ),
+        blaise(&["run", "--max-steps", "100", "-"], src)
+    );
+    let (code, _, stderr) = blaise(&["run", "--timeout=0.05", "-"], src);
+    assert_eq!(1, code);
+    assert!(stderr.ends_with(": error[B0037]: execution stopped after 50ms\n"));
+    let (code, _, stderr) = blaise(&["check", "--max-steps=100", "-"], src);
+    assert_eq!(2, code);
+    assert!(stderr.starts_with("error: `--max-steps`, `--max-depth`"));
+    let (code, _, stderr) = blaise(&["run", "--timeout=-1", "-"], src);
+    assert_eq!(2, code);
+    assert!(stderr.starts_with("error: invalid `--timeout` `-1`"));
+}
+
+#[test]
+fn test_cli_fix() {
+    let dir = temp_dir("blaise-cli-fix");
+    let path = dir.join("p.bl");
+    fs::write(&path, "program p\nlet x: int\nx = 1\nwriteln(x)\n").unwrap();
+    let path = path.to_str().unwrap();
+    assert_eq!(
+        (0, String::new(), String::new()),
+        blaise(&["fix", path], "")
+    );
+    assert_eq!(
+        "program p;\nlet x: int;\nx = 1;\nwriteln(x);\n",
+        fs::read_to_string(path).unwrap()
+    );
+
+    // Suggestions which may be wrong are only shown.
+    let src = "program p; let x: int; whlie x < 3 { x = x + 1 }";
+    assert_eq!(
+        (
+            1,
+            src.to_owned(),
+            "<stdin>:1:30: error[B0007]: expected `=`, but got identifier\n\
+             <stdin>:1:24: help: did you mean `while`?\n"
+                .to_owned()
+        ),
+        blaise(&["fix", "-"], src)
+    );
+}
+
+#[test]
+fn test_cli_io_errors() {
+    let dir = temp_dir("blaise-cli-io-errors");
+    let missing = dir.join("missing.bl");
+    let missing = missing.to_str().unwrap();
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            format!(
+                "error: couldn't read `{}`: no such file or directory\n",
+                missing
+            )
+        ),
+        blaise(&["check", missing], "")
+    );
+
+    let out = dir.join("missing").join("out.txt");
+    let out = out.to_str().unwrap();
+    assert_eq!(
+        (
+            1,
+            String::new(),
+            format!(
+                "error: couldn't write `{}`: no such file or directory\n",
+                out
+            )
+        ),
+        blaise(&["tokens", "-", "-o", out], "program p;")
+    );
+}
+
+#[test]
+fn test_cli_emit() {
+    let src = "program p;\nwriteln(1);\n";
+    assert_eq!(
+        (
+            0,
+            "1:1: Program 0..7 \"program\"\n\
+             1:9: Ident 8..9 \"p\"\n\
+             1:10: Semi 9..10 \";\"\n\
+             2:1: Ident 11..18 \"writeln\"\n\
+             2:8: OpenParen 18..19 \"(\"\n\
+             2:9: NumConst { is_float: false } 19..20 \"1\"\n\
+             2:10: CloseParen 20..21 \")\"\n\
+             2:11: Semi 21..22 \";\"\n"
+                .to_owned(),
+            String::new()
+        ),
+        blaise(&["tokens", "-"], src)
+    );
+
+    let dir = temp_dir("blaise-cli-emit");
+    let out = dir.join("ast.txt");
+    let (code, stdout, _) = blaise(
+        &["build", "--emit=ast", "-o", out.to_str().unwrap(), "-"],
+        src,
+    );
+    assert_eq!((0, ""), (code, stdout.as_str()));
+    let ast = fs::read_to_string(&out).unwrap();
+    assert!(ast.starts_with("Program {\n"));
+    assert_eq!(ast, blaise(&["ast", "-"], src).1);
+    // Without a command, a file's syntax tree is written.
+    assert_eq!(ast, blaise(&["-"], src).1);
+
+    let (code, cst, _) = blaise(&["cst", "-"], src);
+    assert_eq!(0, code);
+    assert!(cst.starts_with("Program 0..23\n  Program 0..7 \"program\"\n"));
+    assert!(cst.ends_with("  Whitespace \"\\n\"\n  Eof 23..23 \"\"\n"));
+    assert_eq!(cst, blaise(&["build", "--emit=cst", "-"], src).1);
+
+    assert_eq!(
+        (
+            0,
+            "program p\n\nbb0:\n    call writeln(1)\n    return\n".to_owned(),
+            String::new()
+        ),
+        blaise(&["build", "--emit=ir", "-"], src)
+    );
+
+    // Unlike `ast`, `build` checks the program first.
+    let src = "program p; x = 1;";
+    assert_eq!(0, blaise(&["ast", "-"], src).0);
+    assert_eq!((1, String::new()), {
+        let (code, stdout, _) = blaise(&["build", "-"], src);
+        (code, stdout)
+    });
+}
+
+#[test]
+fn test_cli_fmt() {
+    let dir = temp_dir("blaise-cli-fmt");
+    let path = dir.join("p.bl");
+    fs::write(&path, "program p;   writeln(1);").unwrap();
+    let path = path.to_str().unwrap();
+
+    assert_eq!(
+        (1, format!("{}: not formatted\n", path), String::new()),
+        blaise(&["fmt", "--check", path], "")
+    );
+    assert_eq!(
+        (0, String::new(), String::new()),
+        blaise(&["fmt", path], "")
+    );
+    assert_eq!(
+        "program p;\n\nwriteln(1);\n",
+        fs::read_to_string(path).unwrap()
+    );
+    assert_eq!(
+        (0, String::new(), String::new()),
+        blaise(&["fmt", "--check", path], "")
+    );
+
+    assert_eq!(
+        (0, "program p;\n\nlet x: int;\n".to_owned(), String::new()),
+        blaise(&["fmt", "-"], "program p; let x: int;")
+    );
+}
@@ -0,0 +1,366 @@
+extern crate blaise;
+
+use blaise::json::Json;
+use blaise::lsp::{self, path_to_uri};
+use std::env;
+use std::fs;
+use std::io::BufReader;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// A client driving a `blaise-lsp` process over its stdio.
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: usize,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_blaise-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Client {
+            child,
+            stdin,
+            stdout,
+            next_id: 1,
+        }
+    }
+
+    fn send(&mut self, message: Json) {
+        lsp::write_message(&mut self.stdin, &message).unwrap();
+    }
+
+    fn receive(&mut self) -> Json {
+        let content = lsp::read_message(&mut self.stdout).unwrap().unwrap();
+        Json::parse(&content).unwrap()
+    }
+
+    /// Sends a request and returns the result of its response.
+    fn request(&mut self, method: &str, params: Json) -> Json {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("id", Json::from(id)),
+            ("method", Json::from(method)),
+            ("params", params),
+        ]));
+        let response = self.receive();
+        assert_eq!(Some(id), response.get("id").and_then(Json::as_usize));
+        response.get("result").unwrap().clone()
+    }
+
+    fn notify(&mut self, method: &str, params: Json) {
+        self.send(Json::object(vec![
+            ("jsonrpc", Json::from("2.0")),
+            ("method", Json::from(method)),
+            ("params", params),
+        ]));
+    }
+
+    /// Waits for the diagnostics of a document, returning their messages.
+    fn diagnostics(&mut self) -> Vec<(usize, usize, String)> {
+        let notification = self.receive();
+        assert_eq!(
+            Some("textDocument/publishDiagnostics"),
+            notification.get("method").and_then(Json::as_str)
+        );
+        let params = notification.get("params").unwrap();
+        params
+            .get("diagnostics")
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .map(|diag| {
+                let start = diag.get("range").and_then(|range| range.get("start"));
+                let number = |key| {
+                    start
+                        .and_then(|start| start.get(key))
+                        .and_then(Json::as_usize)
+                };
+                (
+                    number("line").unwrap(),
+                    number("character").unwrap(),
+                    diag.get("message")
+                        .and_then(Json::as_str)
+                        .unwrap()
+                        .to_owned(),
+                )
+            })
+            .collect()
+    }
+}
+
+fn position(uri: &str, line: usize, character: usize) -> Json {
+    Json::object(vec![
+        ("textDocument", Json::object(vec![("uri", Json::from(uri))])),
+        (
+            "position",
+            Json::object(vec![
+                ("line", Json::from(line)),
+                ("character", Json::from(character)),
+            ]),
+        ),
+    ])
+}
+
+fn range(start: (usize, usize), end: (usize, usize)) -> Json {
+    let position = |(line, character): (usize, usize)| {
+        Json::object(vec![
+            ("line", Json::from(line)),
+            ("character", Json::from(character)),
+        ])
+    };
+    Json::object(vec![("start", position(start)), ("end", position(end))])
+}
+
+#[test]
+fn test_lsp_session() {
+    let dir = env::temp_dir().join("blaise-lsp-session");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("shapes.bl"),
+        "unit shapes;\nconst sides: int = 4;\n",
+    )
+    .unwrap();
+    let uri = path_to_uri(&dir.join("main.bl"));
+    let unit_uri = path_to_uri(&dir.join("shapes.bl"));
+
+    let mut client = Client::start();
+    let result = client.request("initialize", Json::object(vec![]));
+    let sync = result
+        .get("capabilities")
+        .and_then(|capabilities| capabilities.get("textDocumentSync"));
+    assert_eq!(
+        Some(2),
+        sync.and_then(|sync| sync.get("change"))
+            .and_then(Json::as_usize)
+    );
+    client.notify("initialized", Json::object(vec![]));
+
+    let text = "program main;\nuses shapes;\nlet n: int;\nn = sides * 2.0;\n";
+    client.notify(
+        "textDocument/didOpen",
+        Json::object(vec![(
+            "textDocument",
+            Json::object(vec![
+                ("uri", Json::from(uri.as_str())),
+                ("languageId", Json::from("blaise")),
+                ("version", Json::from(1)),
+                ("text", Json::from(text)),
+            ]),
+        )]),
+    );
+    let diags = client.diagnostics();
+    assert_eq!(1, diags.len());
+    assert_eq!((3, 4), (diags[0].0, diags[0].1));
+
+    // Replaces `2.0` with `2`, which fixes the program.
+    client.notify(
+        "textDocument/didChange",
+        Json::object(vec![
+            (
+                "textDocument",
+                Json::object(vec![
+                    ("uri", Json::from(uri.as_str())),
+                    ("version", Json::from(2)),
+                ]),
+            ),
+            (
+                "contentChanges",
+                Json::Array(vec![Json::object(vec![
+                    ("range", range((3, 12), (3, 15))),
+                    ("text", Json::from("2")),
+                ])]),
+            ),
+        ]),
+    );
+    assert_eq!(Vec::<(usize, usize, String)>::new(), client.diagnostics());
+
+    // A syntax error is reported as well.
+    client.notify(
+        "textDocument/didChange",
+        Json::object(vec![
+            (
+                "textDocument",
+                Json::object(vec![
+                    ("uri", Json::from(uri.as_str())),
+                    ("version", Json::from(3)),
+                ]),
+            ),
+            (
+                "contentChanges",
+                Json::Array(vec![Json::object(vec![
+                    ("range", range((3, 2), (3, 3))),
+                    ("text", Json::from("+")),
+                ])]),
+            ),
+        ]),
+    );
+    let diags = client.diagnostics();
+    assert_eq!(1, diags.len());
+    assert_eq!((3, 2), (diags[0].0, diags[0].1));
+
+    // Names are still completed while the document can't be parsed.
+    let items = client.request("textDocument/completion", position(&uri, 3, 0));
+    let labels = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|item| item.get("label").and_then(Json::as_str))
+        .collect::<Vec<_>>();
+    for label in &["while", "writeln", "n", "sides", "shapes"] {
+        assert!(labels.contains(label), "missing completion `{}`", label);
+    }
+
+    client.notify(
+        "textDocument/didChange",
+        Json::object(vec![
+            (
+                "textDocument",
+                Json::object(vec![
+                    ("uri", Json::from(uri.as_str())),
+                    ("version", Json::from(4)),
+                ]),
+            ),
+            (
+                "contentChanges",
+                Json::Array(vec![Json::object(vec![("text", Json::from(text))])]),
+            ),
+        ]),
+    );
+    assert_eq!(1, client.diagnostics().len());
+
+    let hover = client.request("textDocument/hover", position(&uri, 3, 0));
+    assert_eq!(
+        Some("let n: int"),
+        hover
+            .get("contents")
+            .and_then(|contents| contents.get("value"))
+            .and_then(Json::as_str)
+    );
+    let hover = client.request("textDocument/hover", position(&uri, 3, 6));
+    assert_eq!(
+        Some("const sides: int"),
+        hover
+            .get("contents")
+            .and_then(|contents| contents.get("value"))
+            .and_then(Json::as_str)
+    );
+
+    let definition = client.request("textDocument/definition", position(&uri, 3, 0));
+    assert_eq!(
+        Some(uri.as_str()),
+        definition.get("uri").and_then(Json::as_str)
+    );
+    assert_eq!(Some(&range((2, 4), (2, 5))), definition.get("range"));
+    let definition = client.request("textDocument/definition", position(&uri, 3, 6));
+    assert_eq!(
+        Some(unit_uri.as_str()),
+        definition.get("uri").and_then(Json::as_str)
+    );
+    assert_eq!(Some(&range((1, 6), (1, 11))), definition.get("range"));
+    let definition = client.request("textDocument/definition", position(&uri, 1, 5));
+    assert_eq!(Some(&range((0, 5), (0, 11))), definition.get("range"));
+
+    let symbols = client.request("textDocument/documentSymbol", position(&uri, 0, 0));
+    let program = &symbols.as_array().unwrap()[0];
+    assert_eq!(Some("main"), program.get("name").and_then(Json::as_str));
+    let children = program.get("children").and_then(Json::as_array).unwrap();
+    assert_eq!(1, children.len());
+    assert_eq!(Some("n"), children[0].get("name").and_then(Json::as_str));
+    assert_eq!(
+        Some("int"),
+        children[0].get("detail").and_then(Json::as_str)
+    );
+
+    client.notify(
+        "textDocument/didClose",
+        Json::object(vec![(
+            "textDocument",
+            Json::object(vec![("uri", Json::from(uri.as_str()))]),
+        )]),
+    );
+    assert_eq!(Vec::<(usize, usize, String)>::new(), client.diagnostics());
+
+    assert_eq!(Json::Null, client.request("shutdown", Json::Null));
+    client.notify("exit", Json::Null);
+    assert!(client.child.wait().unwrap().success());
+}
+
+#[test]
+fn test_lsp_invalid_documents() {
+    let uri = path_to_uri(&env::temp_dir().join("blaise-lsp-invalid").join("main.bl"));
+    let mut client = Client::start();
+    client.request("initialize", Json::object(vec![]));
+    client.notify("initialized", Json::object(vec![]));
+
+    // An empty block is fine, as typed before its statements.
+    client.notify(
+        "textDocument/didOpen",
+        Json::object(vec![(
+            "textDocument",
+            Json::object(vec![
+                ("uri", Json::from(uri.as_str())),
+                ("languageId", Json::from("blaise")),
+                ("version", Json::from(1)),
+                (
+                    "text",
+                    Json::from("program p;\nlet b: bool;\nwhile b { }\n"),
+                ),
+            ]),
+        )]),
+    );
+    assert_eq!(Vec::<(usize, usize, String)>::new(), client.diagnostics());
+
+    let change = |version: usize, text: &str| {
+        Json::object(vec![
+            (
+                "textDocument",
+                Json::object(vec![
+                    ("uri", Json::from(uri.as_str())),
+                    ("version", Json::from(version)),
+                ]),
+            ),
+            (
+                "contentChanges",
+                Json::Array(vec![Json::object(vec![("text", Json::from(text))])]),
+            ),
+        ])
+    };
+    client.notify(
+        "textDocument/didChange",
+        change(2, "program p;\nlet x: int;\nx = 99999999999999999999;\n"),
+    );
+    assert_eq!(
+        vec![(2, 4, "integer literal too large".to_owned())],
+        client.diagnostics()
+    );
+    client.notify(
+        "textDocument/didChange",
+        change(3, "program p;\nlet b: bool;\nwhile b { else }\n"),
+    );
+    assert_eq!(
+        vec![(2, 10, "expected `}`, but got `else`".to_owned())],
+        client.diagnostics()
+    );
+
+    assert_eq!(Json::Null, client.request("shutdown", Json::Null));
+    client.notify("exit", Json::Null);
+    assert!(client.child.wait().unwrap().success());
+}
+
+#[test]
+fn test_lsp_exit_without_shutdown() {
+    let mut client = Client::start();
+    client.request("initialize", Json::object(vec![]));
+    client.notify("exit", Json::Null);
+    assert!(!client.child.wait().unwrap().success());
+}